@@ -0,0 +1,121 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use haversine::{
+    distance_matrix, reference_haversine, simd::haversine_batch, Coord, HaversineData,
+    HaversineDataPoint, EARTH_RADIUS, X_HIGH, X_LOW, Y_HIGH, Y_LOW,
+};
+use rand::{distributions::Uniform, prelude::Distribution, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use serde_json::ser::PrettyFormatter;
+
+const PAIR_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn generate(n: usize) -> HaversineData {
+    let mut rng = ChaCha8Rng::seed_from_u64(n as u64);
+    let uniform_x = Uniform::new_inclusive(X_LOW, X_HIGH);
+    let uniform_y = Uniform::new_inclusive(Y_LOW, Y_HIGH);
+    let pairs = (0..n)
+        .map(|_| HaversineDataPoint {
+            p0: Coord {
+                lon: uniform_x.sample(&mut rng),
+                lat: uniform_y.sample(&mut rng),
+            },
+            p1: Coord {
+                lon: uniform_x.sample(&mut rng),
+                lat: uniform_y.sample(&mut rng),
+            },
+        })
+        .collect();
+    HaversineData { pairs }
+}
+
+fn to_json(data: &HaversineData) -> Vec<u8> {
+    let mut json = Vec::new();
+    let mut serializer =
+        serde_json::Serializer::with_formatter(&mut json, PrettyFormatter::with_indent(b"  "));
+    data.serialize(&mut serializer).expect("serialize data");
+    json
+}
+
+fn distance(c: &mut Criterion) {
+    let mut group = c.benchmark_group("distance");
+    for n in PAIR_COUNTS {
+        let data = generate(n);
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("reference", n), &data, |b, data| {
+            b.iter(|| {
+                data.pairs
+                    .iter()
+                    .map(|point| reference_haversine(black_box(point), EARTH_RADIUS))
+                    .sum::<f64>()
+            });
+        });
+        let mut out = vec![0.0; n];
+        group.bench_with_input(BenchmarkId::new("simd batch", n), &data, |b, data| {
+            b.iter(|| {
+                haversine_batch(black_box(&data.pairs), EARTH_RADIUS, &mut out);
+                out.iter().sum::<f64>()
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Points of the all-pairs matrices, their pairs are about `PAIR_COUNTS`
+const MATRIX_POINTS: [usize; 3] = [45, 142, 448];
+
+fn matrix(c: &mut Criterion) {
+    let mut group = c.benchmark_group("matrix");
+    for n in MATRIX_POINTS {
+        let points: Vec<Coord> = generate(n).pairs.iter().map(|point| point.p0).collect();
+        group.throughput(Throughput::Elements((n * (n - 1) / 2) as u64));
+        group.bench_with_input(BenchmarkId::new("naive", n), &points, |b, points| {
+            b.iter(|| {
+                let mut matrix = Vec::with_capacity(n * (n - 1) / 2);
+                for (i, &p0) in points.iter().enumerate() {
+                    for &p1 in &points[i + 1..] {
+                        let point = HaversineDataPoint { p0, p1 };
+                        matrix.push(reference_haversine(black_box(&point), EARTH_RADIUS));
+                    }
+                }
+                matrix
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("blocked", n), &points, |b, points| {
+            b.iter(|| distance_matrix(black_box(points), EARTH_RADIUS));
+        });
+    }
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for n in PAIR_COUNTS {
+        let json = to_json(&generate(n));
+        group.throughput(Throughput::Bytes(json.len() as u64));
+        group.bench_with_input(BenchmarkId::new("nom", n), &json, |b, json| {
+            b.iter(|| HaversineData::parse_from_json_slice(black_box(json)).expect("parse"));
+        });
+        group.bench_with_input(BenchmarkId::new("serde", n), &json, |b, json| {
+            b.iter(|| serde_json::from_slice::<HaversineData>(black_box(json)).expect("parse"));
+        });
+    }
+    group.finish();
+}
+
+fn serialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    for n in PAIR_COUNTS {
+        let data = generate(n);
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::new("serde_pretty", n), &data, |b, data| {
+            b.iter(|| to_json(black_box(data)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, distance, matrix, parse, serialize);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "ffi")]
+    generate_c_header();
+}
+
+#[cfg(feature = "ffi")]
+fn generate_c_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=HAVERSINE_UPDATE_HEADER");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets CARGO_MANIFEST_DIR");
+    let out_dir = std::env::var("OUT_DIR").expect("cargo sets OUT_DIR");
+    let config = cbindgen::Config::from_root_or_default(&crate_dir);
+    let bindings = cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("generate C bindings");
+    bindings.write_to_file(format!("{out_dir}/haversine.h"));
+    // the committed header is only rewritten on request, so that building
+    // with `ffi` leaves the tree clean
+    if std::env::var_os("HAVERSINE_UPDATE_HEADER").is_some() {
+        bindings.write_to_file(format!("{crate_dir}/include/haversine.h"));
+    }
+}
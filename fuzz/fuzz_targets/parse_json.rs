@@ -0,0 +1,16 @@
+#![no_main]
+
+use haversine::HaversineData;
+use libfuzzer_sys::fuzz_target;
+
+// Anything the nom parser accepts must survive a serde_json round trip unchanged.
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(data) = HaversineData::parse_from_json_slice(bytes) {
+        let mut points = data.pairs.iter().flat_map(|p| [p.p0, p.p1]);
+        if points.any(|c| !c.lon.is_finite() || !c.lat.is_finite()) {
+            return;
+        }
+        let json = serde_json::to_vec(&data).unwrap();
+        assert_eq!(HaversineData::parse_from_json_slice(&json), Ok(data));
+    }
+});
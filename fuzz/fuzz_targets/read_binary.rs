@@ -0,0 +1,13 @@
+#![no_main]
+
+use haversine::HaversineData;
+use libfuzzer_sys::fuzz_target;
+
+// Accepted binary input is canonical: writing it back reproduces the bytes.
+fuzz_target!(|bytes: &[u8]| {
+    if let Ok(data) = HaversineData::read_binary(bytes) {
+        let mut written = Vec::new();
+        data.write_binary(&mut written).unwrap();
+        assert_eq!(written, bytes);
+    }
+});
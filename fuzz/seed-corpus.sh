@@ -0,0 +1,21 @@
+#!/bin/sh
+# Seeds the fuzz corpora with valid and corrupted datasets from the generator.
+# Run from the repository root: ./fuzz/seed-corpus.sh
+set -e
+
+cargo build --release --bin haversine
+haversine="$PWD/target/release/haversine"
+# Only errors, the progress events would drown the output
+export RUST_LOG=error
+mkdir -p fuzz/corpus/parse_json fuzz/corpus/read_binary
+
+cd fuzz/corpus/parse_json
+for seed in 1 2 3; do
+    "$haversine" generate cluster "$seed" 8 --corrupt 16 > /dev/null
+    for file in data_8_corrupt*.json; do
+        mv "$file" "seed${seed}_$file"
+    done
+    "$haversine" convert data_8_flex.json "../read_binary/seed$seed.bin" > /dev/null
+    mv data_8_flex.json "seed${seed}_data_8_flex.json"
+    rm data_8_haveranswer.f64
+done
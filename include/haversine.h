@@ -0,0 +1,88 @@
+#ifndef HAVERSINE_H
+#define HAVERSINE_H
+
+/* Generated by cbindgen from src/ffi.rs with `HAVERSINE_UPDATE_HEADER=1 cargo build --features ffi`. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Status codes returned by the fallible FFI functions
+ */
+typedef enum HaversineStatus {
+  HaversineStatus_Ok = 0,
+  HaversineStatus_NullPointer = 1,
+  HaversineStatus_ParseError = 2,
+} HaversineStatus;
+
+/**
+ * A point, in degrees
+ */
+typedef struct Coord {
+  double lon;
+  double lat;
+} Coord;
+
+/**
+ * Two points, serialized flat as `{"x0": .., "y0": .., "x1": .., "y1": ..}`
+ * with `p0` as `x0`, `y0` and `p1` as `x1`, `y1`, and laid out as those four
+ * `f64`s in that order
+ */
+typedef struct HaversineDataPoint {
+  struct Coord p0;
+  struct Coord p1;
+} HaversineDataPoint;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Great circle distance between (`x0`, `y0`) and (`x1`, `y1`) in degrees,
+ * on a sphere of the given radius.
+ */
+double haversine_distance(double x0, double y0, double x1, double y1, double radius);
+
+/**
+ * Parses `{"pairs": [...]}` JSON into a newly allocated array of pairs.
+ * On success `*out_pairs` and `*out_len` describe the array, which must be
+ * released with `haversine_free_pairs`.
+ *
+ * # Safety
+ *
+ * `json` must be valid for reads of `len` bytes, `out_pairs` and `out_len`
+ * must be valid for writes.
+ */
+enum HaversineStatus haversine_parse_json(const uint8_t *json,
+                                          size_t len,
+                                          struct HaversineDataPoint **out_pairs,
+                                          size_t *out_len);
+
+/**
+ * Releases an array returned by `haversine_parse_json`.
+ *
+ * # Safety
+ *
+ * `pairs` and `len` must come from a single successful `haversine_parse_json`
+ * call and must not be used afterwards. Null `pairs` is ignored.
+ */
+void haversine_free_pairs(struct HaversineDataPoint *pairs, size_t len);
+
+/**
+ * Sum of the distances of `len` pairs on a sphere of the given radius.
+ * Returns 0 for null `pairs`.
+ *
+ * # Safety
+ *
+ * `pairs` must be valid for reads of `len` elements.
+ */
+double haversine_sum(const struct HaversineDataPoint *pairs, size_t len, double radius);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* HAVERSINE_H */
@@ -1,52 +1,665 @@
 #[cfg(feature = "perf")]
 use {
-    quote::quote,
-    syn::{parse_quote, Error, Expr, ItemFn, LitStr},
+    proc_macro2::TokenStream,
+    quote::{quote, ToTokens},
+    syn::{
+        meta::ParseNestedMeta,
+        parse::{Parse, ParseStream},
+        parse_quote,
+        punctuated::Punctuated,
+        visit_mut::{self, VisitMut},
+        Attribute, Block, Error, Expr, ExprClosure, ExprLit, Ident, ImplItem, Item, ItemFn,
+        ItemImpl, Lit, LitInt, LitStr, Meta, Stmt, Token, TraitItemFn,
+    },
 };
 
-/// Safety: Cannot be used in a multi-threaded context
+#[cfg(feature = "perf")]
+#[derive(Default)]
+struct InstrumentArgs {
+    /// overrides the `function_name!()` derived anchor name
+    name: Option<LitStr>,
+    /// cfg predicate gating the instrumentation
+    cfg: Option<Meta>,
+    /// only every Nth invocation is traced
+    sample: Option<LitInt>,
+    /// slice/str parameter whose `len()` is recorded as the processed byte count
+    bytes_from: Option<Ident>,
+    /// expression evaluated on entry, recorded as the processed byte count
+    bytes: Option<Expr>,
+    /// also count the page faults of every traced call
+    page_faults: bool,
+    /// time every traced call with serialized timer reads
+    precise_timer: bool,
+    /// give every instantiation of a generic fn its own anchor
+    concrete_generics: bool,
+}
+
+#[cfg(feature = "perf")]
+impl InstrumentArgs {
+    fn parse(&mut self, meta: &ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("name") {
+            self.name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("cfg") || meta.path.is_ident("when") {
+            self.cfg = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("sample") {
+            let sample: LitInt = meta.value()?.parse()?;
+            if sample.base10_parse::<usize>()? == 0 {
+                return Err(Error::new_spanned(sample, "sample rate must be at least 1"));
+            }
+            self.sample = Some(sample);
+            Ok(())
+        } else if meta.path.is_ident("bytes_from") {
+            self.bytes_from = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("bytes") {
+            let bytes: LitStr = meta.value()?.parse()?;
+            self.bytes = Some(bytes.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("page_faults") {
+            self.page_faults = true;
+            Ok(())
+        } else if meta.path.is_ident("precise_timer") {
+            self.precise_timer = true;
+            Ok(())
+        } else if meta.path.is_ident("generics") {
+            let generics: LitStr = meta.value()?.parse()?;
+            self.concrete_generics = match generics.value().as_str() {
+                "aggregate" => false,
+                "concrete" => true,
+                _ => {
+                    return Err(Error::new_spanned(
+                        generics,
+                        "expected `generics = \"aggregate\"` or `generics = \"concrete\"`",
+                    ))
+                }
+            };
+            Ok(())
+        } else {
+            Err(meta.error(
+                "unsupported instrument argument, expected one of `name = \"...\"`, `cfg = <predicate>`, `sample = <N>`, `bytes_from = <param>`, `bytes = \"<expr>\"`, `page_faults`, `precise_timer`, `generics = \"aggregate\"|\"concrete\"`",
+            ))
+        }
+    }
+}
+
+/// Hit of `trace_id` through a slot of its own, so that it indexes the traces
+/// instead of hashing the id (see `perf::trace::TraceSlot`)
+#[cfg(feature = "perf")]
+fn slotted_trace(trace_id: &TokenStream) -> Expr {
+    parse_quote! {{
+        static __TRACE_SLOT: perf::trace::TraceSlot = perf::trace::TraceSlot::new();
+        perf::ScopedTrace::new_in(&__TRACE_SLOT, #trace_id)
+    }}
+}
+
+#[cfg(feature = "perf")]
+fn fn_trace(generic_args: Option<&Expr>) -> Expr {
+    let trace_id = quote! { perf::trace::TraceId::new(__trace_name, perf::trace::TraceType::Fn) };
+    match generic_args {
+        Some(args) => slotted_trace(&quote! { #trace_id.with_generic_args(#args) }),
+        None => slotted_trace(&trace_id),
+    }
+}
+
+#[cfg(feature = "perf")]
+fn method_trace(method_name: &str, generic_args: Option<&Expr>) -> Expr {
+    let trace_id = quote! {
+        perf::trace::TraceId::new(__trace_name, perf::trace::TraceType::Method(#method_name))
+    };
+    match generic_args {
+        Some(args) => slotted_trace(&quote! { #trace_id.with_generic_args(#args) }),
+        None => slotted_trace(&trace_id),
+    }
+}
+
+#[cfg(feature = "perf")]
+fn has_param(input: &ItemFn, param: &Ident) -> bool {
+    input.sig.inputs.iter().any(|arg| match arg {
+        syn::FnArg::Typed(pat_type) => {
+            matches!(pat_type.pat.as_ref(), syn::Pat::Ident(pat) if pat.ident == *param)
+        }
+        syn::FnArg::Receiver(_) => false,
+    })
+}
+
+/// `instrument` on something else than a fn with a body
+#[cfg(feature = "perf")]
+fn not_a_fn(item: proc_macro::TokenStream, err: Error) -> Error {
+    match syn::parse::<TraitItemFn>(item) {
+        Ok(method) if method.default.is_none() => Error::new_spanned(
+            method.sig.fn_token,
+            format!(
+                "`instrument` needs a fn body, put it on the implementations of `{}` instead",
+                method.sig.ident
+            ),
+        ),
+        _ => err,
+    }
+}
+
+/// Usage: `#[perf::instrument]` or `#[perf::instrument(name = "parse_json")]`
+///
+/// Methods with a `self` receiver are named `Type::method` after the `Self` type, including
+/// default methods of traits, which are named after the implementing type. A trait method
+/// without a default body cannot be instrumented, only its implementations.
+///
+/// Instantiations of generic fns and methods of generic types share one anchor, unless
+/// `generics = "concrete"` is given to name them by their type arguments (`f<u8>`).
+/// On an `async fn` every poll of the body is timed, so hits count polls.
+///
+/// `#[perf::instrument(cfg = feature = "deep-profile")]` (or `when = ...`) only instruments
+/// the fn when the cfg predicate holds in the calling crate.
+///
+/// `#[perf::instrument(sample = 64)]` only traces every 64th invocation of a hot fn.
+///
+/// `#[perf::instrument(bytes_from = input)]` records `input.len()` as the bytes processed by
+/// each call, reported as bandwidth. `#[perf::instrument(bytes = "self.buf.len() * 8")]` records
+/// any integer expression instead, evaluated when the call starts.
+///
+/// `#[perf::instrument(page_faults)]` also counts the soft and hard page faults of each call.
+///
+/// `#[perf::instrument(precise_timer)]` times each call with serialized timer reads
+/// (`ScopedTrace::with_precise_timer`), which needs perf's `precise-timer` feature.
+///
+/// Safety: Only safe in a single-threaded program, unless perf is built with the `perf-mt`
+/// feature, which keeps the traces per thread.
 #[proc_macro_attribute]
 #[cfg(feature = "perf")]
+pub fn instrument(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let mut instrument_args = InstrumentArgs::default();
+    let args_parser = syn::meta::parser(|meta| instrument_args.parse(&meta));
+    syn::parse_macro_input!(args with args_parser);
+
+    let original = match syn::parse::<ItemFn>(item.clone()) {
+        Ok(original) => original,
+        Err(err) => return not_a_fn(item, err).to_compile_error().into(),
+    };
+    if let Some(constness) = &original.sig.constness {
+        return Error::new_spanned(constness, "`instrument` cannot time a `const fn`")
+            .to_compile_error()
+            .into();
+    }
+    let mut input = original.clone();
+    let method_name = input.sig.ident.to_string();
+    let type_params: Vec<&Ident> = input.sig.generics.type_params().map(|p| &p.ident).collect();
+    let generic_args: Option<Expr> = (instrument_args.concrete_generics && !type_params.is_empty())
+        .then(|| parse_quote! { ::std::any::type_name::<(#(#type_params,)*)>() });
+    // The name is bound up front so `function_name!()` isn't expanded inside a closure
+    let (trace_name, trace): (Expr, Expr) = match (instrument_args.name, input.sig.receiver()) {
+        (Some(name), _) => (parse_quote! { #name }, fn_trace(generic_args.as_ref())),
+        // `function_name!()` inside a method yields paths like `<a::Foo<_> as a::Tr>::m`
+        (None, Some(_)) if instrument_args.concrete_generics => (
+            parse_quote! { ::std::any::type_name::<Self>() },
+            method_trace(&method_name, generic_args.as_ref()),
+        ),
+        (None, Some(_)) => (
+            parse_quote! { perf::trace::strip_generics(::std::any::type_name::<Self>()) },
+            method_trace(&method_name, generic_args.as_ref()),
+        ),
+        (None, None) => (
+            parse_quote! { perf::function_name!() },
+            fn_trace(generic_args.as_ref()),
+        ),
+    };
+    if let (Some(sample), Some(asyncness)) = (&instrument_args.sample, &input.sig.asyncness) {
+        return Error::new_spanned(
+            quote! { #sample #asyncness },
+            "`sample` is not supported on async fns",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let trace: Expr = match &instrument_args.bytes_from {
+        Some(param) if input.sig.asyncness.is_some() => {
+            return Error::new_spanned(param, "`bytes_from` is not supported on async fns")
+                .to_compile_error()
+                .into();
+        }
+        Some(param) if !has_param(&input, param) => {
+            return Error::new_spanned(
+                param,
+                format!("`bytes_from` expects a `&[u8]` or `&str` parameter of `{method_name}`"),
+            )
+            .to_compile_error()
+            .into();
+        }
+        Some(param) => parse_quote! { #trace.with_bytes(#param.len() as u64) },
+        None => trace,
+    };
+    let trace: Expr = match &instrument_args.bytes {
+        Some(bytes) if instrument_args.bytes_from.is_some() => {
+            return Error::new_spanned(bytes, "`bytes` and `bytes_from` cannot be combined")
+                .to_compile_error()
+                .into();
+        }
+        Some(bytes) if input.sig.asyncness.is_some() => {
+            return Error::new_spanned(bytes, "`bytes` is not supported on async fns")
+                .to_compile_error()
+                .into();
+        }
+        Some(bytes) => parse_quote! {
+            #trace.with_bytes(::core::convert::TryFrom::try_from(#bytes).unwrap_or(u64::MAX))
+        },
+        None => trace,
+    };
+    let trace: Expr = if instrument_args.page_faults {
+        parse_quote! { #trace.with_faults() }
+    } else {
+        trace
+    };
+    let trace: Expr = if instrument_args.precise_timer {
+        parse_quote! { #trace.with_precise_timer() }
+    } else {
+        trace
+    };
+    if input.sig.asyncness.is_some() {
+        // Time every poll of the body rather than the construction of the future.
+        // The body of an `async fn` is itself a closure as far as `type_name` is concerned.
+        let block = &input.block;
+        *input.block = parse_quote! {{
+            let __trace_name = #trace_name.trim_end_matches("::{{closure}}");
+            perf::TracedFuture::new(move || #trace, async move #block).await
+        }};
+    } else if let Some(sample) = &instrument_args.sample {
+        let block = input.block.as_mut();
+        block.stmts.splice(
+            0..0,
+            [
+                parse_quote! { static __TRACE_SAMPLER: perf::Sampler = perf::Sampler::new(#sample); },
+                parse_quote! { let __trace_name = #trace_name; },
+                parse_quote! { let __trace_fn = __TRACE_SAMPLER.trace(|| #trace); },
+            ],
+        );
+    } else {
+        let block = input.block.as_mut();
+        block.stmts.splice(
+            0..0,
+            [
+                parse_quote! { let __trace_name = #trace_name; },
+                parse_quote! { let __trace_fn = #trace; },
+            ],
+        );
+    }
+    let gen = match instrument_args.cfg {
+        Some(cfg) => quote! {
+            #[cfg(#cfg)]
+            #input
+            #[cfg(not(#cfg))]
+            #original
+        },
+        None => quote! {#input},
+    };
+    gen.into()
+}
+
+#[proc_macro_attribute]
+#[cfg(not(feature = "perf"))]
 pub fn instrument(
     _args: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let mut input = syn::parse_macro_input!(item as ItemFn);
-    let block = input.block.as_mut();
-    block.stmts.insert(
-        0,
-        parse_quote! { let __trace_fn = perf::ScopedTrace::new_fn(perf::function_name!());},
-    );
+    item
+}
+
+#[cfg(feature = "perf")]
+#[allow(clippy::large_enum_variant)]
+enum LoopArg {
+    Name(LitStr),
+    Option(Meta),
+}
+
+#[cfg(feature = "perf")]
+impl Parse for LoopArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(LitStr) {
+            input.parse().map(LoopArg::Name)
+        } else {
+            input.parse().map(LoopArg::Option)
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+impl ToTokens for LoopArg {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            LoopArg::Name(name) => name.to_tokens(tokens),
+            LoopArg::Option(meta) => meta.to_tokens(tokens),
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+#[derive(Default)]
+struct InstrumentLoopArgs {
+    name: Option<LitStr>,
+    /// additionally trace every iteration of the loop body
+    per_iteration: bool,
+}
+
+#[cfg(feature = "perf")]
+impl Parse for InstrumentLoopArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = Self::default();
+        for arg in Punctuated::<LoopArg, Token![,]>::parse_terminated(input)? {
+            match arg {
+                LoopArg::Name(name) if args.name.is_none() => args.name = Some(name),
+                LoopArg::Name(name) => {
+                    return Err(Error::new_spanned(
+                        name,
+                        "loop name specified more than once",
+                    ))
+                }
+                LoopArg::Option(Meta::Path(path)) if path.is_ident("per_iteration") => {
+                    args.per_iteration = true;
+                }
+                LoopArg::Option(Meta::NameValue(stats)) if stats.path.is_ident("stats") => {
+                    match &stats.value {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(kind),
+                            ..
+                        }) if kind.value() == "iterations" => args.per_iteration = true,
+                        value => {
+                            return Err(Error::new_spanned(
+                                value,
+                                "expected `stats = \"iterations\"`",
+                            ))
+                        }
+                    }
+                }
+                LoopArg::Option(meta) => {
+                    return Err(Error::new_spanned(
+                        meta,
+                        "unsupported instrument_loop argument, expected `per_iteration` or `stats = \"iterations\"`",
+                    ))
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(feature = "perf")]
+fn loop_body_mut(input: &mut Expr) -> Option<&mut Block> {
+    match input {
+        Expr::ForLoop(l) => Some(&mut l.body),
+        Expr::While(l) => Some(&mut l.body),
+        Expr::Loop(l) => Some(&mut l.body),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "perf")]
+fn loop_label(input: &Expr) -> Option<String> {
+    let label = match input {
+        Expr::ForLoop(l) => l.label.as_ref(),
+        Expr::While(l) => l.label.as_ref(),
+        Expr::Loop(l) => l.label.as_ref(),
+        _ => None,
+    }?;
+    Some(label.name.ident.to_string())
+}
+
+#[cfg(feature = "perf")]
+const SUPPORTED_LOOPS: &str =
+    "`for`, `while` and `loop` expressions, optionally labeled or as the sole statement of an `unsafe` block";
+
+/// Span of the first token, so errors point at the expression head and not the whole body
+#[cfg(feature = "perf")]
+fn expr_head_span(expr: &Expr) -> proc_macro2::Span {
+    if let Expr::Block(block) = expr {
+        return block.block.brace_token.span.open();
+    }
+    expr.to_token_stream()
+        .into_iter()
+        .next()
+        .map_or_else(proc_macro2::Span::call_site, |token| token.span())
+}
+
+#[cfg(feature = "perf")]
+fn not_an_expression(item: proc_macro::TokenStream, err: Error) -> Error {
+    match syn::parse::<Item>(item) {
+        Ok(Item::Fn(f)) => Error::new_spanned(
+            f.sig.fn_token,
+            "`instrument_loop` cannot be applied to a function, use `#[perf::instrument]` instead",
+        ),
+        Ok(item) => Error::new(
+            item.to_token_stream()
+                .into_iter()
+                .next()
+                .map_or_else(proc_macro2::Span::call_site, |token| token.span()),
+            format!("Expected a loop construct, `instrument_loop` supports {SUPPORTED_LOOPS}"),
+        ),
+        Err(_) => err,
+    }
+}
+
+/// Finds the loop to instrument: `input` itself or the loop inside an `unsafe` block
+#[cfg(feature = "perf")]
+fn target_loop_mut(input: &mut Expr) -> syn::Result<&mut Expr> {
+    match input {
+        Expr::ForLoop(_) | Expr::While(_) | Expr::Loop(_) => Ok(input),
+        Expr::Unsafe(unsafe_block) => match unsafe_block.block.stmts.as_mut_slice() {
+            [Stmt::Expr(
+                inner @ (Expr::ForLoop(_) | Expr::While(_) | Expr::Loop(_)),
+                _,
+            )] => Ok(inner),
+            _ => Err(Error::new_spanned(
+                unsafe_block.unsafe_token,
+                format!("Expected an `unsafe` block containing only a loop, `instrument_loop` supports {SUPPORTED_LOOPS}"),
+            )),
+        },
+        Expr::Block(_) => Err(Error::new(
+            expr_head_span(input),
+            format!("Expected a loop construct, found a block. Use `#[perf::instrument_block(\"name\")]` to trace a block; `instrument_loop` supports {SUPPORTED_LOOPS}"),
+        )),
+        _ => Err(Error::new(
+            expr_head_span(input),
+            format!("Expected a loop construct, `instrument_loop` supports {SUPPORTED_LOOPS}"),
+        )),
+    }
+}
+
+#[cfg(feature = "perf")]
+#[derive(Default)]
+struct InstrumentImplArgs {
+    /// methods left uninstrumented
+    skip: Vec<Ident>,
+}
+
+#[cfg(feature = "perf")]
+impl InstrumentImplArgs {
+    fn parse(&mut self, meta: &ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("skip") {
+            meta.parse_nested_meta(|method| {
+                let ident = method.path.require_ident()?;
+                self.skip.push(ident.clone());
+                Ok(())
+            })
+        } else {
+            Err(meta.error("unsupported instrument_impl argument, expected `skip(...)`"))
+        }
+    }
+}
+
+/// Usage: `#[perf::instrument_impl]` or `#[perf::instrument_impl(skip(new, len))]` on an
+/// `impl` block, equivalent to `#[perf::instrument]` on every method not skipped.
+/// Methods that already carry an `instrument` attribute keep it.
+///
+/// Safety: Only safe in a single-threaded program, unless perf is built with the `perf-mt`
+/// feature, which keeps the traces per thread.
+#[proc_macro_attribute]
+#[cfg(feature = "perf")]
+pub fn instrument_impl(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let mut impl_args = InstrumentImplArgs::default();
+    let args_parser = syn::meta::parser(|meta| impl_args.parse(&meta));
+    syn::parse_macro_input!(args with args_parser);
+
+    let mut input = syn::parse_macro_input!(item as ItemImpl);
+    for item in &mut input.items {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let instrumented = method.attrs.iter().any(|attr| {
+            attr.path()
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "instrument")
+        });
+        if !instrumented && !impl_args.skip.contains(&method.sig.ident) {
+            method.attrs.push(parse_quote! { #[perf::instrument] });
+        }
+    }
     let gen = quote! {#input};
     gen.into()
 }
 
 #[proc_macro_attribute]
 #[cfg(not(feature = "perf"))]
-pub fn instrument(
+pub fn instrument_impl(
     _args: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
     item
 }
 
-/// Safety: Cannot be used in a multi-threaded context
+/// Prefixes the names of `#[instrument_loop]`s nested in a loop body with the
+/// enclosing loop's name, e.g. `outer/inner`.
+#[cfg(feature = "perf")]
+struct NestedLoopNames<'a> {
+    prefix: &'a str,
+}
+
+#[cfg(feature = "perf")]
+impl NestedLoopNames<'_> {
+    fn is_instrument_loop(attr: &Attribute) -> bool {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "instrument_loop")
+    }
+
+    fn prefix_name(&self, attr: &mut Attribute, label: Option<String>) {
+        let mut args = match &attr.meta {
+            Meta::Path(_) => Punctuated::new(),
+            Meta::List(list) => {
+                match list.parse_args_with(Punctuated::<LoopArg, Token![,]>::parse_terminated) {
+                    Ok(args) => args,
+                    // leave malformed arguments for the nested expansion to report
+                    Err(_) => return,
+                }
+            }
+            Meta::NameValue(_) => return,
+        };
+        let name = args.iter_mut().find_map(|arg| match arg {
+            LoopArg::Name(name) => Some(name),
+            LoopArg::Option(_) => None,
+        });
+        match (name, label) {
+            (Some(name), _) => {
+                *name = LitStr::new(&format!("{}/{}", self.prefix, name.value()), name.span());
+            }
+            (None, Some(label)) => {
+                let name = LitStr::new(
+                    &format!("{}/{}", self.prefix, label),
+                    proc_macro2::Span::call_site(),
+                );
+                args.insert(0, LoopArg::Name(name));
+            }
+            (None, None) => return,
+        }
+        let path = attr.path().clone();
+        attr.meta = parse_quote! { #path(#args) };
+    }
+}
+
+#[cfg(feature = "perf")]
+impl VisitMut for NestedLoopNames<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        let label = loop_label(expr);
+        let attrs = match expr {
+            Expr::ForLoop(l) => &mut l.attrs,
+            Expr::While(l) => &mut l.attrs,
+            Expr::Loop(l) => &mut l.attrs,
+            _ => return visit_mut::visit_expr_mut(self, expr),
+        };
+        match attrs.iter_mut().find(|attr| Self::is_instrument_loop(attr)) {
+            // loops nested further are prefixed when this attribute expands
+            Some(attr) => self.prefix_name(attr, label),
+            None => visit_mut::visit_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_item_mut(&mut self, _: &mut Item) {
+        // loops in nested items don't run as part of this loop
+    }
+}
+
+/// Usage: `#[perf::instrument_loop("name")]`, or `#[perf::instrument_loop]` on a
+/// labeled loop (`'name: for ...`) to use the label as the anchor name.
+///
+/// `#[perf::instrument_loop("name", per_iteration)]` (or `stats = "iterations"`) additionally
+/// traces the loop body, reporting iteration count and min/avg/max cycles and their standard
+/// deviation per iteration.
+///
+/// Instrumented loops nested in the body are named after their enclosing loop (`outer/inner`).
+///
+/// Safety: Only safe in a single-threaded program, unless perf is built with the `perf-mt`
+/// feature, which keeps the traces per thread.
 #[proc_macro_attribute]
 #[cfg(feature = "perf")]
 pub fn instrument_loop(
     args: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let input = syn::parse_macro_input!(item as Expr);
-    if !matches!(input, Expr::ForLoop(_) | Expr::While(_) | Expr::Loop(_)) {
-        // TODO(sathwik): Improve error diagnostics
-        return Error::new_spanned(input, "Expected a loop construct")
+    let mut input = match syn::parse::<Expr>(item.clone()) {
+        Ok(input) => input,
+        Err(err) => return not_an_expression(item, err).to_compile_error().into(),
+    };
+    let target = match target_loop_mut(&mut input) {
+        Ok(target) => target,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let loop_args = syn::parse_macro_input!(args as InstrumentLoopArgs);
+    let loop_name = match (loop_args.name, loop_label(target)) {
+        (Some(name), _) => name.value(),
+        (None, Some(label)) => label,
+        (None, None) => {
+            return Error::new(
+                expr_head_span(target),
+                "Expected a loop name argument or a labeled loop (`'name: for ...`)",
+            )
             .to_compile_error()
-            .into();
+            .into()
+        }
+    };
+    let body = loop_body_mut(target).expect("checked to be a loop construct");
+    NestedLoopNames { prefix: &loop_name }.visit_block_mut(body);
+    if loop_args.per_iteration {
+        let new_trace = slotted_trace(&quote! {
+            perf::trace::TraceId::new(
+                perf::function_name!(),
+                perf::trace::TraceType::Iteration(#loop_name),
+            )
+        });
+        *body = parse_quote! {{
+            let __trace_iteration = #new_trace;
+            #body
+        }};
     }
-    let loop_name = syn::parse_macro_input!(args as LitStr).value();
+    let new_trace = slotted_trace(&quote! {
+        perf::trace::TraceId::new(perf::function_name!(), perf::trace::TraceType::Loop(#loop_name))
+    });
     let gen = quote! {{
-        let __trace_loop = perf::ScopedTrace::new_loop(perf::function_name!(), #loop_name);
+        let __trace_loop = #new_trace;
         #input
     }};
     gen.into()
@@ -60,3 +673,137 @@ pub fn instrument_loop(
 ) -> proc_macro::TokenStream {
     item
 }
+
+/// Usage: `#[perf::instrument_block("name")]` on a block expression or a statement.
+/// On a `let` statement the binding stays in scope after the traced section ends.
+///
+/// Safety: Only safe in a single-threaded program, unless perf is built with the `perf-mt`
+/// feature, which keeps the traces per thread.
+#[proc_macro_attribute]
+#[cfg(feature = "perf")]
+pub fn instrument_block(
+    args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let section_name = syn::parse_macro_input!(args as LitStr);
+    let input = syn::parse_macro_input!(item as Stmt);
+    let new_trace = slotted_trace(&quote! {
+        perf::trace::TraceId::new(
+            perf::function_name!(),
+            perf::trace::TraceType::Section(#section_name),
+        )
+    });
+    let gen = match input {
+        Stmt::Expr(expr, None) => quote! {{
+            let __trace_section = #new_trace;
+            #expr
+        }},
+        stmt => quote! {
+            let __trace_section = #new_trace;
+            #stmt
+            drop(__trace_section);
+        },
+    };
+    gen.into()
+}
+
+#[proc_macro_attribute]
+#[cfg(not(feature = "perf"))]
+pub fn instrument_block(
+    _args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    item
+}
+
+#[cfg(feature = "perf")]
+struct TracedClosure {
+    name: LitStr,
+    closure: ExprClosure,
+}
+
+#[cfg(feature = "perf")]
+impl Parse for TracedClosure {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let closure = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { name, closure })
+    }
+}
+
+/// Usage: `perf::traced_closure!("name", |x| ...)`, traces every call of the closure
+/// under a named anchor in the enclosing function.
+///
+/// Safety: Only safe in a single-threaded program, unless perf is built with the `perf-mt`
+/// feature, which keeps the traces per thread.
+#[proc_macro]
+#[cfg(feature = "perf")]
+pub fn traced_closure(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let TracedClosure { name, mut closure } = syn::parse_macro_input!(item as TracedClosure);
+    let body = &closure.body;
+    let new_trace = slotted_trace(&quote! {
+        perf::trace::TraceId::new(
+            perf::function_name!().trim_end_matches("::{{closure}}"),
+            perf::trace::TraceType::Closure(#name),
+        )
+    });
+    *closure.body = parse_quote! {{
+        let __trace_closure = #new_trace;
+        #body
+    }};
+    let gen = quote! {#closure};
+    gen.into()
+}
+
+#[proc_macro]
+#[cfg(not(feature = "perf"))]
+pub fn traced_closure(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let parser = |input: syn::parse::ParseStream| {
+        input.parse::<syn::LitStr>()?;
+        input.parse::<syn::Token![,]>()?;
+        let closure: syn::ExprClosure = input.parse()?;
+        input.parse::<Option<syn::Token![,]>>()?;
+        Ok(closure)
+    };
+    match syn::parse::Parser::parse(parser, item) {
+        Ok(closure) => quote::quote! {#closure}.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// Usage: `perf::instrument_closure!(|x| ...)`, traces every call of the closure under an
+/// anchor in the enclosing function named after where the closure is written
+/// (`line:column`), the way `#[track_caller]` reports its caller. Use `traced_closure!` to
+/// name the anchor instead.
+///
+/// Safety: Only safe in a single-threaded program, unless perf is built with the `perf-mt`
+/// feature, which keeps the traces per thread.
+#[proc_macro]
+#[cfg(feature = "perf")]
+pub fn instrument_closure(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let mut closure = syn::parse_macro_input!(item as ExprClosure);
+    let body = &closure.body;
+    let new_trace = slotted_trace(&quote! {
+        perf::trace::TraceId::new(
+            perf::function_name!().trim_end_matches("::{{closure}}"),
+            perf::trace::TraceType::Closure(::core::concat!(::core::line!(), ":", ::core::column!())),
+        )
+    });
+    *closure.body = parse_quote! {{
+        let __trace_closure = #new_trace;
+        #body
+    }};
+    let gen = quote! {#closure};
+    gen.into()
+}
+
+#[proc_macro]
+#[cfg(not(feature = "perf"))]
+pub fn instrument_closure(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match syn::parse::<syn::ExprClosure>(item) {
+        Ok(closure) => quote::quote! {#closure}.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
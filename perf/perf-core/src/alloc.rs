@@ -0,0 +1,110 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+/// Allocations made through `TrackingAllocator`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Allocations {
+    /// calls to `alloc`, `alloc_zeroed` and `realloc`
+    pub count: u64,
+    /// bytes requested by those calls, a `realloc` counts its new size
+    pub bytes: u64,
+}
+
+impl Allocations {
+    /// Allocations of the calling thread so far, zero unless
+    /// `TrackingAllocator` is the global allocator
+    #[must_use]
+    pub fn now() -> Self {
+        ALLOCATIONS
+            .try_with(|allocations| Self {
+                count: allocations.0.get(),
+                bytes: allocations.1.get(),
+            })
+            .unwrap_or_default()
+    }
+
+    /// Allocations since `earlier`
+    #[must_use]
+    pub fn since(self, earlier: Self) -> Self {
+        self.wrapping_sub(earlier)
+    }
+
+    /// Wraps like the self times of traces do while their children are
+    /// subtracted before their own hit is added
+    #[must_use]
+    pub fn wrapping_add(self, other: Self) -> Self {
+        Self {
+            count: self.count.wrapping_add(other.count),
+            bytes: self.bytes.wrapping_add(other.bytes),
+        }
+    }
+
+    #[must_use]
+    pub fn wrapping_sub(self, other: Self) -> Self {
+        Self {
+            count: self.count.wrapping_sub(other.count),
+            bytes: self.bytes.wrapping_sub(other.bytes),
+        }
+    }
+}
+
+thread_local! {
+    // const and without a destructor, so reading it never allocates
+    static ALLOCATIONS: (Cell<u64>, Cell<u64>) = const { (Cell::new(0), Cell::new(0)) };
+}
+
+/// Runs `f` without counting its allocations, so that the bookkeeping of
+/// traces isn't attributed to the trace around them
+#[cfg(feature = "perf")]
+pub(crate) fn untracked<R>(f: impl FnOnce() -> R) -> R {
+    let before = Allocations::now();
+    let result = f();
+    let _ = ALLOCATIONS.try_with(|allocations| {
+        allocations.0.set(before.count);
+        allocations.1.set(before.bytes);
+    });
+    result
+}
+
+fn count(bytes: usize) {
+    let _ = ALLOCATIONS.try_with(|allocations| {
+        allocations.0.set(allocations.0.get() + 1);
+        allocations.1.set(allocations.1.get() + bytes as u64);
+    });
+}
+
+/// Global allocator that counts the allocations of each thread, which traces
+/// then attribute to themselves (see `Trace::allocations_exclusive`). Install
+/// it with
+///
+/// ```ignore
+/// #[global_allocator]
+/// static ALLOCATOR: perf::TrackingAllocator = perf::TrackingAllocator;
+/// ```
+///
+/// Without `perf` it still counts, for `Allocations::now`, but nothing reads
+/// the counts.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count(layout.size());
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
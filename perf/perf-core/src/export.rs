@@ -0,0 +1,314 @@
+//! Machine-readable profile: a JSON summary of every trace for scripts to
+//! diff, a CSV table of them for spreadsheets and `diff`, or the Chrome Trace
+//! Event format to load into Perfetto or `chrome://tracing`.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    path::Path,
+    str::FromStr,
+};
+
+#[cfg(feature = "perf")]
+use crate::{
+    children_by_parent,
+    trace::{collect_traces, PageFaults, Trace, TraceId, TraceType},
+    Children,
+};
+use crate::{start_ts, timer_freq, READ_TIMER};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// the total time and every trace's counters, times in timer ticks
+    Json,
+    /// Chrome Trace Event format. The profile only keeps totals, so each
+    /// trace is a single event as long as all its hits, with its children
+    /// laid out one after another from its start, like a flame graph.
+    Chrome,
+    /// a header and one row per trace: hits, exclusive and inclusive ticks
+    /// and their percentages of the total time
+    Csv,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "chrome" => Ok(Self::Chrome),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("unknown profile format `{s}`")),
+        }
+    }
+}
+
+/// Writes the profile captured since `begin_profile` to `path` in `format`
+///
+/// # Errors
+///
+/// Returns any error creating or writing `path`.
+///
+/// # Safety
+///
+/// Same as `end_and_print_profile`.
+pub fn end_and_export_profile(path: &Path, format: ProfileFormat) -> io::Result<()> {
+    end_and_write_profile(std::fs::File::create(path)?, format)
+}
+
+/// Writes the profile captured since `begin_profile` to `writer` in `format`
+///
+/// # Errors
+///
+/// Returns any error writing to `writer`.
+///
+/// # Safety
+///
+/// Same as `end_and_print_profile`.
+pub fn end_and_write_profile(mut writer: impl Write, format: ProfileFormat) -> io::Result<()> {
+    let elapsed = READ_TIMER() - unsafe { start_ts() };
+    let timer_freq = unsafe { timer_freq() };
+    let profile = match format {
+        ProfileFormat::Json => json(elapsed, timer_freq),
+        ProfileFormat::Chrome => chrome(elapsed, timer_freq),
+        ProfileFormat::Csv => csv(elapsed),
+    };
+    writer.write_all(profile.as_bytes())
+}
+
+/// Prints the profile captured since `begin_profile` to stdout as CSV
+///
+/// # Panics
+///
+/// Panics if stdout cannot be written to.
+///
+/// # Safety
+///
+/// Same as `end_and_print_profile`.
+pub fn end_and_print_profile_csv() {
+    end_and_write_profile(io::stdout().lock(), ProfileFormat::Csv).expect("write to stdout");
+}
+
+/// Totals of the profile captured since `begin_profile`, for callers that
+/// lay it out themselves
+#[derive(Clone, Debug, Default)]
+pub struct ProfileTotals {
+    /// timer ticks since `begin_profile`
+    pub elapsed: u64,
+    pub timer_frequency: u64,
+    /// name and ticks (with children) of every trace, sorted by first hit;
+    /// empty without the `perf` feature
+    pub traces: Vec<(String, u64)>,
+}
+
+/// # Safety
+///
+/// Same as `end_and_print_profile`.
+#[must_use]
+pub fn profile_totals() -> ProfileTotals {
+    ProfileTotals {
+        elapsed: READ_TIMER() - unsafe { start_ts() },
+        timer_frequency: unsafe { timer_freq() },
+        #[cfg(feature = "perf")]
+        traces: unsafe { collect_traces() }
+            .into_iter()
+            .map(|(trace_id, trace)| (trace_id.to_string(), trace.elapsed_inclusive))
+            .collect(),
+        #[cfg(not(feature = "perf"))]
+        traces: Vec::new(),
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn json(elapsed: u64, timer_freq: u64) -> String {
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "{{\"total_seconds\":{},\"total_ticks\":{elapsed},\"timer_frequency\":{timer_freq},\"traces\":[",
+        elapsed as f64 / timer_freq as f64
+    );
+    #[cfg(feature = "perf")]
+    for (i, (trace_id, trace)) in unsafe { collect_traces() }.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        let parent = trace
+            .parent
+            .map_or_else(|| "null".to_owned(), |parent| quote(&parent.to_string()));
+        let page_faults = trace.page_faults.map_or_else(
+            || "null".to_owned(),
+            |PageFaults { soft, hard }| format!("{{\"soft\":{soft},\"hard\":{hard}}}"),
+        );
+        let _ = write!(
+            out,
+            "\n{{\"name\":{},\"kind\":\"{}\",\"parent\":{parent},\"hits\":{},\"sample_every\":{},\
+             \"inclusive_ticks\":{},\"exclusive_ticks\":{},\"min_ticks\":{},\"max_ticks\":{},\
+             \"bytes\":{},\"page_faults\":{page_faults},\"allocations\":{{\"exclusive_count\":{},\
+             \"exclusive_bytes\":{},\"inclusive_count\":{},\"inclusive_bytes\":{}}}}}",
+            quote(&trace_id.to_string()),
+            kind(trace_id.ty),
+            trace.hit_count,
+            trace.sample_every,
+            trace.elapsed_inclusive,
+            trace.elapsed_exclusive,
+            trace.min_elapsed,
+            trace.max_elapsed,
+            trace.processed_byte_count,
+            trace.allocations_exclusive.count,
+            trace.allocations_exclusive.bytes,
+            trace.allocations_inclusive.count,
+            trace.allocations_inclusive.bytes,
+        );
+    }
+    out.push_str("\n]}\n");
+    out
+}
+
+#[cfg_attr(not(feature = "perf"), allow(unused_variables, unused_mut))]
+fn csv(elapsed: u64) -> String {
+    let mut out = String::from(
+        "name,kind,hits,exclusive_ticks,inclusive_ticks,exclusive_percent,inclusive_percent,\
+         exclusive_allocations,exclusive_allocated_bytes\n",
+    );
+    #[cfg(feature = "perf")]
+    for (trace_id, trace) in unsafe { collect_traces() } {
+        #[allow(clippy::cast_precision_loss)]
+        let percent = |ticks: i64| ticks as f64 / elapsed as f64 * 100.0;
+        #[allow(clippy::cast_possible_wrap)]
+        let inclusive = trace.elapsed_inclusive as i64;
+        let name = trace_id.to_string();
+        let name = if name.contains([',', '"']) {
+            format!("\"{}\"", name.replace('"', "\"\""))
+        } else {
+            name
+        };
+        let _ = writeln!(
+            out,
+            "{name},{},{},{},{inclusive},{:.4},{:.4},{},{}",
+            kind(trace_id.ty),
+            trace.hit_count,
+            trace.elapsed_exclusive,
+            percent(trace.elapsed_exclusive),
+            percent(inclusive),
+            trace.allocations_exclusive.count,
+            trace.allocations_exclusive.bytes,
+        );
+    }
+    out
+}
+
+fn chrome(elapsed: u64, timer_freq: u64) -> String {
+    let mut events = Vec::new();
+    event(
+        &mut events,
+        "profile",
+        "profile",
+        0,
+        elapsed,
+        timer_freq,
+        "{}",
+    );
+    #[cfg(feature = "perf")]
+    {
+        let traces = unsafe { collect_traces() };
+        chrome_subtree(
+            &mut events,
+            &children_by_parent(&traces),
+            None,
+            0,
+            timer_freq,
+        );
+    }
+    format!(
+        "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+        events.join(",\n")
+    )
+}
+
+/// Adds the children of `parent`, which starts at `start` ticks, then theirs
+#[cfg(feature = "perf")]
+fn chrome_subtree(
+    events: &mut Vec<String>,
+    children: &Children,
+    parent: Option<TraceId>,
+    mut start: u64,
+    timer_freq: u64,
+) {
+    for &(trace_id, trace) in children.get(&parent).map_or(&[][..], Vec::as_slice) {
+        let Trace {
+            hit_count,
+            sample_every,
+            elapsed_exclusive,
+            processed_byte_count,
+            ..
+        } = *trace;
+        let args = format!(
+            "{{\"hits\":{hit_count},\"sample_every\":{sample_every},\
+             \"exclusive_ticks\":{elapsed_exclusive},\"bytes\":{processed_byte_count}}}"
+        );
+        let name = trace_id.to_string();
+        let duration = trace.elapsed_inclusive;
+        event(
+            events,
+            &name,
+            kind(trace_id.ty),
+            start,
+            duration,
+            timer_freq,
+            &args,
+        );
+        chrome_subtree(events, children, Some(trace_id), start, timer_freq);
+        start += duration;
+    }
+}
+
+/// Complete (`X`) event from `start` for `duration` ticks, in microseconds
+#[allow(clippy::cast_precision_loss)]
+fn event(
+    events: &mut Vec<String>,
+    name: &str,
+    category: &str,
+    start: u64,
+    duration: u64,
+    timer_freq: u64,
+    args: &str,
+) {
+    let micros = |ticks: u64| ticks as f64 * 1e6 / timer_freq as f64;
+    events.push(format!(
+        "{{\"name\":{},\"cat\":\"{category}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":1,\"args\":{args}}}",
+        quote(name),
+        micros(start),
+        micros(duration)
+    ));
+}
+
+#[cfg(feature = "perf")]
+fn kind(ty: TraceType) -> &'static str {
+    match ty {
+        TraceType::Fn => "fn",
+        TraceType::Method(_) => "method",
+        TraceType::Loop(_) => "loop",
+        TraceType::Section(_) => "section",
+        TraceType::Iteration(_) => "iteration",
+        TraceType::Closure(_) => "closure",
+        TraceType::Anchor => "anchor",
+    }
+}
+
+/// `value` as a JSON string
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str(r#"\""#),
+            '\\' => quoted.push_str(r"\\"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
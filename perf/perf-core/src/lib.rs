@@ -1,15 +1,32 @@
 #![feature(once_cell_get_mut)]
 
+mod alloc;
+pub mod export;
+pub mod prometheus;
 mod racy_unsafe_cell;
+pub mod repetition;
+pub mod report;
 use racy_unsafe_cell::RacyUnsafeCell;
 use std::cell::OnceCell;
 
-use nix::time::ClockId;
+pub use alloc::{Allocations, TrackingAllocator};
+pub use export::{
+    end_and_export_profile, end_and_print_profile_csv, end_and_write_profile, profile_totals,
+    ProfileFormat, ProfileTotals,
+};
+pub use nix::time::ClockId;
 
 #[cfg(feature = "perf")]
 pub mod trace;
 #[cfg(feature = "perf")]
 use trace::*;
+#[cfg(feature = "perf")]
+use {
+    std::collections::HashMap,
+    std::future::Future,
+    std::pin::Pin,
+    std::task::{Context, Poll},
+};
 
 type ReadTimer = fn() -> u64;
 static READ_TIMER: ReadTimer = read_cpu_timer;
@@ -23,17 +40,134 @@ fn get_os_timer_freq() -> u64 {
     1_000_000_000
 }
 
+/// Clocks tried, in order, after the preferred one when reading the OS timer.
+/// `CLOCK_MONOTONIC_RAW` isn't subject to NTP slewing, which would skew the
+/// CPU timer frequency estimate.
+const OS_CLOCK_FALLBACKS: [ClockId; 3] = [
+    // nix only names it on Linux, but macOS has one too
+    ClockId::from_raw(nix::libc::CLOCK_MONOTONIC_RAW),
+    ClockId::CLOCK_MONOTONIC,
+    ClockId::CLOCK_REALTIME,
+];
+
+static PREFERRED_OS_CLOCK: RacyUnsafeCell<Option<ClockId>> = RacyUnsafeCell::new(None);
+
+/// Selects the clock used as the OS timer reference for estimating the CPU
+/// timer frequency. Falls back to `CLOCK_MONOTONIC_RAW`, `CLOCK_MONOTONIC`
+/// and `CLOCK_REALTIME` if the clock is unavailable (e.g. EPERM for
+/// `CLOCK_REALTIME_ALARM` without `CAP_WAKE_ALARM`).
+/// Must be invoked before `begin_profile` to take effect.
+///
+/// # Safety
+///
+/// This function is only safe to call in single-threaded program.
+pub fn select_os_clock(clock: ClockId) {
+    unsafe { *PREFERRED_OS_CLOCK.get() = Some(clock) }
+}
+
+unsafe fn os_clock() -> ClockId {
+    static CELL: RacyUnsafeCell<OnceCell<ClockId>> = RacyUnsafeCell::new(OnceCell::new());
+    *(*CELL.get()).get_or_init(|| {
+        (*PREFERRED_OS_CLOCK.get())
+            .into_iter()
+            .chain(OS_CLOCK_FALLBACKS)
+            .find(|clock| clock.now().is_ok())
+            .expect("No readable OS clock")
+    })
+}
+
 #[allow(clippy::cast_sign_loss)]
 fn read_os_timer() -> u64 {
     // https://berthub.eu/articles/posts/on-linux-vdso-and-clockgettime/
-    let cur = ClockId::CLOCK_REALTIME.now().expect("Get current clock");
+    let cur = unsafe { os_clock() }.now().expect("Get current clock");
     cur.tv_sec() as u64 * get_os_timer_freq() + cur.tv_nsec() as u64
 }
 
+#[cfg(target_arch = "x86_64")]
 fn read_cpu_timer() -> u64 {
     unsafe { core::arch::x86_64::_rdtsc() }
 }
 
+/// The virtual count of the generic timer, which ticks at a fixed frequency
+/// (`cntfrq_el0`) like an invariant TSC, though usually far slower than the
+/// cores, e.g. 24MHz on Apple Silicon.
+#[cfg(target_arch = "aarch64")]
+fn read_cpu_timer() -> u64 {
+    let ticks: u64;
+    unsafe {
+        core::arch::asm!("mrs {}, cntvct_el0", out(reg) ticks, options(nomem, nostack));
+    }
+    ticks
+}
+
+/// Without a cycle counter the OS timer stands in, so timings are in
+/// nanoseconds and the estimated frequency is 1GHz.
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn read_cpu_timer() -> u64 {
+    read_os_timer()
+}
+
+/// `read_cpu_timer` once the instructions before it have executed and before
+/// the ones after it start, for the beginning of a hit
+#[cfg(feature = "precise-timer")]
+fn read_cpu_timer_begin() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use core::arch::x86_64::{_mm_lfence, _rdtsc};
+        _mm_lfence();
+        let ticks = _rdtsc();
+        _mm_lfence();
+        ticks
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        let ticks: u64;
+        unsafe {
+            core::arch::asm!("isb", "mrs {}, cntvct_el0", "isb", out(reg) ticks, options(nostack));
+        }
+        ticks
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    read_cpu_timer()
+}
+
+/// `read_cpu_timer` once the instructions before it have executed, for the
+/// end of a hit. `rdtscp` waits for them itself, the fence keeps the
+/// instructions after it from starting before the read.
+#[cfg(feature = "precise-timer")]
+fn read_cpu_timer_end() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use core::arch::x86_64::{__rdtscp, _mm_lfence};
+        let mut aux = 0;
+        let ticks = __rdtscp(&raw mut aux);
+        _mm_lfence();
+        ticks
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    read_cpu_timer_begin()
+}
+
+/// Ticks a hit timed with `read_cpu_timer_begin`/`_end` takes over one timed
+/// with plain reads, the least of a thousand tries each
+#[cfg(feature = "precise-timer")]
+unsafe fn precise_timer_overhead() -> u64 {
+    static CELL: RacyUnsafeCell<OnceCell<u64>> = RacyUnsafeCell::new(OnceCell::new());
+    *(*CELL.get()).get_or_init(|| {
+        let least = |begin: fn() -> u64, end: fn() -> u64| {
+            (0..1000)
+                .map(|_| {
+                    let ticks = begin();
+                    end() - ticks
+                })
+                .min()
+                .unwrap_or_default()
+        };
+        let precise = least(read_cpu_timer_begin, read_cpu_timer_end);
+        precise.saturating_sub(least(READ_TIMER, READ_TIMER))
+    })
+}
+
 fn estimate_timer_freq(millis_to_wait: u64) -> u64 {
     let os_freq = get_os_timer_freq();
     let timer_start = READ_TIMER();
@@ -59,78 +193,459 @@ unsafe fn start_ts() -> u64 {
 
 /// # Safety
 ///
-/// This struct is only safe to be used in single-threaded program.
+/// This struct is only safe to be used in single-threaded program, unless
+/// built with the `perf-mt` feature, which keeps the traces per thread.
 #[cfg(feature = "perf")]
 pub struct ScopedTrace {
-    trace_id: TraceId,
-    parent: Option<TraceId>,
+    /// slot of the trace in `trace_map`
+    index: usize,
+    /// slot of the trace open when this hit began
+    parent: Option<usize>,
     begin: u64,
-    old_elapsed_inclusive: u64,
+    /// hits of the same trace open when this one began
+    depth: usize,
+    byte_count: u64,
+    /// page faults when the hit began, if it counts them
+    faults_begin: Option<PageFaults>,
+    /// allocations of the thread when the hit began
+    allocations_begin: Allocations,
+    /// `begin` and the end were read with `read_cpu_timer_begin`/`_end`
+    #[cfg(feature = "precise-timer")]
+    precise: bool,
+    /// tracing was enabled when the hit began, otherwise it records nothing
+    active: bool,
 }
 
 #[cfg(feature = "perf")]
 impl ScopedTrace {
-    fn new(trace_id: TraceId) -> Self {
+    /// Hit of `trace_id` at the call site of `slot`, see `TraceSlot`
+    #[inline]
+    pub fn new_in(slot: &TraceSlot, trace_id: TraceId) -> Self {
+        if !tracing_enabled() {
+            return Self {
+                index: 0,
+                parent: None,
+                begin: 0,
+                depth: 0,
+                byte_count: 0,
+                faults_begin: None,
+                allocations_begin: Allocations::default(),
+                #[cfg(feature = "precise-timer")]
+                precise: false,
+                active: false,
+            };
+        }
+        let index = slot.get(&trace_id);
         let trace_map = unsafe { trace_map() };
-        let trace = trace_map.entry(trace_id).or_default();
-        let begin = READ_TIMER();
-        let old_elapsed_inclusive = trace.elapsed_inclusive;
-        let current = CURRENT_TRACE.get();
+        if !trace_map.has(index) {
+            Self::enter(index, trace_id, trace_map);
+        }
+        let current = current_trace();
         let parent = unsafe { *current };
-        unsafe { *current = Some(trace_id) }
+        let trace = trace_map.at(index);
+        let depth = trace.depth;
+        trace.depth += 1;
+        let allocations_begin = Allocations::now();
+        let begin = READ_TIMER();
+        unsafe { *current = Some(index) }
         Self {
-            trace_id,
+            index,
             parent,
             begin,
-            old_elapsed_inclusive,
+            depth,
+            byte_count: 0,
+            faults_begin: None,
+            allocations_begin,
+            #[cfg(feature = "precise-timer")]
+            precise: false,
+            active: true,
         }
     }
 
-    pub fn new_fn(fn_name: &'static str) -> Self {
+    /// Puts the trace at `index` in the thread's traces, on the thread's
+    /// first hit of it, under the trace open there
+    #[cold]
+    fn enter(index: usize, trace_id: TraceId, trace_map: &mut TraceMap) {
+        let parent = unsafe { *current_trace() }.and_then(|parent| trace_map.id_at(parent));
+        // growing the map allocates, which isn't the parent's doing
+        alloc::untracked(|| {
+            trace_map.insert(
+                index,
+                trace_id,
+                Trace {
+                    parent,
+                    ..Trace::default()
+                },
+            );
+        });
+    }
+
+    /// Attributes `byte_count` processed bytes to this hit for bandwidth reporting
+    #[must_use]
+    pub fn with_bytes(mut self, byte_count: u64) -> Self {
+        self.byte_count += byte_count;
+        self
+    }
+
+    /// Also counts the page faults during this hit. They are read with a
+    /// `getrusage` syscall at either end, whose cost is part of the timing.
+    #[must_use]
+    pub fn with_faults(mut self) -> Self {
+        if self.active {
+            self.faults_begin = Some(PageFaults::now());
+        }
+        self
+    }
+
+    /// Reads the timer at either end of this hit once the instructions before
+    /// it have executed and before the ones after it start, so the CPU can't
+    /// move work of the hit outside of it or work around it inside. The fences
+    /// stall the pipeline, which adds tens of ticks to every hit (printed with
+    /// the trace): use it for short hits whose boundaries matter, not for hot
+    /// loops.
+    #[cfg(feature = "precise-timer")]
+    #[must_use]
+    pub fn with_precise_timer(mut self) -> Self {
+        if !self.active {
+            return self;
+        }
+        unsafe { trace_map() }.at(self.index).precise = true;
+        self.precise = true;
+        self.begin = read_cpu_timer_begin();
+        self
+    }
+
+    pub fn new_fn(slot: &TraceSlot, fn_name: &'static str) -> Self {
+        let trace_id = TraceId {
+            enclosing_function_name: fn_name,
+            ty: TraceType::Fn,
+            generic_args: None,
+        };
+        Self::new_in(slot, trace_id)
+    }
+
+    /// `new_fn` that also counts page faults, see `with_faults`
+    pub fn new_fn_with_faults(slot: &TraceSlot, fn_name: &'static str) -> Self {
+        Self::new_fn(slot, fn_name).with_faults()
+    }
+
+    pub fn new_method(
+        slot: &TraceSlot,
+        self_type_name: &'static str,
+        method_name: &'static str,
+    ) -> Self {
+        let trace_id = TraceId {
+            enclosing_function_name: self_type_name,
+            ty: TraceType::Method(method_name),
+            generic_args: None,
+        };
+        Self::new_in(slot, trace_id)
+    }
+
+    pub fn new_generic_fn(
+        slot: &TraceSlot,
+        fn_name: &'static str,
+        generic_args: &'static str,
+    ) -> Self {
         let trace_id = TraceId {
             enclosing_function_name: fn_name,
             ty: TraceType::Fn,
+            generic_args: Some(generic_args),
+        };
+        Self::new_in(slot, trace_id)
+    }
+
+    pub fn new_generic_method(
+        slot: &TraceSlot,
+        self_type_name: &'static str,
+        method_name: &'static str,
+        generic_args: &'static str,
+    ) -> Self {
+        let trace_id = TraceId {
+            enclosing_function_name: self_type_name,
+            ty: TraceType::Method(method_name),
+            generic_args: Some(generic_args),
         };
-        Self::new(trace_id)
+        Self::new_in(slot, trace_id)
     }
 
-    pub fn new_loop(fn_name: &'static str, loop_name: &'static str) -> Self {
+    pub fn new_loop(slot: &TraceSlot, fn_name: &'static str, loop_name: &'static str) -> Self {
         let trace_id = TraceId {
             enclosing_function_name: fn_name,
             ty: TraceType::Loop(loop_name),
+            generic_args: None,
         };
-        Self::new(trace_id)
+        Self::new_in(slot, trace_id)
     }
 
-    pub fn new_section(fn_name: &'static str, section_name: &'static str) -> Self {
+    pub fn new_section(
+        slot: &TraceSlot,
+        fn_name: &'static str,
+        section_name: &'static str,
+    ) -> Self {
         let trace_id = TraceId {
             enclosing_function_name: fn_name,
             ty: TraceType::Section(section_name),
+            generic_args: None,
         };
-        Self::new(trace_id)
+        Self::new_in(slot, trace_id)
+    }
+
+    /// Section `label` of `fn_name` that processes `byte_count` bytes, printed
+    /// with its throughput by `end_and_print_profile`
+    pub fn new_bandwidth(
+        slot: &TraceSlot,
+        fn_name: &'static str,
+        label: &'static str,
+        byte_count: u64,
+    ) -> Self {
+        Self::new_section(slot, fn_name, label).with_bytes(byte_count)
+    }
+
+    pub fn new_closure(
+        slot: &TraceSlot,
+        fn_name: &'static str,
+        closure_name: &'static str,
+    ) -> Self {
+        let trace_id = TraceId {
+            enclosing_function_name: fn_name,
+            ty: TraceType::Closure(closure_name),
+            generic_args: None,
+        };
+        Self::new_in(slot, trace_id)
+    }
+
+    pub fn new_iteration(slot: &TraceSlot, fn_name: &'static str, loop_name: &'static str) -> Self {
+        let trace_id = TraceId {
+            enclosing_function_name: fn_name,
+            ty: TraceType::Iteration(loop_name),
+            generic_args: None,
+        };
+        Self::new_in(slot, trace_id)
     }
 }
 
 #[cfg(feature = "perf")]
 impl Drop for ScopedTrace {
+    #[allow(clippy::cast_precision_loss)]
     fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
         let trace_map = unsafe { trace_map() };
-        let trace = trace_map.get_mut(&self.trace_id).unwrap();
-        let time = READ_TIMER() - self.begin;
+        let faults = self
+            .faults_begin
+            .map(|begin| PageFaults::now().since(begin));
+        let trace = trace_map.at(self.index);
+        #[cfg(feature = "precise-timer")]
+        let end = if self.precise {
+            read_cpu_timer_end()
+        } else {
+            READ_TIMER()
+        };
+        #[cfg(not(feature = "precise-timer"))]
+        let end = READ_TIMER();
+        let time = end - self.begin;
+        let allocations = Allocations::now().since(self.allocations_begin);
+        if let Some(faults) = faults {
+            *trace.page_faults.get_or_insert_default() += faults;
+        }
         trace.elapsed_exclusive += time as i64;
         trace.hit_count += 1;
-        trace.elapsed_inclusive = self.old_elapsed_inclusive + time;
-        let current = CURRENT_TRACE.get();
+        trace.processed_byte_count += self.byte_count;
+        trace.allocations_exclusive = trace.allocations_exclusive.wrapping_add(allocations);
+        trace.min_elapsed = trace.min_elapsed.min(time);
+        trace.max_elapsed = trace.max_elapsed.max(time);
+        trace.elapsed_squares += (time as f64).powi(2);
+        trace.depth -= 1;
+        // recursive hits are already part of the outermost one
+        if self.depth == 0 {
+            trace.elapsed_inclusive += time;
+            trace.allocations_inclusive = trace.allocations_inclusive.wrapping_add(allocations);
+        }
+        // the innermost hit ends first
+        if trace.depths.len() <= self.depth {
+            alloc::untracked(|| trace.depths.resize(self.depth + 1, DepthStats::default()));
+        }
+        trace.depths[self.depth].hit_count += 1;
+        trace.depths[self.depth].elapsed += time;
+        let current = current_trace();
         unsafe { *current = self.parent }
-        if let Some(parent_trace_id) = self.parent {
-            trace_map
-                .get_mut(&parent_trace_id)
-                .unwrap()
-                .elapsed_exclusive -= time as i64;
+        if let Some(parent) = self.parent {
+            let parent = trace_map.at(parent);
+            parent.child_hits += 1;
+            parent.elapsed_exclusive -= time as i64;
+            parent.allocations_exclusive = parent.allocations_exclusive.wrapping_sub(allocations);
+        }
+    }
+}
+
+/// A region started by `anchor_begin`. Dropping it ends the region like
+/// `anchor_end`.
+#[cfg(feature = "perf")]
+#[must_use = "the region ends when the handle is dropped"]
+pub struct AnchorHandle(ScopedTrace);
+
+/// Starts timing `label` until the handle is passed to `anchor_end`, for
+/// regions that don't fit a lexical scope, such as one that begins in one
+/// function and ends in another. Anchors must still nest with the other
+/// traces: end the region before the trace that was open when it began.
+/// `slot` is a static of the call site, which `perf::anchor_begin!` declares.
+///
+/// # Safety
+///
+/// Same as `ScopedTrace`.
+#[cfg(feature = "perf")]
+pub fn anchor_begin(slot: &TraceSlot, label: &'static str) -> AnchorHandle {
+    AnchorHandle(ScopedTrace::new_in(
+        slot,
+        TraceId {
+            enclosing_function_name: label,
+            ty: TraceType::Anchor,
+            generic_args: None,
+        },
+    ))
+}
+
+/// Ends the region of `handle` and records it under its label
+#[cfg(feature = "perf")]
+pub fn anchor_end(handle: AnchorHandle) {
+    drop(handle.0);
+}
+
+/// Invocation counter behind `#[perf::instrument(sample = N)]`.
+/// Traces the first of every `every` invocations.
+///
+/// # Safety
+///
+/// This struct is only safe to be used in single-threaded program, unless
+/// built with the `perf-mt` feature, where the count is shared atomically.
+#[cfg(feature = "perf")]
+pub struct Sampler {
+    every: usize,
+    #[cfg(not(feature = "perf-mt"))]
+    count: RacyUnsafeCell<usize>,
+    #[cfg(feature = "perf-mt")]
+    count: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(feature = "perf-mt")]
+impl Sampler {
+    #[must_use]
+    pub const fn new(every: usize) -> Self {
+        Self {
+            every,
+            count: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn trace(&self, new_trace: impl FnOnce() -> ScopedTrace) -> Option<ScopedTrace> {
+        let count = self
+            .count
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if count % self.every != 0 {
+            return None;
+        }
+        let scoped_trace = new_trace();
+        if scoped_trace.active {
+            let trace_map = unsafe { trace_map() };
+            trace_map.at(scoped_trace.index).sample_every = self.every;
         }
+        Some(scoped_trace)
+    }
+}
+
+#[cfg(all(feature = "perf", not(feature = "perf-mt")))]
+impl Sampler {
+    #[must_use]
+    pub const fn new(every: usize) -> Self {
+        Self {
+            every,
+            count: RacyUnsafeCell::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn trace(&self, new_trace: impl FnOnce() -> ScopedTrace) -> Option<ScopedTrace> {
+        let count = unsafe { &mut *self.count.get() };
+        let sampled = *count == 0;
+        *count += 1;
+        if *count == self.every {
+            *count = 0;
+        }
+        if !sampled {
+            return None;
+        }
+        let scoped_trace = new_trace();
+        if scoped_trace.active {
+            let trace_map = unsafe { trace_map() };
+            trace_map.at(scoped_trace.index).sample_every = self.every;
+        }
+        Some(scoped_trace)
+    }
+}
+
+/// Future adapter that traces every poll of the wrapped future.
+/// Used by `#[perf::instrument]` on `async fn`s.
+#[cfg(feature = "perf")]
+pub struct TracedFuture<F, T> {
+    new_trace: T,
+    inner: F,
+}
+
+#[cfg(feature = "perf")]
+impl<F, T> TracedFuture<F, T>
+where
+    F: Future,
+    T: Fn() -> ScopedTrace,
+{
+    pub fn new(new_trace: T, inner: F) -> Self {
+        Self { new_trace, inner }
     }
 }
 
+#[cfg(feature = "perf")]
+impl<F, T> Future for TracedFuture<F, T>
+where
+    F: Future,
+    T: Fn() -> ScopedTrace,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `inner` is structurally pinned and never moved out of `self`
+        let this = unsafe { self.get_unchecked_mut() };
+        let _trace = (this.new_trace)();
+        unsafe { Pin::new_unchecked(&mut this.inner) }.poll(cx)
+    }
+}
+
+#[cfg(feature = "perf")]
+static TRACING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+/// Turns the traces on or off for the rest of the run: while off, every
+/// trace, anchor and sampled hit is skipped after checking this flag, and
+/// the profile only has the total time. On by default; `begin_profile` turns
+/// it off if the `PERF_PROFILE` environment variable is `0`, `false` or
+/// `off`, so one `perf` build can run with or without its profile. Hits that
+/// are open when it changes still end the way they began.
+#[cfg_attr(not(feature = "perf"), allow(unused_variables))]
+pub fn set_tracing(enabled: bool) {
+    #[cfg(feature = "perf")]
+    TRACING.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Whether hits are being traced, never without `perf`
+#[must_use]
+#[inline]
+pub fn tracing_enabled() -> bool {
+    #[cfg(feature = "perf")]
+    return TRACING.load(std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "perf"))]
+    false
+}
+
 /// Initializes profile environment.
 /// Ideally, this should be invoked during program start up.
 ///
@@ -138,16 +653,209 @@ impl Drop for ScopedTrace {
 ///
 /// This function is only safe to call in single-threaded program.
 /// Invoking this function in a multi-threaded program can lead to UB.
+/// With `perf-mt`, call it before spawning the threads that trace.
 #[cfg(feature = "perf")]
 pub fn begin_profile() {
+    if let Ok(value) = std::env::var("PERF_PROFILE") {
+        set_tracing(!matches!(
+            value.to_ascii_lowercase().as_str(),
+            "0" | "false" | "off"
+        ));
+    }
     // initialize lazy statics
     let _ = unsafe { timer_freq() };
     let _ = unsafe { trace_map() };
+    if tracing_enabled() {
+        let _ = anchor_overhead();
+    }
 
     // capture profile start time
     let _ = unsafe { start_ts() };
 }
 
+/// Ticks an empty hit of a trace takes, from before it begins to after it
+/// ends, see `anchor_overhead_split`.
+///
+/// # Safety
+///
+/// Same as `begin_profile` when it is the first call.
+#[cfg(feature = "perf")]
+#[must_use]
+pub fn anchor_overhead() -> u64 {
+    let overhead = anchor_overhead_split();
+    overhead.inside + overhead.outside
+}
+
+/// Ticks an empty hit of a trace adds to the exclusive times, split at its
+/// timer reads
+#[cfg(feature = "perf")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Overhead {
+    /// between the timer reads, part of the hit's own time
+    pub inside: u64,
+    /// before and after them, part of the time of the trace open around it
+    pub outside: u64,
+}
+
+#[cfg(feature = "perf")]
+impl Overhead {
+    /// Exclusive time of `trace` less the overhead of its hits and of the
+    /// hits of its children, at least 0 as the overhead is an estimate
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn subtract_from(self, trace: &Trace) -> i64 {
+        let overhead =
+            trace.hit_count as u64 * self.inside + trace.child_hits as u64 * self.outside;
+        (trace.elapsed_exclusive - overhead as i64).max(0)
+    }
+}
+
+/// Overhead of an empty hit, measured once by `begin_profile`: the least of
+/// a thousand from before it begins to after it ends, of which the least
+/// time it recorded is inside.
+///
+/// # Safety
+///
+/// Same as `begin_profile` when it is the first call.
+#[cfg(feature = "perf")]
+#[must_use]
+pub fn anchor_overhead_split() -> Overhead {
+    static CELL: RacyUnsafeCell<OnceCell<Overhead>> = RacyUnsafeCell::new(OnceCell::new());
+    *unsafe { &*CELL.get() }.get_or_init(|| {
+        // the cost of a hit at a call site of the macros
+        static SLOT: TraceSlot = TraceSlot::new();
+        let calibration = TraceId::new("perf::calibration", TraceType::Anchor);
+        let total = (0..1000)
+            .map(|_| {
+                let begin = READ_TIMER();
+                drop(ScopedTrace::new_in(&SLOT, calibration));
+                READ_TIMER() - begin
+            })
+            .min()
+            .unwrap_or_default();
+        let inside = unsafe { trace_map() }
+            .remove(&calibration)
+            .map_or(0, |trace| trace.min_elapsed.min(total));
+        Overhead {
+            inside,
+            outside: total - inside,
+        }
+    })
+}
+
+#[cfg(feature = "perf")]
+static SUBTRACT_OVERHEAD: RacyUnsafeCell<bool> = RacyUnsafeCell::new(false);
+
+/// Makes `end_and_print_profile` and `end_and_print_profile_tree` subtract
+/// the overhead of the hits of every trace and of its children from its
+/// exclusive time (see `Overhead::subtract_from`), an estimate that matters
+/// for anchors hit millions of times and the traces around them.
+///
+/// # Safety
+///
+/// This function is only safe to call in single-threaded program.
+#[cfg_attr(not(feature = "perf"), allow(unused_variables))]
+pub fn subtract_overhead(enabled: bool) {
+    #[cfg(feature = "perf")]
+    unsafe {
+        *SUBTRACT_OVERHEAD.get() = enabled;
+    }
+}
+
+/// Unit of the times `end_and_print_profile` and `end_and_print_profile_tree`
+/// print, see `set_time_unit`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// timer ticks, CPU cycles with the TSC, each followed by its milliseconds
+    #[default]
+    Cycles,
+    Milliseconds,
+    Microseconds,
+}
+
+impl std::str::FromStr for TimeUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cycles" | "ticks" => Ok(Self::Cycles),
+            "ms" => Ok(Self::Milliseconds),
+            "us" => Ok(Self::Microseconds),
+            _ => Err(format!(
+                "unknown time unit `{s}`, expected cycles, ms or us"
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+impl TimeUnit {
+    /// `ticks` of a timer at `timer_freq`, none for `Cycles`
+    #[allow(clippy::cast_precision_loss)]
+    fn convert(self, ticks: f64, timer_freq: u64) -> Option<f64> {
+        let seconds = ticks / timer_freq as f64;
+        match self {
+            Self::Cycles => None,
+            Self::Milliseconds => Some(seconds * 1e3),
+            Self::Microseconds => Some(seconds * 1e6),
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Self::Cycles => "",
+            Self::Milliseconds => "ms",
+            Self::Microseconds => "us",
+        }
+    }
+
+    /// A time of the profile: the ticks and their milliseconds, or the time in
+    /// this unit
+    #[allow(clippy::cast_precision_loss)]
+    fn format(self, ticks: i64, timer_freq: u64) -> String {
+        if let Some(time) = self.convert(ticks as f64, timer_freq) {
+            format!("{time:.3} {}", self.suffix())
+        } else {
+            let ms = Self::Milliseconds.format(ticks, timer_freq);
+            format!("{ticks}, {ms}")
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+static TIME_UNIT: RacyUnsafeCell<TimeUnit> = RacyUnsafeCell::new(TimeUnit::Cycles);
+
+/// Makes `end_and_print_profile` and `end_and_print_profile_tree` print times
+/// in `unit`, converted with the estimated timer frequency. Exports keep the
+/// ticks.
+///
+/// # Safety
+///
+/// This function is only safe to call in single-threaded program.
+#[cfg_attr(not(feature = "perf"), allow(unused_variables))]
+pub fn set_time_unit(unit: TimeUnit) {
+    #[cfg(feature = "perf")]
+    unsafe {
+        *TIME_UNIT.get() = unit;
+    }
+}
+
+#[cfg(feature = "perf")]
+fn time_unit() -> TimeUnit {
+    unsafe { *TIME_UNIT.get() }
+}
+
+/// Exclusive time of `trace`, less its estimated profiler overhead if
+/// `subtract_overhead` asked for it
+#[cfg(feature = "perf")]
+fn exclusive(trace: &Trace) -> i64 {
+    if unsafe { *SUBTRACT_OVERHEAD.get() } {
+        anchor_overhead_split().subtract_from(trace)
+    } else {
+        trace.elapsed_exclusive
+    }
+}
+
 /// Prints the perf timings of captured traces to stdout
 ///
 /// # Panics
@@ -158,9 +866,116 @@ pub fn begin_profile() {
 ///
 /// This function is only safe to call in single-threaded program.
 /// Invoking this function in a multi-threaded program can lead to UB.
+/// With `perf-mt`, it merges the traces of every thread, which must have
+/// stopped tracing (e.g. been joined). Percentages are of the wall time, so
+/// the traces of concurrent threads can add up past 100%.
 #[cfg(feature = "perf")]
-#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
 pub fn end_and_print_profile() {
+    let (timer_time, timer_freq) = print_total_time();
+
+    let unit = time_unit();
+    for (trace_id, trace) in unsafe { collect_traces() } {
+        let hits = hits(&trace);
+        let elapsed_self = exclusive(&trace);
+        let percent_wo_children = (elapsed_self as f64 / timer_time as f64) * 100.0;
+        let time_self = unit.format(elapsed_self, timer_freq);
+        if trace.elapsed_exclusive as u64 == trace.elapsed_inclusive {
+            println!("  {trace_id}[{hits}]: {time_self} ({percent_wo_children:.2}%)");
+        } else {
+            let percent_w_children = (trace.elapsed_inclusive as f64 / timer_time as f64) * 100.0;
+            let time_w_children = unit.format(trace.elapsed_inclusive as i64, timer_freq);
+            println!("  {trace_id}[{hits}]: {time_self} ({percent_wo_children:.2}%), {time_w_children} ({percent_w_children:.2}%) w/ children");
+        }
+        print_trace_details("    ", trace_id, &trace, timer_freq);
+    }
+}
+
+/// Prints the perf timings of captured traces to stdout, each indented
+/// under the trace that was open on its first hit, with its time (with
+/// children) as a percentage of that parent's. Traces hit under several
+/// parents are only nested under the first one, so their share of it can
+/// exceed 100%.
+///
+/// # Panics
+///
+/// Will panic if `begin_profile` is not invoked before calling this fn
+///
+/// # Safety
+///
+/// Same as `end_and_print_profile`.
+#[cfg(feature = "perf")]
+pub fn end_and_print_profile_tree() {
+    let (timer_time, timer_freq) = print_total_time();
+    let traces = unsafe { collect_traces() };
+    print_subtree(
+        &children_by_parent(&traces),
+        None,
+        timer_time,
+        1,
+        timer_freq,
+    );
+}
+
+/// Traces by the parent they are nested under (`None` for the roots), each
+/// list sorted by first hit like `traces`
+#[cfg(feature = "perf")]
+fn children_by_parent(traces: &[(TraceId, Trace)]) -> Children<'_> {
+    let mut children: Children = HashMap::new();
+    for (trace_id, trace) in traces {
+        children
+            .entry(trace.parent)
+            .or_default()
+            .push((*trace_id, trace));
+    }
+    children
+}
+
+#[cfg(feature = "perf")]
+type Children<'a> = HashMap<Option<TraceId>, Vec<(TraceId, &'a Trace)>>;
+
+/// Prints the children of `parent`, then theirs one level deeper
+#[cfg(feature = "perf")]
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_wrap
+)]
+fn print_subtree(
+    children: &Children,
+    parent: Option<TraceId>,
+    parent_elapsed: u64,
+    depth: usize,
+    timer_freq: u64,
+) {
+    let indent = "  ".repeat(depth);
+    for &(trace_id, trace) in children.get(&parent).map_or(&[][..], Vec::as_slice) {
+        let hits = hits(trace);
+        let elapsed = trace.elapsed_inclusive;
+        let percent = (elapsed as f64 / parent_elapsed as f64) * 100.0;
+        let of = if parent.is_some() {
+            "of parent"
+        } else {
+            "of total"
+        };
+        let elapsed_self = exclusive(trace);
+        let time = time_unit().format(elapsed as i64, timer_freq);
+        if elapsed_self as u64 == elapsed {
+            println!("{indent}{trace_id}[{hits}]: {time} ({percent:.2}% {of})");
+        } else {
+            let time_self = time_unit().format(elapsed_self, timer_freq);
+            println!("{indent}{trace_id}[{hits}]: {time} ({percent:.2}% {of}), {time_self} self");
+        }
+        print_trace_details(&format!("{indent}  "), trace_id, trace, timer_freq);
+        print_subtree(children, Some(trace_id), elapsed, depth + 1, timer_freq);
+    }
+}
+
+/// Prints the time since `begin_profile`, returns it in timer ticks and the
+/// timer frequency
+#[cfg(feature = "perf")]
+#[allow(clippy::cast_precision_loss)]
+fn print_total_time() -> (u64, u64) {
     let end = READ_TIMER();
     let start = unsafe { start_ts() };
     assert!(end > start, "ERROR: Profile end time is earlier than start time. `begin_profile` call should precede `end_and_print_profile` call.");
@@ -169,24 +984,94 @@ pub fn end_and_print_profile() {
     let timer_freq = unsafe { timer_freq() };
     let total_time_ms: f64 = (1000f64 * timer_time as f64) / timer_freq as f64;
     println!("Total time: {total_time_ms} ms (CPU freq {timer_freq})");
+    if !tracing_enabled() {
+        println!("Tracing disabled, see `PERF_PROFILE`");
+    }
+    if unsafe { *SUBTRACT_OVERHEAD.get() } {
+        let overhead = anchor_overhead_split();
+        println!(
+            "Anchor overhead: {} ticks per hit, {} in its self time and {} in its parent's, subtracted from them",
+            overhead.inside + overhead.outside,
+            overhead.inside,
+            overhead.outside
+        );
+    }
+    (timer_time, timer_freq)
+}
 
-    let trace_map = unsafe { trace_map() };
-    let mut trace_ids = trace_map.keys().collect::<Vec<_>>();
-    trace_ids.sort_unstable_by_key(|k| trace_map.get(*k).unwrap().order);
-    for trace_id in trace_ids.into_iter() {
-        let trace = trace_map.get(trace_id).unwrap();
-        let hits = trace.hit_count;
-        if trace.elapsed_exclusive as u64 == trace.elapsed_inclusive {
-            let elapsed = trace.elapsed_inclusive;
-            let percent = (elapsed as f64 / timer_time as f64) * 100.0;
-            println!("  {trace_id}[{hits}]: {elapsed} ({percent:.2}%)");
+#[cfg(feature = "perf")]
+fn hits(trace: &Trace) -> String {
+    match trace.sample_every {
+        1 => trace.hit_count.to_string(),
+        every => format!("{} sampled 1/{every}", trace.hit_count),
+    }
+}
+
+/// Per iteration times, bandwidth, page faults and allocations of a trace that
+/// has them
+#[cfg(feature = "perf")]
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_wrap)]
+fn print_trace_details(indent: &str, trace_id: TraceId, trace: &Trace, timer_freq: u64) {
+    if let TraceType::Iteration(_) = trace_id.ty {
+        let avg = trace.elapsed_inclusive as f64 / trace.hit_count as f64;
+        let (min, max) = (trace.min_elapsed, trace.max_elapsed);
+        let std_dev = (trace.elapsed_squares / trace.hit_count as f64 - avg * avg)
+            .max(0.0)
+            .sqrt();
+        let unit = time_unit();
+        if let Some(scale) = unit.convert(1.0, timer_freq) {
+            println!(
+                "{indent}per iteration ({}): min {:.3} avg {:.3} max {:.3} std dev {:.3}",
+                unit.suffix(),
+                min as f64 * scale,
+                avg * scale,
+                max as f64 * scale,
+                std_dev * scale,
+            );
         } else {
-            let percent_wo_children = (trace.elapsed_exclusive as f64 / timer_time as f64) * 100.0;
-            let percent_w_children = (trace.elapsed_inclusive as f64 / timer_time as f64) * 100.0;
-            let elapsed_self = trace.elapsed_exclusive;
-            println!("  {trace_id}[{hits}]: {elapsed_self} ({percent_wo_children:.2}%, {percent_w_children:.2}% w/ children)");
+            println!(
+                "{indent}per iteration: min {min} avg {avg:.2} max {max} std dev {std_dev:.2}"
+            );
         }
     }
+    if trace.processed_byte_count > 0 {
+        const MEGABYTE: f64 = 1024.0 * 1024.0;
+        const GIGABYTE: f64 = MEGABYTE * 1024.0;
+        let seconds = trace.elapsed_inclusive as f64 / timer_freq as f64;
+        let bytes = trace.processed_byte_count as f64;
+        let megabytes = bytes / MEGABYTE;
+        let gigabytes_per_second = bytes / GIGABYTE / seconds;
+        println!("{indent}{megabytes:.3}mb at {gigabytes_per_second:.2}gb/s");
+    }
+    if let Some(PageFaults { soft, hard }) = trace.page_faults {
+        println!("{indent}page faults: {soft} soft, {hard} hard");
+    }
+    if trace.allocations_inclusive.count > 0 {
+        let (exclusive, inclusive) = (trace.allocations_exclusive, trace.allocations_inclusive);
+        println!(
+            "{indent}allocations: {} ({} bytes) self, {} ({} bytes) w/ children",
+            exclusive.count, exclusive.bytes, inclusive.count, inclusive.bytes
+        );
+    }
+    #[cfg(feature = "precise-timer")]
+    if trace.precise {
+        let overhead = unsafe { precise_timer_overhead() };
+        println!("{indent}serialized timer: ~{overhead} more ticks per hit than plain reads");
+    }
+    if trace.depths.len() > 1 {
+        for (depth, DepthStats { hit_count, elapsed }) in trace.depths.iter().enumerate() {
+            let percent = (*elapsed as f64 / trace.elapsed_inclusive as f64) * 100.0;
+            let time = time_unit().format(*elapsed as i64, timer_freq);
+            println!("{indent}depth {depth}[{hit_count}]: {time} ({percent:.2}%)");
+        }
+    }
+}
+
+/// Without `perf` there are no anchors
+#[cfg(not(feature = "perf"))]
+#[must_use]
+pub fn anchor_overhead() -> u64 {
+    0
 }
 
 #[cfg(not(feature = "perf"))]
@@ -206,12 +1091,48 @@ pub fn end_and_print_profile() {
     println!("Total time: {total_time_ms} ms (CPU freq {cpu_freq})");
 }
 
+/// Without `perf` there are no traces to nest, so this is `end_and_print_profile`
+#[cfg(not(feature = "perf"))]
+pub fn end_and_print_profile_tree() {
+    end_and_print_profile();
+}
+
 #[cfg(not(feature = "perf"))]
 pub struct ScopedTrace {}
 
+#[cfg(not(feature = "perf"))]
+pub struct AnchorHandle {}
+
+/// Without `perf` a call site's slot holds nothing
+#[cfg(not(feature = "perf"))]
+pub mod trace {
+    #[derive(Default)]
+    pub struct TraceSlot;
+
+    impl TraceSlot {
+        #[must_use]
+        pub const fn new() -> Self {
+            Self
+        }
+    }
+}
+
+#[cfg(not(feature = "perf"))]
+#[must_use]
+pub fn anchor_begin(_: &trace::TraceSlot, _: &'static str) -> AnchorHandle {
+    AnchorHandle {}
+}
+
+#[cfg(not(feature = "perf"))]
+pub fn anchor_end(_: AnchorHandle) {}
+
 #[cfg(not(feature = "perf"))]
 impl ScopedTrace {
-    pub fn new_section(_: &'static str, _: &'static str) -> Self {
+    pub fn new_section(_: &trace::TraceSlot, _: &'static str, _: &'static str) -> Self {
+        Self {}
+    }
+
+    pub fn new_bandwidth(_: &trace::TraceSlot, _: &'static str, _: &'static str, _: u64) -> Self {
         Self {}
     }
 }
@@ -0,0 +1,484 @@
+//! Prometheus text exposition of the profile and of repetition tester results.
+//! Write it to a file for `node_exporter`'s textfile collector, or serve it over
+//! HTTP with `MetricsServer`.
+
+use std::{
+    fmt::{self, Display, Write as _},
+    io::{self, Read, Write},
+    net::{AddrParseError, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{repetition::RepetitionResults, start_ts, timer_freq, READ_TIMER};
+
+struct Family {
+    name: String,
+    help: &'static str,
+    kind: &'static str,
+    /// rendered label set and value
+    samples: Vec<(String, f64)>,
+}
+
+/// Metric families in the order they were first added
+#[derive(Default)]
+pub struct Metrics {
+    families: Vec<Family>,
+}
+
+impl Metrics {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn sample(
+        &mut self,
+        name: &str,
+        help: &'static str,
+        kind: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) -> &mut Self {
+        let mut rendered = String::new();
+        for (i, (label, value)) in labels.iter().enumerate() {
+            let separator = if i == 0 { "{" } else { "," };
+            let _ = write!(rendered, "{separator}{label}=\"{}\"", escape(value));
+        }
+        if !labels.is_empty() {
+            rendered.push('}');
+        }
+        let index = if let Some(index) = self.families.iter().position(|f| f.name == name) {
+            index
+        } else {
+            self.families.push(Family {
+                name: name.to_owned(),
+                help,
+                kind,
+                samples: Vec::new(),
+            });
+            self.families.len() - 1
+        };
+        self.families[index].samples.push((rendered, value));
+        self
+    }
+
+    /// Adds a sample to the gauge `name`, declared with `help` on first use
+    pub fn gauge(
+        &mut self,
+        name: &str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) -> &mut Self {
+        self.sample(name, help, "gauge", labels, value)
+    }
+
+    /// Adds a sample to the counter `name`, declared with `help` on first use
+    pub fn counter(
+        &mut self,
+        name: &str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) -> &mut Self {
+        self.sample(name, help, "counter", labels, value)
+    }
+
+    /// Adds the time since `begin_profile` and, with the `perf` feature, the
+    /// timings, hit counts and processed bytes of every trace
+    ///
+    /// # Safety
+    ///
+    /// Reads the profiler's statics, so it is only safe to call in
+    /// single-threaded program.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn add_profile(&mut self) -> &mut Self {
+        let timer_freq = unsafe { timer_freq() };
+        let seconds = |ticks: u64| ticks as f64 / timer_freq as f64;
+        let elapsed = READ_TIMER() - unsafe { start_ts() };
+        self.gauge(
+            "perf_profile_seconds",
+            "Time since begin_profile",
+            &[],
+            seconds(elapsed),
+        );
+        self.gauge(
+            "perf_timer_frequency_hertz",
+            "Estimated CPU timer frequency",
+            &[],
+            timer_freq as f64,
+        );
+
+        #[cfg(feature = "perf")]
+        {
+            for (trace_id, trace) in unsafe { crate::trace::collect_traces() } {
+                let name = trace_id.to_string();
+                let labels = [("trace", name.as_str())];
+                #[allow(clippy::cast_sign_loss)]
+                let exclusive = trace.elapsed_exclusive.max(0) as u64;
+                self.gauge(
+                    "perf_trace_exclusive_seconds",
+                    "Time spent in a trace without its children",
+                    &labels,
+                    seconds(exclusive),
+                )
+                .gauge(
+                    "perf_trace_inclusive_seconds",
+                    "Time spent in a trace with its children",
+                    &labels,
+                    seconds(trace.elapsed_inclusive),
+                )
+                .counter(
+                    "perf_trace_hits_total",
+                    "Recorded hits of a trace, one in sample_every when sampled",
+                    &labels,
+                    trace.hit_count as f64,
+                );
+                if trace.hit_count > 0 {
+                    self.gauge(
+                        "perf_trace_hit_min_seconds",
+                        "Shortest single hit of a trace",
+                        &labels,
+                        seconds(trace.min_elapsed),
+                    )
+                    .gauge(
+                        "perf_trace_hit_max_seconds",
+                        "Longest single hit of a trace",
+                        &labels,
+                        seconds(trace.max_elapsed),
+                    );
+                }
+                if trace.processed_byte_count > 0 {
+                    self.counter(
+                        "perf_trace_processed_bytes_total",
+                        "Bytes attributed to a trace",
+                        &labels,
+                        trace.processed_byte_count as f64,
+                    );
+                }
+                if let Some(faults) = trace.page_faults {
+                    for (kind, count) in [("soft", faults.soft), ("hard", faults.hard)] {
+                        self.counter(
+                            "perf_trace_page_faults_total",
+                            "Page faults during the hits of a trace that counted them",
+                            &[("trace", name.as_str()), ("kind", kind)],
+                            count as f64,
+                        );
+                    }
+                }
+                if trace.allocations_inclusive.count > 0 {
+                    self.counter(
+                        "perf_trace_allocations_total",
+                        "Allocations during the hits of a trace, without children",
+                        &labels,
+                        trace.allocations_exclusive.count as f64,
+                    )
+                    .counter(
+                        "perf_trace_allocated_bytes_total",
+                        "Bytes allocated during the hits of a trace, without children",
+                        &labels,
+                        trace.allocations_exclusive.bytes as f64,
+                    );
+                }
+            }
+        }
+        self
+    }
+
+    /// Adds the run times of `test`, and its bandwidths if it processes bytes
+    #[allow(clippy::cast_precision_loss)]
+    pub fn add_repetition(&mut self, test: &str, results: &RepetitionResults) -> &mut Self {
+        const HELP: &str = "Run time of a repetition test";
+        self.gauge(
+            "perf_repetition_seconds",
+            HELP,
+            &[("test", test), ("stat", "min")],
+            results.min_seconds(),
+        )
+        .gauge(
+            "perf_repetition_seconds",
+            HELP,
+            &[("test", test), ("stat", "avg")],
+            results.avg_seconds(),
+        )
+        .gauge(
+            "perf_repetition_seconds",
+            HELP,
+            &[("test", test), ("stat", "max")],
+            results.max_seconds(),
+        )
+        .counter(
+            "perf_repetition_runs_total",
+            "Runs of a repetition test",
+            &[("test", test)],
+            results.test_count as f64,
+        );
+        if results.byte_count > 0 {
+            const HELP: &str = "Bytes per second of a repetition test";
+            self.gauge(
+                "perf_repetition_bandwidth_bytes_per_second",
+                HELP,
+                &[("test", test), ("stat", "best")],
+                results.best_bandwidth(),
+            )
+            .gauge(
+                "perf_repetition_bandwidth_bytes_per_second",
+                HELP,
+                &[("test", test), ("stat", "avg")],
+                results.avg_bandwidth(),
+            );
+        }
+        self
+    }
+
+    /// Writes the metrics to `path` through a temporary file in the same
+    /// directory, so a collector never reads a partial file
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the write or of the rename.
+    pub fn write_to_file(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, self.to_string())?;
+        std::fs::rename(&temporary, path)
+    }
+}
+
+/// `\`, `"` and newlines are escaped in label values
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for family in &self.families {
+            writeln!(f, "# HELP {} {}", family.name, family.help)?;
+            writeln!(f, "# TYPE {} {}", family.name, family.kind)?;
+            for (labels, value) in &family.samples {
+                writeln!(f, "{}{labels} {value}", family.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serves the last published `Metrics` to every HTTP request, from a
+/// background thread
+pub struct MetricsServer {
+    body: Arc<Mutex<String>>,
+    local_addr: SocketAddr,
+    thread: JoinHandle<io::Error>,
+}
+
+impl MetricsServer {
+    /// Starts serving on `addr`, with no metrics until the first `publish`
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of binding `addr`.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let body = Arc::new(Mutex::new(String::new()));
+        let served = Arc::clone(&body);
+        let thread = std::thread::spawn(move || loop {
+            match listener.accept() {
+                // A client hanging up early only fails its own response
+                Ok((stream, _)) => {
+                    let _ = respond(stream, &served);
+                }
+                Err(e) => return e,
+            }
+        });
+        Ok(Self {
+            body,
+            local_addr,
+            thread,
+        })
+    }
+
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Replaces the metrics served to subsequent requests
+    pub fn publish(&self, metrics: &Metrics) {
+        *self.body.lock().unwrap_or_else(PoisonError::into_inner) = metrics.to_string();
+    }
+
+    /// Blocks, serving the last published metrics until the listener fails
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that stopped the listener.
+    pub fn serve_forever(self) -> io::Result<()> {
+        Err(self
+            .thread
+            .join()
+            .unwrap_or_else(|_| io::Error::other("metrics server panicked")))
+    }
+}
+
+/// How long a client may take to send its request or read the response
+/// before the server moves on to the next one
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Parses a `--metrics-addr`: a port on localhost, or any socket address
+/// (e.g. `0.0.0.0:9100`) to serve on other interfaces
+///
+/// # Errors
+///
+/// Returns the error of parsing `s` as a socket address if it isn't a port.
+pub fn listen_addr(s: &str) -> Result<SocketAddr, AddrParseError> {
+    match s.parse::<u16>() {
+        Ok(port) => Ok((Ipv4Addr::LOCALHOST, port).into()),
+        Err(_) => s.parse(),
+    }
+}
+
+/// Where the metrics of a run go: a file rewritten on every update and/or
+/// an HTTP server on an address
+pub struct Exporter {
+    file: Option<PathBuf>,
+    server: Option<MetricsServer>,
+}
+
+impl Exporter {
+    /// Starts serving on `addr` if given, see `listen_addr`
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of binding `addr`.
+    pub fn new(file: Option<PathBuf>, addr: Option<SocketAddr>) -> io::Result<Self> {
+        let server = addr.map(MetricsServer::bind).transpose()?;
+        Ok(Self { file, server })
+    }
+
+    /// Publishes `metrics` and rewrites the file
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of writing the file.
+    pub fn update(&self, metrics: &Metrics) -> io::Result<()> {
+        if let Some(server) = &self.server {
+            server.publish(metrics);
+        }
+        match &self.file {
+            Some(path) => metrics.write_to_file(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Keeps serving the last update until interrupted, if serving at all
+    ///
+    /// # Errors
+    ///
+    /// Returns the error that stopped the server.
+    pub fn finish(self) -> io::Result<()> {
+        let Some(server) = self.server else {
+            return Ok(());
+        };
+        println!(
+            "Serving metrics on http://{}/metrics until interrupted",
+            server.local_addr()
+        );
+        server.serve_forever()
+    }
+}
+
+fn respond(mut stream: TcpStream, body: &Mutex<String>) -> io::Result<()> {
+    // One client at a time is served, an idle one mustn't hold up the others
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    // The request itself doesn't matter, every path gets the metrics
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request)?;
+    let body = body.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_families_once_with_escaped_labels() {
+        let results = RepetitionResults {
+            test_count: 4,
+            total_time: 400,
+            min_time: 50,
+            max_time: 200,
+            byte_count: 1000,
+            timer_freq: 1000,
+        };
+        let mut metrics = Metrics::new();
+        metrics
+            .add_repetition("scalar", &results)
+            .add_repetition("say \"hi\"\\", &results);
+        let text = metrics.to_string();
+
+        assert_eq!(
+            text.matches("# TYPE perf_repetition_seconds gauge").count(),
+            1
+        );
+        assert!(text.contains("perf_repetition_seconds{test=\"scalar\",stat=\"min\"} 0.05\n"));
+        assert!(text.contains("perf_repetition_runs_total{test=\"say \\\"hi\\\"\\\\\"} 4\n"));
+        assert!(text.contains(
+            "perf_repetition_bandwidth_bytes_per_second{test=\"scalar\",stat=\"best\"} 20000\n"
+        ));
+    }
+
+    #[test]
+    fn serves_published_metrics() {
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        let mut metrics = Metrics::new();
+        metrics.gauge("answer", "The answer", &[], 42.0);
+        server.publish(&metrics);
+
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response
+            .ends_with("\r\n\r\n# HELP answer The answer\n# TYPE answer gauge\nanswer 42\n"));
+    }
+
+    #[test]
+    fn idle_clients_time_out() {
+        let server = MetricsServer::bind("127.0.0.1:0").unwrap();
+        let _idle = TcpStream::connect(server.local_addr()).unwrap();
+
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.set_read_timeout(Some(CLIENT_TIMEOUT * 5)).unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn listens_on_localhost_by_default() {
+        assert_eq!(
+            listen_addr("9100"),
+            Ok(SocketAddr::from(([127, 0, 0, 1], 9100)))
+        );
+        assert_eq!(
+            listen_addr("0.0.0.0:9100"),
+            Ok(SocketAddr::from(([0, 0, 0, 0], 9100)))
+        );
+        assert!(listen_addr("localhost").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use std::{hint::black_box, io::Write};
+
+use crate::{timer_freq, READ_TIMER};
+
+/// Runs a test repeatedly until its fastest time stops improving for a while.
+///
+/// # Safety
+///
+/// Estimates the timer frequency through the profiler's lazy statics, so it is
+/// only safe to use in single-threaded program.
+pub struct RepetitionTester {
+    byte_count: u64,
+    try_for_seconds: u64,
+    live: bool,
+}
+
+/// Timer ticks of the runs of one `RepetitionTester::run`
+#[derive(Clone, Copy, Debug)]
+pub struct RepetitionResults {
+    pub test_count: u64,
+    pub total_time: u64,
+    pub min_time: u64,
+    pub max_time: u64,
+    /// bytes processed by each run
+    pub byte_count: u64,
+    pub timer_freq: u64,
+}
+
+impl RepetitionTester {
+    /// Tester for a test that processes `byte_count` bytes per run
+    #[must_use]
+    pub fn new(byte_count: u64) -> Self {
+        Self {
+            byte_count,
+            try_for_seconds: 10,
+            live: false,
+        }
+    }
+
+    /// Stops once no new minimum was found for `seconds` (10 by default)
+    #[must_use]
+    pub fn try_for_seconds(mut self, seconds: u64) -> Self {
+        self.try_for_seconds = seconds;
+        self
+    }
+
+    /// Prints each new minimum to stderr while running, on a line that is
+    /// cleared once the run settles
+    #[must_use]
+    pub fn live(mut self, live: bool) -> Self {
+        self.live = live;
+        self
+    }
+
+    /// Times `test` until the minimum settles. Its return value is passed
+    /// through `black_box` so the work isn't optimized away.
+    pub fn run<T>(&self, mut test: impl FnMut() -> T) -> RepetitionResults {
+        let timer_freq = unsafe { timer_freq() };
+        let mut results = RepetitionResults {
+            test_count: 0,
+            total_time: 0,
+            min_time: u64::MAX,
+            max_time: 0,
+            byte_count: self.byte_count,
+            timer_freq,
+        };
+        let try_for = self.try_for_seconds * timer_freq;
+        let mut last_improvement = READ_TIMER();
+        loop {
+            let begin = READ_TIMER();
+            black_box(test());
+            let end = READ_TIMER();
+            let elapsed = end - begin;
+
+            results.test_count += 1;
+            results.total_time += elapsed;
+            results.max_time = results.max_time.max(elapsed);
+            if elapsed < results.min_time {
+                results.min_time = elapsed;
+                last_improvement = end;
+                if self.live {
+                    print_min(&results);
+                }
+            } else if end - last_improvement > try_for {
+                if self.live {
+                    eprint!("\r\x1b[2K");
+                }
+                return results;
+            }
+        }
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn print_min(results: &RepetitionResults) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(
+        stderr,
+        "\r\x1b[2KMin: {} ({:.3}ms",
+        results.min_time,
+        results.min_seconds() * 1000.0
+    );
+    if results.byte_count > 0 {
+        let _ = write!(
+            stderr,
+            " {:.3}gb/s",
+            results.best_bandwidth() / (1024.0 * 1024.0 * 1024.0)
+        );
+    }
+    let _ = write!(stderr, ")");
+    let _ = stderr.flush();
+}
+
+#[allow(clippy::cast_precision_loss)]
+impl RepetitionResults {
+    fn seconds(&self, time: u64) -> f64 {
+        time as f64 / self.timer_freq as f64
+    }
+
+    #[must_use]
+    pub fn min_seconds(&self) -> f64 {
+        self.seconds(self.min_time)
+    }
+
+    #[must_use]
+    pub fn max_seconds(&self) -> f64 {
+        self.seconds(self.max_time)
+    }
+
+    #[must_use]
+    pub fn avg_seconds(&self) -> f64 {
+        self.seconds(self.total_time) / self.test_count as f64
+    }
+
+    /// Bytes per second of the fastest run
+    #[must_use]
+    pub fn best_bandwidth(&self) -> f64 {
+        self.byte_count as f64 / self.min_seconds()
+    }
+
+    /// Bytes per second over all runs
+    #[must_use]
+    pub fn avg_bandwidth(&self) -> f64 {
+        self.byte_count as f64 / self.avg_seconds()
+    }
+}
@@ -0,0 +1,420 @@
+//! Profiles exported with `ProfileFormat::Json` read back, and the change of
+//! every anchor between two of them, to catch regressions across runs.
+
+use std::{fmt, io, path::Path};
+
+/// An anchor of an exported profile
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorReport {
+    pub name: String,
+    pub hits: u64,
+    /// with children
+    pub inclusive_ticks: u64,
+    /// without children. Their time is taken off as each of them ends and the
+    /// anchor's own is added when it does, so an anchor still open when the
+    /// profile was exported (e.g. the one around the export) is negative.
+    pub exclusive_ticks: i64,
+}
+
+/// The part of an exported profile that `diff` compares
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub total_ticks: u64,
+    pub timer_frequency: u64,
+    /// in the order of the export, by first hit
+    pub anchors: Vec<AnchorReport>,
+}
+
+impl ProfileReport {
+    /// # Errors
+    ///
+    /// Returns an error if `path` cannot be read, or `InvalidData` if it
+    /// isn't a profile exported as JSON.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// # Errors
+    ///
+    /// Returns why `json` isn't a profile exported as JSON.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut parser = Parser { rest: json };
+        let profile = parser.value()?;
+        parser.skip_whitespace();
+        if !parser.rest.is_empty() {
+            return Err(format!("trailing data `{}`", parser.rest));
+        }
+        let anchors = profile
+            .field("traces")?
+            .array()?
+            .iter()
+            .map(|trace| {
+                Ok(AnchorReport {
+                    name: trace.field("name")?.string()?.to_owned(),
+                    hits: trace.field("hits")?.number()?,
+                    inclusive_ticks: trace.field("inclusive_ticks")?.number()?,
+                    exclusive_ticks: trace.field("exclusive_ticks")?.number()?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self {
+            total_ticks: profile.field("total_ticks")?.number()?,
+            timer_frequency: profile.field("timer_frequency")?.number()?,
+            anchors,
+        })
+    }
+}
+
+/// Inclusive ticks of an anchor in both profiles, `None` where it's missing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorDiff {
+    pub name: String,
+    pub old: Option<u64>,
+    pub new: Option<u64>,
+}
+
+impl AnchorDiff {
+    /// Ticks gained, if the anchor is in both profiles
+    #[must_use]
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn delta(&self) -> Option<i64> {
+        Some(self.new? as i64 - self.old? as i64)
+    }
+
+    /// `delta` in percent of the old ticks
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn change_percent(&self) -> Option<f64> {
+        let old = self.old.filter(|&old| old > 0)?;
+        Some(self.delta()? as f64 / old as f64 * 100.0)
+    }
+
+    /// Slower by more than `threshold_percent`
+    #[must_use]
+    pub fn regressed(&self, threshold_percent: f64) -> bool {
+        self.change_percent()
+            .is_some_and(|change| change > threshold_percent)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileDiff {
+    /// ticks of the whole profiles
+    pub total: AnchorDiff,
+    /// in the order of the new profile, then the anchors it lost
+    pub anchors: Vec<AnchorDiff>,
+}
+
+impl ProfileDiff {
+    /// The anchors slower by more than `threshold_percent`
+    pub fn regressions(&self, threshold_percent: f64) -> impl Iterator<Item = &AnchorDiff> {
+        self.anchors
+            .iter()
+            .filter(move |anchor| anchor.regressed(threshold_percent))
+    }
+}
+
+/// Compares the inclusive ticks of every anchor of `old` and `new`. Ticks
+/// are only comparable between runs on the same machine.
+#[must_use]
+pub fn diff(old: &ProfileReport, new: &ProfileReport) -> ProfileDiff {
+    let ticks = |profile: &ProfileReport, name: &str| {
+        profile
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == name)
+            .map(|anchor| anchor.inclusive_ticks)
+    };
+    let mut anchors: Vec<_> = new
+        .anchors
+        .iter()
+        .map(|anchor| AnchorDiff {
+            name: anchor.name.clone(),
+            old: ticks(old, &anchor.name),
+            new: Some(anchor.inclusive_ticks),
+        })
+        .collect();
+    anchors.extend(
+        old.anchors
+            .iter()
+            .filter(|anchor| ticks(new, &anchor.name).is_none())
+            .map(|anchor| AnchorDiff {
+                name: anchor.name.clone(),
+                old: Some(anchor.inclusive_ticks),
+                new: None,
+            }),
+    );
+    ProfileDiff {
+        total: AnchorDiff {
+            name: String::from("total"),
+            old: Some(old.total_ticks),
+            new: Some(new.total_ticks),
+        },
+        anchors,
+    }
+}
+
+/// One row per anchor: old and new ticks, their delta and change
+impl fmt::Display for ProfileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .anchors
+            .iter()
+            .map(|anchor| anchor.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("anchor".len());
+        writeln!(
+            f,
+            "{:width$}  {:>14}  {:>14}  {:>14}  {:>8}",
+            "anchor", "old ticks", "new ticks", "delta", "change"
+        )?;
+        for anchor in std::iter::once(&self.total).chain(&self.anchors) {
+            let ticks =
+                |ticks: Option<u64>| ticks.map_or_else(|| "-".to_owned(), |t| t.to_string());
+            let delta = anchor
+                .delta()
+                .map_or_else(|| "-".to_owned(), |delta| format!("{delta:+}"));
+            let change = anchor
+                .change_percent()
+                .map_or_else(|| "-".to_owned(), |change| format!("{change:+.2}%"));
+            writeln!(
+                f,
+                "{:width$}  {:>14}  {:>14}  {delta:>14}  {change:>8}",
+                anchor.name,
+                ticks(anchor.old),
+                ticks(anchor.new),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// JSON value, numbers kept as written so that ticks past 2^53 stay exact
+enum Value<'a> {
+    Null,
+    Bool,
+    Number(&'a str),
+    String(String),
+    Array(Vec<Value<'a>>),
+    Object(Vec<(String, Value<'a>)>),
+}
+
+impl<'a> Value<'a> {
+    fn field(&self, name: &str) -> Result<&Value<'a>, String> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("missing `{name}`")),
+            _ => Err(format!("expected an object with `{name}`")),
+        }
+    }
+
+    fn array(&self) -> Result<&[Value<'a>], String> {
+        match self {
+            Self::Array(values) => Ok(values),
+            _ => Err(String::from("expected an array")),
+        }
+    }
+
+    fn string(&self) -> Result<&str, String> {
+        match self {
+            Self::String(s) => Ok(s),
+            _ => Err(String::from("expected a string")),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&self) -> Result<T, String> {
+        match self {
+            Self::Number(text) => text
+                .parse()
+                .map_err(|_| format!("`{text}` is out of range")),
+            _ => Err(String::from("expected a number")),
+        }
+    }
+}
+
+/// Reads the JSON that `export` writes, which only escapes with `\"`, `\\`
+/// and `\uXXXX`, but accepts any JSON
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        self.rest
+            .strip_prefix(token)
+            .map(|rest| self.rest = rest)
+            .is_some()
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!("expected `{token}` at `{}`", self.excerpt()))
+        }
+    }
+
+    fn excerpt(&self) -> &str {
+        let end = self
+            .rest
+            .char_indices()
+            .nth(20)
+            .map_or(self.rest.len(), |(i, _)| i);
+        &self.rest[..end]
+    }
+
+    fn value(&mut self) -> Result<Value<'a>, String> {
+        self.skip_whitespace();
+        match self.rest.as_bytes().first() {
+            Some(b'{') => {
+                self.expect("{")?;
+                let mut fields = Vec::new();
+                if !self.eat("}") {
+                    loop {
+                        let name = self.string()?;
+                        self.expect(":")?;
+                        fields.push((name, self.value()?));
+                        if self.eat("}") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Value::Object(fields))
+            }
+            Some(b'[') => {
+                self.expect("[")?;
+                let mut values = Vec::new();
+                if !self.eat("]") {
+                    loop {
+                        values.push(self.value()?);
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                Ok(Value::Array(values))
+            }
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'-' | b'0'..=b'9') => {
+                let end = self
+                    .rest
+                    .find(|c: char| !matches!(c, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+                    .unwrap_or(self.rest.len());
+                let (number, rest) = self.rest.split_at(end);
+                self.rest = rest;
+                Ok(Value::Number(number))
+            }
+            _ if self.eat("null") => Ok(Value::Null),
+            _ if self.eat("true") || self.eat("false") => Ok(Value::Bool),
+            _ => Err(format!("expected a value at `{}`", self.excerpt())),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut out = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(out);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let code = u32::from_str_radix(&hex, 16)
+                            .map_err(|_| format!("invalid escape `\\u{hex}`"))?;
+                        out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some(c @ ('"' | '\\' | '/')) => out.push(c),
+                    _ => return Err(String::from("invalid escape in a string")),
+                },
+                c => out.push(c),
+            }
+        }
+        Err(String::from("unterminated string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_anchors_of_exported_profiles() {
+        let old = ProfileReport::from_json(
+            r#"{"total_seconds":1,"total_ticks":1000,"timer_frequency":1000,"traces":[
+{"name":"main::fn","kind":"fn","parent":null,"hits":1,"sample_every":1,"inclusive_ticks":900,"exclusive_ticks":100,"min_ticks":900,"max_ticks":900,"bytes":0,"page_faults":null},
+{"name":"parse::\"json\"::section","kind":"section","parent":"main::fn","hits":2,"sample_every":1,"inclusive_ticks":500,"exclusive_ticks":500,"min_ticks":200,"max_ticks":300,"bytes":10,"page_faults":{"soft":1,"hard":0}},
+{"name":"old::fn","kind":"fn","parent":"main::fn","hits":1,"sample_every":1,"inclusive_ticks":300,"exclusive_ticks":-5,"min_ticks":300,"max_ticks":300,"bytes":0,"page_faults":null}
+]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            old.anchors[1],
+            AnchorReport {
+                name: String::from("parse::\"json\"::section"),
+                hits: 2,
+                inclusive_ticks: 500,
+                exclusive_ticks: 500,
+            }
+        );
+        assert_eq!(old.anchors[2].exclusive_ticks, -5);
+
+        let new = ProfileReport {
+            total_ticks: 1100,
+            timer_frequency: 1000,
+            anchors: vec![
+                AnchorReport {
+                    inclusive_ticks: 880,
+                    ..old.anchors[0].clone()
+                },
+                AnchorReport {
+                    inclusive_ticks: 600,
+                    ..old.anchors[1].clone()
+                },
+                AnchorReport {
+                    name: String::from("new::fn"),
+                    hits: 1,
+                    inclusive_ticks: 10,
+                    exclusive_ticks: 10,
+                },
+            ],
+        };
+        let diff = diff(&old, &new);
+        assert_eq!(diff.total.change_percent(), Some(10.0));
+        let names: Vec<_> = diff.anchors.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["main::fn", "parse::\"json\"::section", "new::fn", "old::fn"]
+        );
+        assert_eq!(diff.anchors[0].delta(), Some(-20));
+        assert_eq!(diff.anchors[1].change_percent(), Some(20.0));
+        assert_eq!(diff.anchors[2].delta(), None);
+        assert_eq!(diff.anchors[3].new, None);
+        let regressed: Vec<_> = diff.regressions(5.0).map(|a| a.name.as_str()).collect();
+        assert_eq!(regressed, ["parse::\"json\"::section"]);
+        assert_eq!(diff.regressions(20.0).count(), 0);
+
+        assert!(ProfileReport::from_json(r#"{"total_ticks":1}"#).is_err());
+        assert!(ProfileReport::from_json("{").is_err());
+    }
+}
@@ -1,49 +1,467 @@
-use crate::racy_unsafe_cell::RacyUnsafeCell;
-use std::{cell::OnceCell, collections::HashMap, fmt::Display, hash::Hash};
+use crate::Allocations;
+use nix::sys::resource::{getrusage, UsageWho};
+#[cfg(feature = "perf-mt")]
+use std::cell::UnsafeCell;
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize, Ordering},
+        LazyLock, Mutex,
+    },
+};
+#[cfg(not(feature = "perf-mt"))]
+use {crate::racy_unsafe_cell::RacyUnsafeCell, std::cell::OnceCell};
 
-pub static CURRENT_TRACE: RacyUnsafeCell<Option<TraceId>> = RacyUnsafeCell::new(None);
-pub static TRACE_ID: RacyUnsafeCell<usize> = RacyUnsafeCell::new(0);
+pub static TRACE_ID: AtomicUsize = AtomicUsize::new(0);
 
-pub unsafe fn trace_map() -> &'static mut HashMap<TraceId, Trace> {
-    static CELL: RacyUnsafeCell<OnceCell<HashMap<TraceId, Trace>>> =
-        RacyUnsafeCell::new(OnceCell::new());
-    (*CELL.get()).get_mut_or_init(|| HashMap::with_capacity(4096))
+/// Slot of every trace hit so far, by id, shared by all threads so that a
+/// slot indexes the traces of any of them
+static SLOTS: LazyLock<Mutex<HashMap<TraceId, usize>>> =
+    LazyLock::new(|| Mutex::new(HashMap::with_capacity(4096)));
+
+/// Slot of `trace_id`, the next free one on its first hit. Only a call site's
+/// first hit of an id looks it up, so that sites with equal ids share a trace.
+fn slot_of(trace_id: TraceId) -> usize {
+    let mut slots = SLOTS.lock().unwrap();
+    let next = slots.len();
+    *slots.entry(trace_id).or_insert(next)
+}
+
+/// Slot of the trace at one call site, kept in a static by
+/// `#[perf::instrument]` and the other macros so that a hit indexes the
+/// traces of its thread instead of hashing its `TraceId`. It lists the ids hit
+/// there with their slot, one for most sites and one per instantiation of a
+/// generic fn named by its type arguments, so each is looked up with
+/// `slot_of` once and never again, whichever the site hits next.
+#[derive(Default)]
+pub struct TraceSlot {
+    /// last id registered here, linked to the ones before it
+    head: AtomicPtr<SlotEntry>,
+}
+
+/// An id hit at the site of a `TraceSlot`, leaked so that hits can read it
+/// without a lock
+struct SlotEntry {
+    trace_id: TraceId,
+    slot: usize,
+    next: *const SlotEntry,
+}
+
+impl TraceSlot {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &SlotEntry> {
+        // entries are never freed nor modified once linked
+        let head = self.head.load(Ordering::Acquire).cast_const();
+        std::iter::successors(unsafe { head.as_ref() }, |entry| unsafe {
+            entry.next.as_ref()
+        })
+    }
+
+    /// Slot of `trace_id`, registering it on its first hit here
+    #[inline]
+    pub(crate) fn get(&self, trace_id: &TraceId) -> usize {
+        match self.entries().find(|entry| entry.trace_id.same(trace_id)) {
+            Some(entry) => entry.slot,
+            None => self.register(*trace_id),
+        }
+    }
+
+    #[cold]
+    fn register(&self, trace_id: TraceId) -> usize {
+        // the entry isn't the doing of the trace open around the hit
+        let (slot, entry) = crate::alloc::untracked(|| {
+            let slot = slot_of(trace_id);
+            let entry = Box::leak(Box::new(SlotEntry {
+                trace_id,
+                slot,
+                next: std::ptr::null(),
+            }));
+            (slot, entry)
+        });
+        // another thread may link an entry first, even of the same id, which
+        // then has the same slot
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            entry.next = head;
+            match self
+                .head
+                .compare_exchange_weak(head, entry, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return slot,
+                Err(next) => head = next,
+            }
+        }
+    }
+
+    /// Ids registered at this site so far
+    #[must_use]
+    pub fn registered(&self) -> usize {
+        self.entries().count()
+    }
+}
+
+/// Traces of a thread (of the program without `perf-mt`) by slot
+#[derive(Default)]
+pub struct TraceMap {
+    /// `None` for the slots of traces the thread hasn't hit
+    traces: Vec<Option<(TraceId, Trace)>>,
+}
+
+impl TraceMap {
+    pub fn iter(&self) -> impl Iterator<Item = (&TraceId, &Trace)> {
+        self.traces
+            .iter()
+            .flatten()
+            .map(|(trace_id, trace)| (trace_id, trace))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &TraceId> {
+        self.iter().map(|(trace_id, _)| trace_id)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.traces.iter().flatten().count()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Slot holding `trace_id`, found by scanning them as it is only used
+    /// outside of hits
+    fn find(&self, trace_id: &TraceId) -> Option<usize> {
+        self.traces
+            .iter()
+            .position(|entry| matches!(entry, Some((id, _)) if id == trace_id))
+    }
+
+    #[must_use]
+    pub fn get(&self, trace_id: &TraceId) -> Option<&Trace> {
+        let slot = self.find(trace_id)?;
+        self.traces[slot].as_ref().map(|(_, trace)| trace)
+    }
+
+    pub fn get_mut(&mut self, trace_id: &TraceId) -> Option<&mut Trace> {
+        let slot = self.find(trace_id)?;
+        self.traces[slot].as_mut().map(|(_, trace)| trace)
+    }
+
+    /// Forgets the hits of `trace_id`, its slot stays assigned
+    pub fn remove(&mut self, trace_id: &TraceId) -> Option<Trace> {
+        let slot = self.find(trace_id)?;
+        self.traces[slot].take().map(|(_, trace)| trace)
+    }
+
+    /// The thread has hit the trace at `slot`
+    #[inline]
+    pub(crate) fn has(&self, slot: usize) -> bool {
+        matches!(self.traces.get(slot), Some(Some(_)))
+    }
+
+    /// Trace at `slot`, which the thread has hit
+    #[inline]
+    pub(crate) fn at(&mut self, slot: usize) -> &mut Trace {
+        &mut self.traces[slot].as_mut().expect("hit trace slot").1
+    }
+
+    pub(crate) fn id_at(&self, slot: usize) -> Option<TraceId> {
+        self.traces
+            .get(slot)?
+            .as_ref()
+            .map(|(trace_id, _)| *trace_id)
+    }
+
+    /// Puts the trace of `trace_id` at `slot`, which the thread hasn't hit
+    pub(crate) fn insert(&mut self, slot: usize, trace_id: TraceId, trace: Trace) {
+        if self.traces.len() <= slot {
+            self.traces.resize_with(slot + 1, || None);
+        }
+        self.traces[slot] = Some((trace_id, trace));
+    }
+}
+
+#[cfg(not(feature = "perf-mt"))]
+static CURRENT_TRACE: RacyUnsafeCell<Option<usize>> = RacyUnsafeCell::new(None);
+
+/// Slot of the innermost open trace of the calling thread
+#[cfg(not(feature = "perf-mt"))]
+#[must_use]
+pub fn current_trace() -> *mut Option<usize> {
+    CURRENT_TRACE.get()
+}
+
+#[cfg(not(feature = "perf-mt"))]
+pub unsafe fn trace_map() -> &'static mut TraceMap {
+    static CELL: RacyUnsafeCell<OnceCell<TraceMap>> = RacyUnsafeCell::new(OnceCell::new());
+    (*CELL.get()).get_mut_or_init(TraceMap::default)
+}
+
+/// Every trace, sorted by first hit
+///
+/// # Safety
+///
+/// Reads the trace map, so it is only safe to call in single-threaded program.
+#[cfg(not(feature = "perf-mt"))]
+#[must_use]
+pub unsafe fn collect_traces() -> Vec<(TraceId, Trace)> {
+    let mut traces: Vec<_> = trace_map()
+        .iter()
+        .map(|(trace_id, trace)| (*trace_id, trace.clone()))
+        .collect();
+    traces.sort_unstable_by_key(|(_, trace)| trace.order);
+    traces
+}
+
+/// Traces of one thread. Only that thread touches them until they are merged
+/// by `collect_traces`, after it stopped tracing.
+#[cfg(feature = "perf-mt")]
+struct ThreadTraces {
+    current: UnsafeCell<Option<usize>>,
+    map: UnsafeCell<TraceMap>,
+}
+
+#[cfg(feature = "perf-mt")]
+unsafe impl Sync for ThreadTraces {}
+
+/// The traces of every thread that traced, leaked so that they outlive it
+#[cfg(feature = "perf-mt")]
+static THREAD_TRACES: Mutex<Vec<&'static ThreadTraces>> = Mutex::new(Vec::new());
+
+#[cfg(feature = "perf-mt")]
+thread_local! {
+    static TRACES: &'static ThreadTraces = {
+        let traces: &'static ThreadTraces = Box::leak(Box::new(ThreadTraces {
+            current: UnsafeCell::new(None),
+            map: UnsafeCell::new(TraceMap::default()),
+        }));
+        THREAD_TRACES.lock().unwrap().push(traces);
+        traces
+    };
+}
+
+/// Slot of the innermost open trace of the calling thread
+#[cfg(feature = "perf-mt")]
+#[must_use]
+pub fn current_trace() -> *mut Option<usize> {
+    TRACES.with(|traces| traces.current.get())
+}
+
+/// Traces of the calling thread
+#[cfg(feature = "perf-mt")]
+pub unsafe fn trace_map() -> &'static mut TraceMap {
+    TRACES.with(|traces| &mut *traces.map.get())
+}
+
+/// Every trace merged across threads, sorted by first hit
+///
+/// # Safety
+///
+/// Threads other than the caller must not be tracing anymore.
+///
+/// # Panics
+///
+/// Will panic if a thread panicked while registering its traces
+#[cfg(feature = "perf-mt")]
+#[must_use]
+pub unsafe fn collect_traces() -> Vec<(TraceId, Trace)> {
+    let mut merged: HashMap<TraceId, Trace> = HashMap::new();
+    for traces in THREAD_TRACES.lock().unwrap().iter() {
+        for (trace_id, trace) in (*traces.map.get()).iter() {
+            merged
+                .entry(*trace_id)
+                .and_modify(|merged| merged.merge(trace))
+                .or_insert_with(|| trace.clone());
+        }
+    }
+    let mut traces: Vec<_> = merged.into_iter().collect();
+    traces.sort_unstable_by_key(|(_, trace)| trace.order);
+    traces
 }
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone)]
 pub enum TraceType {
     Fn,
+    /// method name, `enclosing_function_name` holds the `Self` type name
+    Method(&'static str),
     Loop(&'static str),
     Section(&'static str),
+    Iteration(&'static str),
+    Closure(&'static str),
+    /// region timed with `anchor_begin`/`anchor_end`, `enclosing_function_name`
+    /// holds its label
+    Anchor,
 }
 
 #[derive(PartialEq, Eq, Hash, Copy, Clone)]
 pub struct TraceId {
     pub enclosing_function_name: &'static str,
     pub ty: TraceType,
+    /// concrete type arguments of a generic fn, as `type_name::<(T, U,)>()`
+    pub generic_args: Option<&'static str>,
+}
+
+impl TraceId {
+    #[must_use]
+    pub const fn new(enclosing_function_name: &'static str, ty: TraceType) -> Self {
+        Self {
+            enclosing_function_name,
+            ty,
+            generic_args: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_generic_args(mut self, generic_args: &'static str) -> Self {
+        self.generic_args = Some(generic_args);
+        self
+    }
+
+    /// Equal names at the same addresses, as every hit of a call site has;
+    /// ids that only have equal copies of the names are not the same
+    #[inline]
+    fn same(&self, other: &Self) -> bool {
+        let same_str = std::ptr::eq::<str>;
+        let same_ty = match (self.ty, other.ty) {
+            (TraceType::Fn, TraceType::Fn) | (TraceType::Anchor, TraceType::Anchor) => true,
+            (TraceType::Method(a), TraceType::Method(b))
+            | (TraceType::Loop(a), TraceType::Loop(b))
+            | (TraceType::Section(a), TraceType::Section(b))
+            | (TraceType::Iteration(a), TraceType::Iteration(b))
+            | (TraceType::Closure(a), TraceType::Closure(b)) => same_str(a, b),
+            _ => false,
+        };
+        let same_generic_args = match (self.generic_args, other.generic_args) {
+            (None, None) => true,
+            (Some(a), Some(b)) => same_str(a, b),
+            _ => false,
+        };
+        same_str(self.enclosing_function_name, other.enclosing_function_name)
+            && same_ty
+            && same_generic_args
+    }
 }
 
 impl Display for TraceId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // `(u8, alloc::string::String)` -> `<u8, String>`
+        let generic_args = self.generic_args.map_or_else(String::new, |args| {
+            let args = strip_module_paths(args);
+            format!("<{}>", args[1..args.len() - 1].trim_end_matches(','))
+        });
         match self.ty {
-            TraceType::Fn => write!(f, "{}::fn", self.enclosing_function_name),
+            TraceType::Fn => write!(f, "{}{generic_args}::fn", self.enclosing_function_name),
+            TraceType::Method(mname) => {
+                let type_name = strip_module_paths(self.enclosing_function_name);
+                write!(f, "{type_name}::{mname}{generic_args}::fn")
+            }
             TraceType::Loop(lname) => {
                 write!(f, "{}::{}::loop", self.enclosing_function_name, lname)
             }
             TraceType::Section(sname) => {
                 write!(f, "{}::{}::section", self.enclosing_function_name, sname)
             }
+            TraceType::Iteration(lname) => {
+                write!(f, "{}::{}::iteration", self.enclosing_function_name, lname)
+            }
+            TraceType::Closure(cname) => {
+                write!(f, "{}::{}::closure", self.enclosing_function_name, cname)
+            }
+            TraceType::Anchor => write!(f, "{}::anchor", self.enclosing_function_name),
         }
     }
 }
 
+/// `a::b::Foo<alloc::vec::Vec<u8>>` -> `a::b::Foo`, so that anchors of generic
+/// types aggregate across instantiations
+#[must_use]
+pub fn strip_generics(type_name: &'static str) -> &'static str {
+    match type_name.find('<') {
+        Some(i)
+            if type_name[..i]
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == ':') =>
+        {
+            &type_name[..i]
+        }
+        _ => type_name,
+    }
+}
+
+/// `a::b::Foo<alloc::vec::Vec<u8>>` -> `Foo<Vec<u8>>`
+fn strip_module_paths(type_name: &str) -> String {
+    let mut out = String::with_capacity(type_name.len());
+    let mut segment_start = 0;
+    for (i, c) in type_name.char_indices() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            continue;
+        }
+        let path = &type_name[segment_start..i];
+        out.push_str(path.rsplit("::").next().unwrap_or(path));
+        out.push(c);
+        segment_start = i + c.len_utf8();
+    }
+    let path = &type_name[segment_start..];
+    out.push_str(path.rsplit("::").next().unwrap_or(path));
+    out
+}
+
+#[derive(Clone)]
 pub struct Trace {
     /// without children
     pub elapsed_exclusive: i64,
     /// with children
     pub elapsed_inclusive: u64,
     pub hit_count: usize,
+    /// hits of traces that began and ended during the hits of this one, the
+    /// overhead of which outside of their own timing is part of this one's
+    /// exclusive time
+    pub child_hits: usize,
     pub order: usize,
+    /// shortest single hit (with children)
+    pub min_elapsed: u64,
+    /// longest single hit (with children)
+    pub max_elapsed: u64,
+    /// sum of the squared times of the hits (with children), for their
+    /// standard deviation
+    pub elapsed_squares: f64,
+    /// only one in `sample_every` hits is recorded
+    pub sample_every: usize,
+    /// bytes attributed to this trace, for bandwidth reporting
+    pub processed_byte_count: u64,
+    /// page faults during the hits that counted them (with children)
+    pub page_faults: Option<PageFaults>,
+    /// made through `TrackingAllocator` during the hits, without children
+    pub allocations_exclusive: Allocations,
+    /// made through `TrackingAllocator` during the hits, with children
+    pub allocations_inclusive: Allocations,
+    /// trace that was open on the first hit, under which the tree profile
+    /// nests this one
+    pub parent: Option<TraceId>,
+    /// hits of this trace currently open, more than one while it recurses
+    pub depth: usize,
+    /// hits and time (with children) at each recursion depth, the outermost
+    /// first; the depths past the first are part of `elapsed_inclusive`
+    pub depths: Vec<DepthStats>,
+    /// hits were timed with `ScopedTrace::with_precise_timer`
+    pub precise: bool,
+}
+
+/// Hits of a trace at one recursion depth
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DepthStats {
+    pub hit_count: usize,
+    /// with children
+    pub elapsed: u64,
 }
 
 impl Default for Trace {
@@ -52,11 +470,98 @@ impl Default for Trace {
             elapsed_exclusive: 0,
             elapsed_inclusive: 0,
             hit_count: 0,
-            order: unsafe {
-                let id = TRACE_ID.get();
-                *id += 1;
-                *id
-            },
+            child_hits: 0,
+            min_elapsed: u64::MAX,
+            max_elapsed: 0,
+            elapsed_squares: 0.0,
+            sample_every: 1,
+            processed_byte_count: 0,
+            page_faults: None,
+            allocations_exclusive: Allocations::default(),
+            allocations_inclusive: Allocations::default(),
+            parent: None,
+            depth: 0,
+            depths: Vec::new(),
+            precise: false,
+            order: TRACE_ID.fetch_add(1, Ordering::Relaxed) + 1,
+        }
+    }
+}
+
+impl Trace {
+    /// Adds the hits of the same trace on another thread
+    #[cfg(feature = "perf-mt")]
+    fn merge(&mut self, other: &Trace) {
+        self.elapsed_exclusive += other.elapsed_exclusive;
+        self.elapsed_inclusive += other.elapsed_inclusive;
+        self.hit_count += other.hit_count;
+        self.child_hits += other.child_hits;
+        if other.order < self.order {
+            self.parent = other.parent;
+        }
+        self.order = self.order.min(other.order);
+        self.min_elapsed = self.min_elapsed.min(other.min_elapsed);
+        self.max_elapsed = self.max_elapsed.max(other.max_elapsed);
+        self.elapsed_squares += other.elapsed_squares;
+        self.sample_every = self.sample_every.max(other.sample_every);
+        self.processed_byte_count += other.processed_byte_count;
+        self.precise |= other.precise;
+        self.allocations_exclusive = self
+            .allocations_exclusive
+            .wrapping_add(other.allocations_exclusive);
+        self.allocations_inclusive = self
+            .allocations_inclusive
+            .wrapping_add(other.allocations_inclusive);
+        if let Some(faults) = other.page_faults {
+            *self.page_faults.get_or_insert_default() += faults;
+        }
+        if self.depths.len() < other.depths.len() {
+            self.depths
+                .resize(other.depths.len(), DepthStats::default());
+        }
+        for (depth, other) in self.depths.iter_mut().zip(&other.depths) {
+            depth.hit_count += other.hit_count;
+            depth.elapsed += other.elapsed;
         }
     }
 }
+
+/// Page faults of the process, from `getrusage(RUSAGE_SELF)`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PageFaults {
+    /// served without I/O (minor)
+    pub soft: u64,
+    /// required I/O (major)
+    pub hard: u64,
+}
+
+impl PageFaults {
+    /// # Panics
+    ///
+    /// Will panic if `getrusage` fails, which it cannot for `RUSAGE_SELF`
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn now() -> Self {
+        let usage = getrusage(UsageWho::RUSAGE_SELF).expect("getrusage(RUSAGE_SELF)");
+        Self {
+            soft: usage.minor_page_faults() as u64,
+            hard: usage.major_page_faults() as u64,
+        }
+    }
+
+    /// Faults since `earlier`
+    #[must_use]
+    pub fn since(self, earlier: Self) -> Self {
+        Self {
+            soft: self.soft - earlier.soft,
+            hard: self.hard - earlier.hard,
+        }
+    }
+}
+
+impl std::ops::AddAssign for PageFaults {
+    fn add_assign(&mut self, other: Self) {
+        self.soft += other.soft;
+        self.hard += other.hard;
+    }
+}
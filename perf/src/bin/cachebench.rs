@@ -0,0 +1,141 @@
+//! Load latency (random pointer chasing) and streaming bandwidth across working
+//! set sizes, with the cache sizes they imply.
+//!
+//! Usage: cachebench [max working set in MiB (default 256)] [stride in bytes (default 64)]
+
+use std::hint::black_box;
+
+use perf::repetition::RepetitionTester;
+
+const KILOBYTE: usize = 1024;
+const MIN_SIZE: usize = 4 * KILOBYTE;
+const LOADS_PER_RUN: usize = 1 << 20;
+/// Latency growth between consecutive sizes that marks leaving a cache level
+const LEVEL_STEP: f64 = 2.0;
+const LEVELS: usize = 3;
+
+/// Single random cycle through every `stride` of a `size` byte buffer.
+/// Each visited slot holds the index of the next one.
+fn chase_buffer(size: usize, stride: usize) -> Vec<usize> {
+    let slot = stride / std::mem::size_of::<usize>();
+    let count = size / stride;
+    let mut order: Vec<usize> = (0..count).collect();
+    // xorshift Fisher-Yates, the order only needs to defeat the prefetchers
+    let mut state = 0x9E37_79B9_7F4A_7C15u64;
+    for i in (1..count).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        #[allow(clippy::cast_possible_truncation)]
+        order.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+    let mut buffer = vec![0usize; size / std::mem::size_of::<usize>()];
+    for (i, &from) in order.iter().enumerate() {
+        buffer[from * slot] = order[(i + 1) % count] * slot;
+    }
+    buffer
+}
+
+fn chase(buffer: &[usize], loads: usize) -> usize {
+    let mut index = 0;
+    for _ in 0..loads {
+        index = buffer[index];
+    }
+    index
+}
+
+fn stream(buffer: &[usize]) -> usize {
+    let mut acc = [0usize; 4];
+    for chunk in buffer.chunks_exact(4) {
+        for (a, v) in acc.iter_mut().zip(chunk) {
+            *a ^= v;
+        }
+    }
+    acc.iter().fold(0, |x, a| x ^ a)
+}
+
+fn format_size(bytes: usize) -> String {
+    if bytes >= KILOBYTE * KILOBYTE {
+        format!("{}MiB", bytes / (KILOBYTE * KILOBYTE))
+    } else {
+        format!("{}KiB", bytes / KILOBYTE)
+    }
+}
+
+/// Cache sizes reported by the kernel, for comparison
+fn sysfs_cache_sizes() -> Vec<String> {
+    (0..8)
+        .map_while(|index| {
+            let dir = format!("/sys/devices/system/cpu/cpu0/cache/index{index}");
+            let read = |file| std::fs::read_to_string(format!("{dir}/{file}")).ok();
+            let level = read("level")?;
+            let kind = read("type")?;
+            let size = read("size")?;
+            Some(format!("L{} {} {}", level.trim(), kind.trim(), size.trim()))
+        })
+        .collect()
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let max_size = args
+        .next()
+        .map_or(256, |mb| mb.parse::<usize>().expect("max size in MiB"))
+        * KILOBYTE
+        * KILOBYTE;
+    let stride = args
+        .next()
+        .map_or(64, |s| s.parse::<usize>().expect("stride in bytes"));
+    assert!(
+        stride.is_power_of_two() && stride >= std::mem::size_of::<usize>(),
+        "stride must be a power of two of at least a word"
+    );
+    perf::begin_profile();
+
+    println!(
+        "{:>8} {:>14} {:>12}",
+        "size", "latency cycles", "stream gb/s"
+    );
+    let mut latencies = Vec::new();
+    let mut size = MIN_SIZE.max(stride * 2);
+    while size <= max_size {
+        let buffer = chase_buffer(size, stride);
+        let chase_results = RepetitionTester::new(0)
+            .try_for_seconds(1)
+            .run(|| chase(black_box(&buffer), LOADS_PER_RUN));
+        let latency = chase_results.min_time as f64 / LOADS_PER_RUN as f64;
+
+        let passes = (256 * KILOBYTE * KILOBYTE / size).max(1);
+        let stream_results = RepetitionTester::new((passes * size) as u64)
+            .try_for_seconds(1)
+            .run(|| {
+                for _ in 0..passes {
+                    black_box(stream(black_box(&buffer)));
+                }
+            });
+        println!(
+            "{:>8} {latency:>14.2} {:>12.2}",
+            format_size(size),
+            stream_results.best_bandwidth() / (1024.0 * 1024.0 * 1024.0)
+        );
+        latencies.push((size, latency));
+        size *= 2;
+    }
+
+    println!();
+    let mut level = 1;
+    let mut plateau = latencies.first().map_or(0.0, |&(_, latency)| latency);
+    for pair in latencies.windows(2) {
+        let ((size, _), (_, next)) = (pair[0], pair[1]);
+        if level <= LEVELS && next > plateau * LEVEL_STEP {
+            println!("Detected L{level}: {}", format_size(size));
+            level += 1;
+            plateau = next;
+        }
+    }
+    let kernel = sysfs_cache_sizes();
+    if !kernel.is_empty() {
+        println!("Reported by the kernel: {}", kernel.join(", "));
+    }
+}
@@ -0,0 +1,69 @@
+//! Sequential read bandwidth across buffer sizes, from L1 resident to DRAM.
+//!
+//! Usage: membench [max buffer size in MiB (default 1024)] [seconds without a new min (default 2)]
+
+use std::hint::black_box;
+
+use perf::repetition::RepetitionTester;
+
+const KILOBYTE: usize = 1024;
+const MIN_SIZE: usize = 16 * KILOBYTE;
+/// Bytes read per run, small buffers are read repeatedly to reach it
+const BYTES_PER_RUN: usize = 256 * KILOBYTE * KILOBYTE;
+
+/// XORs the buffer in 32 byte lanes, which the compiler turns into wide loads
+fn read_buffer(buffer: &[u64]) -> u64 {
+    let mut acc = [0u64; 4];
+    for chunk in buffer.chunks_exact(4) {
+        for (a, v) in acc.iter_mut().zip(chunk) {
+            *a ^= v;
+        }
+    }
+    acc.iter().fold(0, |x, a| x ^ a)
+}
+
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        b if b >= KILOBYTE * KILOBYTE * KILOBYTE => {
+            format!("{}GiB", b / (KILOBYTE * KILOBYTE * KILOBYTE))
+        }
+        b if b >= KILOBYTE * KILOBYTE => format!("{}MiB", b / (KILOBYTE * KILOBYTE)),
+        b => format!("{}KiB", b / KILOBYTE),
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let max_size = args
+        .next()
+        .map_or(1024, |mb| mb.parse::<usize>().expect("max size in MiB"))
+        * KILOBYTE
+        * KILOBYTE;
+    let seconds = args
+        .next()
+        .map_or(2, |s| s.parse().expect("seconds without a new min"));
+
+    let buffer: Vec<u64> = (0..max_size / 8).map(|i| i as u64).collect();
+    perf::begin_profile();
+
+    println!("{:>8} {:>10} {:>10}", "size", "best gb/s", "avg gb/s");
+    let mut size = MIN_SIZE;
+    while size <= max_size {
+        let slice = &buffer[..size / 8];
+        let passes = (BYTES_PER_RUN / size).max(1);
+        let tester = RepetitionTester::new((passes * size) as u64).try_for_seconds(seconds);
+        let results = tester.run(|| {
+            for _ in 0..passes {
+                black_box(read_buffer(black_box(slice)));
+            }
+        });
+        const GIGABYTE: f64 = (KILOBYTE * KILOBYTE * KILOBYTE) as f64;
+        println!(
+            "{:>8} {:>10.2} {:>10.2}",
+            format_size(size),
+            results.best_bandwidth() / GIGABYTE,
+            results.avg_bandwidth() / GIGABYTE
+        );
+        size *= 2;
+    }
+}
@@ -0,0 +1,46 @@
+//! Compares two profiles exported with `--profile-format json`, anchor by
+//! anchor, and fails if one got slower by more than the threshold.
+//!
+//! Usage: perf-diff <old.json> <new.json> [threshold in percent (default 5)]
+
+use std::{path::Path, process::ExitCode};
+
+use perf::report::{self, ProfileReport};
+
+fn load(path: &str) -> ProfileReport {
+    ProfileReport::load(Path::new(path)).unwrap_or_else(|e| {
+        eprintln!("Unable to read `{path}`: {e}");
+        std::process::exit(2)
+    })
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let (Some(old), Some(new)) = (args.next(), args.next()) else {
+        eprintln!("Usage: perf-diff <old.json> <new.json> [threshold in percent (default 5)]");
+        return ExitCode::from(2);
+    };
+    let threshold: f64 = args
+        .next()
+        .map_or(5.0, |t| t.parse().expect("threshold in percent"));
+
+    let diff = report::diff(&load(&old), &load(&new));
+    print!("{diff}");
+    let regressions: Vec<_> = diff.regressions(threshold).collect();
+    if regressions.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+    println!();
+    for anchor in &regressions {
+        println!(
+            "Regressed: {} by {:.2}%",
+            anchor.name,
+            anchor.change_percent().unwrap_or_default()
+        );
+    }
+    println!(
+        "{} anchor(s) regressed by more than {threshold}%",
+        regressions.len()
+    );
+    ExitCode::FAILURE
+}
@@ -15,12 +15,19 @@ macro_rules! function_name {
     }};
 }
 
-/// Safety: Cannot be used in a multi-threaded context
+/// Safety: Only safe in a single-threaded program, unless perf is built with the `perf-mt`
+/// feature, which keeps the traces per thread.
 #[cfg(feature = "perf")]
 #[macro_export]
 macro_rules! trace_section {
     ($name:expr, $($s:stmt);+ $(;)?) => {
-        let __trace_section = perf::ScopedTrace::new_section(perf::function_name!(), $name);
+        let __trace_section = {
+            static __TRACE_SLOT: perf::trace::TraceSlot = perf::trace::TraceSlot::new();
+            perf::ScopedTrace::new_in(
+                &__TRACE_SLOT,
+                perf::trace::TraceId::new(perf::function_name!(), perf::trace::TraceType::Section($name)),
+            )
+        };
         $($s)*
         drop(__trace_section);
     };
@@ -33,3 +40,22 @@ macro_rules! trace_section {
         $($s)*
     };
 }
+
+/// `perf::anchor_begin(label)` through a slot of the call site
+#[macro_export]
+macro_rules! anchor_begin {
+    ($label:expr) => {{
+        static __TRACE_SLOT: perf::trace::TraceSlot = perf::trace::TraceSlot::new();
+        perf::anchor_begin(&__TRACE_SLOT, $label)
+    }};
+}
+
+/// Section `label` of the enclosing fn that processes `byte_count` bytes, see
+/// `ScopedTrace::new_bandwidth`, through a slot of the call site
+#[macro_export]
+macro_rules! trace_bandwidth {
+    ($label:expr, $byte_count:expr) => {{
+        static __TRACE_SLOT: perf::trace::TraceSlot = perf::trace::TraceSlot::new();
+        perf::ScopedTrace::new_bandwidth(&__TRACE_SLOT, perf::function_name!(), $label, $byte_count)
+    }};
+}
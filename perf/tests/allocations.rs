@@ -0,0 +1,49 @@
+#![cfg(feature = "perf")]
+
+#[global_allocator]
+static ALLOCATOR: perf::TrackingAllocator = perf::TrackingAllocator;
+
+#[perf::instrument]
+fn parse(len: usize) -> Vec<u64> {
+    let values = collect(len);
+    values.iter().map(|value| value * 2).collect()
+}
+
+#[perf::instrument]
+fn collect(len: usize) -> Vec<u64> {
+    let mut values = Vec::with_capacity(len);
+    values.extend(0..len as u64);
+    values
+}
+
+#[perf::instrument]
+fn sum(values: &[u64]) -> u64 {
+    values.iter().sum()
+}
+
+#[test]
+fn attributes_allocations_to_the_innermost_trace() {
+    perf::begin_profile();
+    let values = parse(1000);
+    assert_eq!(sum(&values), 999_000);
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let trace = |name: &str| {
+        traces
+            .iter()
+            .find(|(id, _)| id.to_string() == format!("allocations::{name}::fn"))
+            .unwrap_or_else(|| panic!("missing anchor {name}"))
+            .1
+    };
+    let (parse, collect) = (trace("parse"), trace("collect"));
+    assert_eq!(collect.allocations_exclusive.count, 1);
+    assert_eq!(collect.allocations_exclusive.bytes, 8000);
+    assert_eq!(collect.allocations_inclusive, collect.allocations_exclusive);
+    assert_eq!(parse.allocations_exclusive, collect.allocations_exclusive);
+    assert_eq!(parse.allocations_inclusive.count, 2);
+    assert_eq!(parse.allocations_inclusive.bytes, 16000);
+    assert_eq!(
+        trace("sum").allocations_inclusive,
+        perf::Allocations::default()
+    );
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "perf")]
+
+use perf::AnchorHandle;
+
+fn open_region() -> AnchorHandle {
+    perf::anchor_begin!("streamed parse")
+}
+
+fn close_region(handle: AnchorHandle, items: &[u64]) -> u64 {
+    let sum = items.iter().sum();
+    perf::anchor_end(handle);
+    sum
+}
+
+#[perf::instrument]
+fn parse(items: &[u64]) -> u64 {
+    let handle = open_region();
+    close_region(handle, items)
+}
+
+#[test]
+fn anchors_time_regions_across_functions() {
+    perf::begin_profile();
+    assert_eq!(parse(&[1, 2, 3]), 6);
+    assert_eq!(parse(&[4]), 4);
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let (_, anchor) = traces
+        .iter()
+        .find(|(id, _)| id.to_string() == "streamed parse::anchor")
+        .expect("missing anchor");
+    assert_eq!(anchor.hit_count, 2);
+    assert_eq!(
+        anchor.parent.map(|parent| parent.to_string()).as_deref(),
+        Some("anchor::parse::fn")
+    );
+    let (_, parse) = traces
+        .iter()
+        .find(|(id, _)| id.to_string() == "anchor::parse::fn")
+        .expect("missing anchor");
+    assert!(parse.elapsed_inclusive >= anchor.elapsed_inclusive);
+    perf::end_and_print_profile_tree();
+}
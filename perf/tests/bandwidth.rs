@@ -0,0 +1,19 @@
+#![cfg(feature = "perf")]
+
+#[test]
+fn bandwidth_sections_accumulate_bytes() {
+    perf::begin_profile();
+    for chunk in [1024, 4096] {
+        let _trace = perf::trace_bandwidth!("copy", chunk);
+    }
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let (_, trace) = traces
+        .iter()
+        .find(|(id, _)| {
+            id.to_string() == "bandwidth::bandwidth_sections_accumulate_bytes::copy::section"
+        })
+        .expect("missing anchor");
+    assert_eq!(trace.hit_count, 2);
+    assert_eq!(trace.processed_byte_count, 5120);
+}
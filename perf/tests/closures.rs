@@ -0,0 +1,18 @@
+#![cfg(feature = "perf")]
+
+#[test]
+fn instrument_closure_is_named_after_its_location() {
+    perf::begin_profile();
+    let line = line!() + 1;
+    let square = perf::instrument_closure!(|x: u64| x * x);
+    assert_eq!((1..=3).map(square).sum::<u64>(), 14);
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let name = format!("closures::instrument_closure_is_named_after_its_location::{line}:");
+    let (id, trace) = traces
+        .iter()
+        .find(|(id, _)| id.to_string().starts_with(&name))
+        .expect("missing anchor");
+    assert!(id.to_string().ends_with("::closure"), "{id}");
+    assert_eq!(trace.hit_count, 3);
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "perf")]
+
+#[perf::instrument]
+fn traced() {}
+
+#[perf::instrument(sample = 2)]
+fn sampled() {}
+
+fn hits(name: &str) -> Option<usize> {
+    unsafe { perf::trace::trace_map() }
+        .iter()
+        .find(|(id, _)| id.to_string() == name)
+        .map(|(_, trace)| trace.hit_count)
+}
+
+#[test]
+fn profile_env_var_disables_tracing() {
+    std::env::set_var("PERF_PROFILE", "0");
+    perf::begin_profile();
+    assert!(!perf::tracing_enabled());
+    for _ in 0..4 {
+        traced();
+        sampled();
+    }
+    perf::anchor_end(perf::anchor_begin!("anchor"));
+    assert!(unsafe { perf::trace::trace_map() }.is_empty());
+
+    perf::set_tracing(true);
+    traced();
+    sampled();
+    assert_eq!(hits("disabled::traced::fn"), Some(1));
+    assert_eq!(hits("disabled::sampled::fn"), Some(1));
+    perf::end_and_print_profile();
+}
@@ -0,0 +1,90 @@
+#![cfg(feature = "perf")]
+#![feature(proc_macro_hygiene)]
+
+use perf::{report::ProfileReport, ProfileFormat};
+use serde_json::Value;
+
+#[perf::instrument]
+fn parse(items: &[u64]) -> u64 {
+    items.iter().sum()
+}
+
+#[perf::instrument]
+fn read_input() -> u64 {
+    parse(&[1, 2, 3])
+}
+
+fn export(format: ProfileFormat) -> Value {
+    let path = std::env::temp_dir().join(format!(
+        "perf_export_{}_{format:?}.json",
+        std::process::id()
+    ));
+    perf::end_and_export_profile(&path, format).expect("export profile");
+    let profile = std::fs::read(&path).expect("read profile");
+    std::fs::remove_file(&path).expect("remove profile");
+    serde_json::from_slice(&profile).expect("valid JSON")
+}
+
+#[test]
+fn exports_parse_as_json() {
+    perf::begin_profile();
+    assert_eq!(read_input(), 6);
+
+    let summary = export(ProfileFormat::Json);
+    let traces = summary["traces"].as_array().expect("traces");
+    let parse = traces
+        .iter()
+        .find(|trace| trace["name"] == "export::parse::fn")
+        .expect("missing anchor");
+    assert_eq!(parse["parent"], "export::read_input::fn");
+    assert_eq!(parse["kind"], "fn");
+    assert_eq!(parse["hits"], 1);
+    let report = ProfileReport::from_json(&summary.to_string()).expect("read back");
+    let anchor = report
+        .anchors
+        .iter()
+        .find(|anchor| anchor.name == "export::parse::fn")
+        .expect("missing anchor");
+    assert_eq!(
+        Some(anchor.inclusive_ticks),
+        parse["inclusive_ticks"].as_u64()
+    );
+    assert_eq!(
+        perf::report::diff(&report, &report)
+            .regressions(0.0)
+            .count(),
+        0
+    );
+
+    let chrome = export(ProfileFormat::Chrome);
+    let events = chrome["traceEvents"].as_array().expect("traceEvents");
+    let event = |name: &str| {
+        events
+            .iter()
+            .find(|event| event["name"] == name)
+            .unwrap_or_else(|| panic!("missing event {name}"))
+    };
+    let (outer, inner) = (event("export::read_input::fn"), event("export::parse::fn"));
+    assert_eq!(outer["ph"], "X");
+    // children start with their parent and end within it
+    assert_eq!(inner["ts"], outer["ts"]);
+    assert!(inner["dur"].as_f64() <= outer["dur"].as_f64());
+
+    let mut csv = Vec::new();
+    perf::end_and_write_profile(&mut csv, ProfileFormat::Csv).expect("write profile");
+    let csv = String::from_utf8(csv).expect("utf-8");
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some(
+            "name,kind,hits,exclusive_ticks,inclusive_ticks,exclusive_percent,inclusive_percent,\
+             exclusive_allocations,exclusive_allocated_bytes"
+        )
+    );
+    let row: Vec<_> = lines
+        .map(|line| line.split(',').collect::<Vec<_>>())
+        .find(|row| row[0] == "export::parse::fn")
+        .expect("missing row");
+    assert_eq!(row[1..3], ["fn", "1"]);
+    assert_eq!(row[3], row[4]);
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "perf")]
+
+use perf::{
+    trace::{TraceId, TraceSlot, TraceType},
+    ScopedTrace,
+};
+use std::any::type_name;
+
+#[perf::instrument(generics = "concrete")]
+fn convert<T: From<u8>>(value: u8) -> T {
+    T::from(value)
+}
+
+struct Wrapper<T>(T);
+
+impl<T: Copy> Wrapper<T> {
+    #[perf::instrument(generics = "concrete")]
+    fn get(&self) -> T {
+        self.0
+    }
+}
+
+fn hits(name: &str) -> Option<usize> {
+    unsafe { perf::trace::trace_map() }
+        .iter()
+        .find(|(id, _)| id.to_string() == name)
+        .map(|(_, trace)| trace.hit_count)
+}
+
+#[test]
+fn instantiations_keep_their_traces() {
+    static SLOT: TraceSlot = TraceSlot::new();
+    perf::begin_profile();
+    let ids = [type_name::<(u16,)>(), type_name::<(u32,)>()]
+        .map(|args| TraceId::new("generics::by_hand", TraceType::Fn).with_generic_args(args));
+    for _ in 0..10 {
+        for trace_id in ids {
+            drop(ScopedTrace::new_in(&SLOT, trace_id));
+        }
+    }
+    // alternating instantiations don't register again
+    assert_eq!(SLOT.registered(), 2);
+    assert_eq!(hits("generics::by_hand<u16>::fn"), Some(10));
+    assert_eq!(hits("generics::by_hand<u32>::fn"), Some(10));
+
+    for value in 0..10 {
+        assert_eq!(convert::<u16>(value), u16::from(value));
+        assert_eq!(convert::<u32>(value), u32::from(value));
+        assert_eq!(Wrapper(value).get(), value);
+        assert_eq!(Wrapper(u64::from(value)).get(), u64::from(value));
+    }
+    assert_eq!(hits("generics::convert<u16>::fn"), Some(10));
+    assert_eq!(hits("generics::convert<u32>::fn"), Some(10));
+    assert_eq!(hits("Wrapper<u8>::get::fn"), Some(10));
+    assert_eq!(hits("Wrapper<u64>::get::fn"), Some(10));
+    perf::end_and_print_profile();
+}
@@ -0,0 +1,30 @@
+#![cfg(feature = "perf")]
+
+struct Buffer {
+    values: Vec<f64>,
+}
+
+impl Buffer {
+    #[perf::instrument(name = "sum_values", bytes = "self.values.len() * 8")]
+    fn sum(&self) -> f64 {
+        self.values.iter().sum()
+    }
+}
+
+#[test]
+fn instrument_records_a_byte_count_expression() {
+    perf::begin_profile();
+    let buffer = Buffer {
+        values: vec![1.0; 64],
+    };
+    assert_eq!(buffer.sum(), 64.0);
+    assert_eq!(buffer.sum(), 64.0);
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let (_, trace) = traces
+        .iter()
+        .find(|(id, _)| id.to_string() == "sum_values::fn")
+        .expect("missing anchor");
+    assert_eq!(trace.hit_count, 2);
+    assert_eq!(trace.processed_byte_count, 1024);
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "perf")]
+#![feature(stmt_expr_attributes)]
+#![feature(proc_macro_hygiene)]
+
+use std::collections::HashMap;
+
+// (hit count, elapsed inclusive) by anchor name
+fn traces() -> HashMap<String, (usize, u64)> {
+    unsafe { perf::trace::trace_map() }
+        .iter()
+        .map(|(id, trace)| (id.to_string(), (trace.hit_count, trace.elapsed_inclusive)))
+        .collect()
+}
+
+// The trace map is global, so every scenario lives in this single test
+#[test]
+fn doubly_nested_instrumented_loops() {
+    perf::begin_profile();
+    let mut sum = 0u64;
+    #[perf::instrument_loop("outer")]
+    for i in 0..4u64 {
+        #[perf::instrument_loop("inner")]
+        for j in 0..8u64 {
+            sum += i * j;
+        }
+        // the outer guard must still be alive after the inner loop ends
+        sum += i;
+    }
+    #[perf::instrument_loop]
+    'rows: for i in 0..3u64 {
+        #[perf::instrument_loop(per_iteration)]
+        'cols: for j in 0..5u64 {
+            if i * j > 100 {
+                break 'rows;
+            }
+            if j > 3 {
+                continue 'cols;
+            }
+            sum += j;
+        }
+    }
+    #[perf::instrument_loop("squares", stats = "iterations")]
+    for j in 0..4u64 {
+        sum += j * j;
+    }
+    assert_eq!(sum, 6 * 28 + 6 + 3 * 6 + 14);
+
+    let traces = traces();
+    let anchor = |name: &str| {
+        let key = format!("nested_loops::doubly_nested_instrumented_loops::{name}");
+        *traces
+            .get(&key)
+            .unwrap_or_else(|| panic!("missing anchor {key}"))
+    };
+    let (outer_hits, outer_elapsed) = anchor("outer::loop");
+    let (inner_hits, inner_elapsed) = anchor("outer/inner::loop");
+    assert_eq!(outer_hits, 1);
+    assert_eq!(inner_hits, 4);
+    assert!(outer_elapsed >= inner_elapsed);
+
+    let (rows_hits, rows_elapsed) = anchor("rows::loop");
+    let (cols_hits, cols_elapsed) = anchor("rows/cols::loop");
+    let (iteration_hits, _) = anchor("rows/cols::iteration");
+    assert_eq!(rows_hits, 1);
+    assert_eq!(cols_hits, 3);
+    assert_eq!(iteration_hits, 15);
+    assert!(rows_elapsed >= cols_elapsed);
+
+    let (squares_hits, _) = anchor("squares::loop");
+    let (iteration_hits, iteration_elapsed) = anchor("squares::iteration");
+    assert_eq!(squares_hits, 1);
+    assert_eq!(iteration_hits, 4);
+    let (_, iterations) = unsafe { perf::trace::trace_map() }
+        .iter()
+        .find(|(id, _)| id.to_string().ends_with("::squares::iteration"))
+        .unwrap();
+    // the squares of the hits add up to at least the squared mean per hit
+    #[allow(clippy::cast_precision_loss)]
+    let mean = iteration_elapsed as f64 / 4.0;
+    assert!(iterations.elapsed_squares >= 4.0 * mean * mean * (1.0 - 1e-9));
+    perf::end_and_print_profile();
+}
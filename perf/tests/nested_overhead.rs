@@ -0,0 +1,62 @@
+#![cfg(feature = "perf")]
+
+use perf::{trace::Trace, Overhead};
+
+#[perf::instrument]
+fn step() {}
+
+#[perf::instrument]
+fn walk() {
+    for _ in 0..100 {
+        step();
+    }
+}
+
+fn trace(name: &str) -> Trace {
+    unsafe { perf::trace::trace_map() }
+        .iter()
+        .find(|(id, _)| id.to_string() == name)
+        .map(|(_, trace)| trace.clone())
+        .unwrap_or_else(|| panic!("missing anchor {name}"))
+}
+
+#[test]
+fn overhead_is_taken_off_each_trace_and_its_parent() {
+    perf::begin_profile();
+    let overhead = perf::anchor_overhead_split();
+    assert_eq!(overhead.inside + overhead.outside, perf::anchor_overhead());
+    walk();
+    walk();
+
+    let (walk, step) = (
+        trace("nested_overhead::walk::fn"),
+        trace("nested_overhead::step::fn"),
+    );
+    assert_eq!((walk.hit_count, walk.child_hits), (2, 200));
+    assert_eq!((step.hit_count, step.child_hits), (200, 0));
+
+    // each hit loses what is inside of it, its parent what is outside
+    let overhead = Overhead {
+        inside: 3,
+        outside: 5,
+    };
+    let walk = Trace {
+        elapsed_exclusive: 10_000,
+        ..walk
+    };
+    assert_eq!(overhead.subtract_from(&walk), 10_000 - 2 * 3 - 200 * 5);
+    let step = Trace {
+        elapsed_exclusive: 1_000,
+        ..step
+    };
+    assert_eq!(overhead.subtract_from(&step), 1_000 - 200 * 3);
+    // an estimate larger than the time leaves nothing
+    let step = Trace {
+        elapsed_exclusive: 500,
+        ..step
+    };
+    assert_eq!(overhead.subtract_from(&step), 0);
+
+    perf::subtract_overhead(true);
+    perf::end_and_print_profile();
+}
@@ -0,0 +1,32 @@
+#![cfg(feature = "perf")]
+
+#[perf::instrument]
+fn empty() {}
+
+#[test]
+fn overhead_is_calibrated_without_leaving_a_trace() {
+    perf::begin_profile();
+    let overhead = perf::anchor_overhead();
+    assert!(overhead > 0);
+    assert_eq!(perf::anchor_overhead(), overhead);
+    for _ in 0..1000 {
+        empty();
+    }
+
+    let traces = unsafe { perf::trace::trace_map() };
+    assert!(traces
+        .keys()
+        .all(|id| id.enclosing_function_name != "perf::calibration"));
+    let (_, trace) = traces
+        .iter()
+        .find(|(id, _)| id.to_string() == "overhead::empty::fn")
+        .expect("missing anchor");
+    // an empty hit is at most as long as the whole calibrated pair
+    assert!(
+        trace.min_elapsed <= overhead,
+        "{} > {overhead}",
+        trace.min_elapsed
+    );
+    perf::subtract_overhead(true);
+    perf::end_and_print_profile();
+}
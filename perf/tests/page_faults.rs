@@ -0,0 +1,35 @@
+#![cfg(feature = "perf")]
+
+const PAGE: usize = 4096;
+
+#[perf::instrument(page_faults)]
+fn touch_pages(count: usize) -> usize {
+    let mut pages = vec![0u8; count * PAGE];
+    for page in pages.chunks_mut(PAGE) {
+        page[0] = 1;
+    }
+    pages.iter().step_by(PAGE).map(|&b| usize::from(b)).sum()
+}
+
+#[perf::instrument]
+fn untracked() {}
+
+#[test]
+fn counts_page_faults_of_instrumented_fns() {
+    perf::begin_profile();
+    assert_eq!(touch_pages(256), 256);
+    untracked();
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let faults = |name: &str| {
+        traces
+            .iter()
+            .find(|(id, _)| id.to_string() == format!("page_faults::{name}::fn"))
+            .unwrap_or_else(|| panic!("missing anchor {name}"))
+            .1
+            .page_faults
+    };
+    let touched = faults("touch_pages").expect("page faults are counted");
+    assert!(touched.soft >= 128, "{touched:?}");
+    assert_eq!(faults("untracked"), None);
+}
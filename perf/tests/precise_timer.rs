@@ -0,0 +1,32 @@
+#![cfg(feature = "precise-timer")]
+
+#[perf::instrument(precise_timer)]
+fn fenced(items: &[u64]) -> u64 {
+    items.iter().sum()
+}
+
+#[perf::instrument]
+fn plain() {}
+
+#[test]
+fn precise_hits_are_marked() {
+    perf::begin_profile();
+    assert_eq!(fenced(&[1, 2, 3]), 6);
+    assert_eq!(fenced(&[4]), 4);
+    plain();
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let trace = |name: &str| {
+        traces
+            .iter()
+            .find(|(id, _)| id.to_string() == format!("precise_timer::{name}::fn"))
+            .unwrap_or_else(|| panic!("missing anchor {name}"))
+            .1
+    };
+    let fenced = trace("fenced");
+    assert!(fenced.precise);
+    assert_eq!(fenced.hit_count, 2);
+    assert!(fenced.min_elapsed <= fenced.max_elapsed);
+    assert!(!trace("plain").precise);
+    perf::end_and_print_profile();
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "perf")]
+
+#[perf::instrument]
+fn nest(level: u64) -> u64 {
+    if level == 0 {
+        return std::hint::black_box(1);
+    }
+    nest(level - 1) + level
+}
+
+#[test]
+fn recursive_hits_are_accounted_per_depth() {
+    perf::begin_profile();
+    assert_eq!(nest(3), 7);
+    assert_eq!(nest(3), 7);
+
+    let traces = unsafe { perf::trace::trace_map() };
+    let (_, trace) = traces
+        .iter()
+        .find(|(id, _)| id.to_string() == "recursion::nest::fn")
+        .expect("missing anchor");
+    assert_eq!(trace.hit_count, 8);
+    assert_eq!(trace.depth, 0);
+    assert_eq!(trace.depths.len(), 4);
+    assert!(trace.depths.iter().all(|depth| depth.hit_count == 2));
+    // the outermost hits hold the deeper ones, so they make up the total
+    assert_eq!(trace.depths[0].elapsed, trace.elapsed_inclusive);
+    assert!(trace
+        .depths
+        .windows(2)
+        .all(|pair| pair[0].elapsed >= pair[1].elapsed));
+    assert!(trace.elapsed_exclusive as u64 <= trace.elapsed_inclusive);
+    perf::end_and_print_profile();
+}
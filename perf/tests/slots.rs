@@ -0,0 +1,39 @@
+#![cfg(feature = "perf")]
+
+use perf::{
+    trace::{TraceId, TraceSlot, TraceType},
+    ScopedTrace,
+};
+
+fn hits(name: &str) -> Vec<usize> {
+    unsafe { perf::trace::trace_map() }
+        .iter()
+        .filter(|(id, _)| id.to_string() == name)
+        .map(|(_, trace)| trace.hit_count)
+        .collect()
+}
+
+#[test]
+fn call_sites_reuse_their_slot() {
+    static FIRST: TraceSlot = TraceSlot::new();
+    static SECOND: TraceSlot = TraceSlot::new();
+    perf::begin_profile();
+    let first = TraceId::new("slots::first", TraceType::Anchor);
+    let second = TraceId::new("slots::second", TraceType::Anchor);
+    for _ in 0..100 {
+        drop(ScopedTrace::new_in(&FIRST, first));
+        drop(ScopedTrace::new_in(&SECOND, second));
+    }
+    assert_eq!(FIRST.registered(), 1);
+    assert_eq!(SECOND.registered(), 1);
+    assert_eq!(hits("slots::first::anchor"), [100]);
+    assert_eq!(hits("slots::second::anchor"), [100]);
+
+    // sites with equal ids share a trace
+    for _ in 0..3 {
+        perf::anchor_end(perf::anchor_begin!("shared"));
+        perf::anchor_end(perf::anchor_begin!("shared"));
+    }
+    assert_eq!(hits("shared::anchor"), [6]);
+    perf::end_and_print_profile();
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "perf-mt")]
+
+#[perf::instrument]
+fn work(n: u64) -> u64 {
+    (0..n).sum()
+}
+
+#[perf::instrument(sample = 4)]
+fn sampled() {}
+
+#[perf::instrument(generics = "concrete")]
+fn widen<T: From<u8>>(value: u8) -> T {
+    T::from(value)
+}
+
+#[test]
+fn merges_traces_of_every_thread() {
+    perf::begin_profile();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                scope.spawn(|| {
+                    for n in 0..10 {
+                        work(n);
+                        sampled();
+                        widen::<u16>(n as u8);
+                        widen::<u32>(n as u8);
+                        perf::anchor_end(perf::anchor_begin!("shared"));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    });
+    work(1);
+    perf::anchor_end(perf::anchor_begin!("shared"));
+
+    let traces = unsafe { perf::trace::collect_traces() };
+    let trace = |name: &str| {
+        traces
+            .iter()
+            .find(|(id, _)| id.to_string() == format!("threads::{name}::fn"))
+            .unwrap_or_else(|| panic!("missing anchor {name}"))
+            .1
+            .clone()
+    };
+    let work = trace("work");
+    assert_eq!(work.hit_count, 41);
+    assert!(work.elapsed_inclusive >= work.max_elapsed);
+    assert!(work.min_elapsed <= work.max_elapsed);
+    let sampled = trace("sampled");
+    assert_eq!(sampled.hit_count, 10);
+    assert_eq!(sampled.sample_every, 4);
+    // each instantiation is one trace however the threads alternate them
+    for name in ["widen<u16>", "widen<u32>"] {
+        assert_eq!(trace(name).hit_count, 40);
+    }
+    // sites with equal ids on different threads merge into one trace
+    let shared: Vec<_> = traces
+        .iter()
+        .filter(|(id, _)| id.to_string() == "shared::anchor")
+        .collect();
+    assert_eq!(shared.len(), 1);
+    assert_eq!(shared[0].1.hit_count, 41);
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "perf")]
+#![feature(stmt_expr_attributes)]
+#![feature(proc_macro_hygiene)]
+
+#[perf::instrument]
+fn parse(items: &[u64]) -> u64 {
+    #[perf::instrument_loop("datapoint")]
+    for item in items {
+        std::hint::black_box(item);
+    }
+    items.iter().sum()
+}
+
+#[perf::instrument]
+fn read_input() -> u64 {
+    parse(&[1, 2, 3])
+}
+
+// parent of each anchor by name
+fn parent(name: &str) -> Option<String> {
+    let traces = unsafe { perf::trace::trace_map() };
+    let (_, trace) = traces
+        .iter()
+        .find(|(id, _)| id.to_string() == name)
+        .unwrap_or_else(|| panic!("missing anchor {name}"));
+    trace.parent.map(|parent| parent.to_string())
+}
+
+#[test]
+fn traces_record_the_parent_of_their_first_hit() {
+    perf::begin_profile();
+    assert_eq!(read_input(), 6);
+    // a later hit from elsewhere keeps the first parent
+    assert_eq!(parse(&[4]), 4);
+
+    assert_eq!(parent("tree::read_input::fn"), None);
+    assert_eq!(
+        parent("tree::parse::fn").as_deref(),
+        Some("tree::read_input::fn")
+    );
+    assert_eq!(
+        parent("tree::parse::datapoint::loop").as_deref(),
+        Some("tree::parse::fn")
+    );
+    perf::set_time_unit("us".parse().unwrap());
+    perf::end_and_print_profile_tree();
+}
@@ -0,0 +1,325 @@
+//! The `.f64` answers file written next to a dataset: a header saying how the
+//! answers were computed, one little endian distance per pair, the average,
+//! and a CRC32 of everything before it as a little endian `u32`.
+//!
+//! Legacy files, from before the header, hold only the distances and the
+//! average.
+
+use std::{
+    fmt,
+    io::{self, Write},
+};
+
+use crc32fast::Hasher;
+
+use crate::EarthModel;
+
+/// First bytes of an answers file, followed by the version as a little
+/// endian `u32`, the distribution, seed flag and model bytes, a reserved
+/// byte, then the pair count (`u64`), radius or semi-major axis (`f64`), seed
+/// (`u64`) and flattening (`f64`, zero for a sphere)
+pub const ANSWERS_MAGIC: &[u8; 8] = b"HAVANSWR";
+/// Version 1 files have no model byte nor flattening, they were computed on
+/// a sphere
+pub const ANSWERS_VERSION: u32 = 2;
+const HEADER_LEN: usize = 48;
+const HEADER_LEN_V1: usize = 40;
+
+/// How the generator placed the pairs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Distribution {
+    Uniform,
+    Cluster,
+    Gaussian,
+    Exponential,
+    EdgeCases,
+    Hub,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnswersHeader {
+    /// number of distances, the average excluded
+    pub count: u64,
+    pub model: EarthModel,
+    /// `None` for datasets that were not generated, e.g. converted or sampled
+    pub distribution: Option<Distribution>,
+    pub seed: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnswersError {
+    /// the file does not start with `ANSWERS_MAGIC`, it may be a legacy file
+    MissingHeader,
+    UnsupportedVersion(u32),
+    /// the size does not match the header's count
+    Length {
+        expected: usize,
+        found: usize,
+    },
+    Checksum {
+        stored: u32,
+        computed: u32,
+    },
+    /// unknown distribution, flag or model byte
+    InvalidHeader,
+}
+
+impl fmt::Display for AnswersError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingHeader => write!(f, "no answers header, is it a legacy file?"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {version}"),
+            Self::Length { expected, found } => {
+                write!(f, "{found} bytes, the header says {expected}")
+            }
+            Self::Checksum { stored, computed } => {
+                write!(f, "checksum {computed:08x} does not match {stored:08x}")
+            }
+            Self::InvalidHeader => write!(f, "invalid header"),
+        }
+    }
+}
+
+impl std::error::Error for AnswersError {}
+
+/// `N` bytes of `bytes` from `at`, which the caller has checked are there
+fn field<const N: usize>(bytes: &[u8], at: usize) -> [u8; N] {
+    bytes[at..at + N].try_into().expect("field is in bounds")
+}
+
+fn distribution_code(distribution: Option<Distribution>) -> u8 {
+    match distribution {
+        None => 0,
+        Some(Distribution::Uniform) => 1,
+        Some(Distribution::Cluster) => 2,
+        Some(Distribution::Gaussian) => 3,
+        Some(Distribution::Exponential) => 4,
+        Some(Distribution::EdgeCases) => 5,
+        Some(Distribution::Hub) => 6,
+    }
+}
+
+impl AnswersHeader {
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..8].copy_from_slice(ANSWERS_MAGIC);
+        bytes[8..12].copy_from_slice(&ANSWERS_VERSION.to_le_bytes());
+        bytes[12] = distribution_code(self.distribution);
+        bytes[13] = u8::from(self.seed.is_some());
+        let (model, size, flattening) = match self.model {
+            EarthModel::Sphere { radius } => (0, radius, 0.0),
+            EarthModel::Ellipsoid { a, f } => (1, a, f),
+        };
+        bytes[14] = model;
+        bytes[16..24].copy_from_slice(&self.count.to_le_bytes());
+        bytes[24..32].copy_from_slice(&size.to_le_bytes());
+        bytes[32..40].copy_from_slice(&self.seed.unwrap_or_default().to_le_bytes());
+        bytes[40..48].copy_from_slice(&flattening.to_le_bytes());
+        bytes
+    }
+
+    /// `bytes` holds a header of `version`, which is 1 or 2
+    fn decode(bytes: &[u8], version: u32) -> Result<Self, AnswersError> {
+        let distribution = match bytes[12] {
+            0 => None,
+            1 => Some(Distribution::Uniform),
+            2 => Some(Distribution::Cluster),
+            3 => Some(Distribution::Gaussian),
+            4 => Some(Distribution::Exponential),
+            5 => Some(Distribution::EdgeCases),
+            6 => Some(Distribution::Hub),
+            _ => return Err(AnswersError::InvalidHeader),
+        };
+        let seed = match bytes[13] {
+            0 => None,
+            1 => Some(u64::from_le_bytes(field(bytes, 32))),
+            _ => return Err(AnswersError::InvalidHeader),
+        };
+        let size = f64::from_le_bytes(field(bytes, 24));
+        let model = match (version, bytes[14]) {
+            (1, _) | (_, 0) => EarthModel::Sphere { radius: size },
+            (_, 1) => EarthModel::Ellipsoid {
+                a: size,
+                f: f64::from_le_bytes(field(bytes, 40)),
+            },
+            _ => return Err(AnswersError::InvalidHeader),
+        };
+        Ok(Self {
+            count: u64::from_le_bytes(field(bytes, 16)),
+            model,
+            distribution,
+            seed,
+        })
+    }
+}
+
+/// Writes an answers file: the header, then `header.count` distances through
+/// `write_distance`, then the average and checksum through `finish`
+pub struct AnswersWriter<W: Write> {
+    writer: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> AnswersWriter<W> {
+    /// # Errors
+    ///
+    /// Returns the error of writing the header.
+    pub fn new(writer: W, header: &AnswersHeader) -> io::Result<Self> {
+        let mut answers = Self {
+            writer,
+            hasher: Hasher::new(),
+        };
+        answers.write(&header.encode())?;
+        Ok(answers)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.hasher.update(bytes);
+        self.writer.write_all(bytes)
+    }
+
+    /// # Errors
+    ///
+    /// Returns the error of the underlying writer.
+    pub fn write_distance(&mut self, distance: f64) -> io::Result<()> {
+        self.write(&distance.to_le_bytes())
+    }
+
+    /// Writes the average and the checksum and returns the writer, unflushed
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the underlying writer.
+    pub fn finish(mut self, average: f64) -> io::Result<W> {
+        self.write(&average.to_le_bytes())?;
+        let checksum = self.hasher.finalize();
+        self.writer.write_all(&checksum.to_le_bytes())?;
+        Ok(self.writer)
+    }
+}
+
+/// The header and the distances followed by the average, as a legacy file
+/// holds them
+///
+/// # Errors
+///
+/// Returns an error if `bytes` is not a complete answers file of this or the
+/// previous version with a matching checksum.
+pub fn decode(bytes: &[u8]) -> Result<(AnswersHeader, Vec<f64>), AnswersError> {
+    if !bytes.starts_with(ANSWERS_MAGIC) {
+        return Err(AnswersError::MissingHeader);
+    }
+    let header_len = match bytes.get(8..12) {
+        Some(version) => match u32::from_le_bytes(field(version, 0)) {
+            1 => HEADER_LEN_V1,
+            ANSWERS_VERSION => HEADER_LEN,
+            version => return Err(AnswersError::UnsupportedVersion(version)),
+        },
+        None => HEADER_LEN,
+    };
+    if bytes.len() < header_len {
+        return Err(AnswersError::Length {
+            expected: header_len,
+            found: bytes.len(),
+        });
+    }
+    let (header, body) = bytes.split_at(header_len);
+    let version = u32::from_le_bytes(field(header, 8));
+    let count = u64::from_le_bytes(field(header, 16));
+    let expected = usize::try_from(count)
+        .ok()
+        .and_then(|count| count.checked_add(1)?.checked_mul(8))
+        .and_then(|values| values.checked_add(header_len + 4))
+        .unwrap_or(usize::MAX);
+    if bytes.len() != expected {
+        return Err(AnswersError::Length {
+            expected,
+            found: bytes.len(),
+        });
+    }
+    let (body, stored) = body.split_at(body.len() - 4);
+    let stored = u32::from_le_bytes(field(stored, 0));
+    let computed = crc32fast::hash(&bytes[..bytes.len() - 4]);
+    if stored != computed {
+        return Err(AnswersError::Checksum { stored, computed });
+    }
+    Ok((AnswersHeader::decode(header, version)?, decode_legacy(body)))
+}
+
+/// The distances followed by the average of a legacy file
+#[must_use]
+pub fn decode_legacy(bytes: &[u8]) -> Vec<f64> {
+    let (chunks, _) = bytes.as_chunks::<8>();
+    chunks.iter().copied().map(f64::from_le_bytes).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(header: &AnswersHeader, values: &[f64]) -> Vec<u8> {
+        let (average, distances) = values.split_last().unwrap();
+        let mut writer = AnswersWriter::new(Vec::new(), header).unwrap();
+        for &distance in distances {
+            writer.write_distance(distance).unwrap();
+        }
+        writer.finish(*average).unwrap()
+    }
+
+    #[test]
+    fn answers_round_trip_and_detect_damage() {
+        let header = AnswersHeader {
+            count: 2,
+            model: EarthModel::SPHERE_DEFAULT,
+            distribution: Some(Distribution::Cluster),
+            seed: Some(7),
+        };
+        let values = [1.5, 2.5, 2.0];
+        let bytes = encode(&header, &values);
+        assert_eq!(bytes.len(), HEADER_LEN + 3 * 8 + 4);
+        assert_eq!(decode(&bytes), Ok((header, values.to_vec())));
+
+        let unknown = AnswersHeader {
+            distribution: None,
+            seed: None,
+            ..header
+        };
+        assert_eq!(
+            decode(&encode(&unknown, &values)),
+            Ok((unknown, values.to_vec()))
+        );
+        let wgs84 = AnswersHeader {
+            model: EarthModel::WGS84,
+            ..header
+        };
+        assert_eq!(
+            decode(&encode(&wgs84, &values)),
+            Ok((wgs84, values.to_vec()))
+        );
+
+        // version 1 headers end before the flattening
+        let mut v1 = bytes[..HEADER_LEN_V1].to_vec();
+        v1[8..12].copy_from_slice(&1u32.to_le_bytes());
+        v1.extend_from_slice(&bytes[HEADER_LEN..bytes.len() - 4]);
+        v1.extend_from_slice(&crc32fast::hash(&v1).to_le_bytes());
+        assert_eq!(decode(&v1), Ok((header, values.to_vec())));
+
+        let mut flipped = bytes.clone();
+        flipped[HEADER_LEN + 3] ^= 1;
+        assert!(matches!(
+            decode(&flipped),
+            Err(AnswersError::Checksum { .. })
+        ));
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 8]),
+            Err(AnswersError::Length {
+                expected: bytes.len(),
+                found: bytes.len() - 8
+            })
+        );
+
+        let legacy: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        assert_eq!(decode(&legacy), Err(AnswersError::MissingHeader));
+        assert_eq!(decode_legacy(&legacy), values);
+    }
+}
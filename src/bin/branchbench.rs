@@ -0,0 +1,203 @@
+//! Branch behavior of the compute loop: times loop layouts over uniform and
+//! cluster data while counting branch instructions and misses.
+
+use clap::Parser;
+use haversine::{
+    generator, numa, reference_haversine, HaversineData, HaversineDataPoint, EARTH_RADIUS,
+};
+use perf::repetition::RepetitionTester;
+
+/// Branch Behavior Benchmark
+#[derive(Parser)]
+struct Arguments {
+    #[arg(long, default_value_t = 1_000_000)]
+    pairs: usize,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Stop a layout once no new minimum was found for this many seconds
+    #[arg(long, default_value_t = 2)]
+    seconds: u64,
+    /// Bind threads and memory to this NUMA node and report its allocations
+    #[arg(long)]
+    numa_node: Option<u32>,
+}
+
+#[cfg(target_os = "linux")]
+mod counter {
+    use std::{
+        fs::File,
+        io::Read,
+        os::fd::{AsRawFd, FromRawFd},
+    };
+
+    use perf_event_open_sys::{
+        bindings::{
+            perf_event_attr, PERF_COUNT_HW_BRANCH_INSTRUCTIONS, PERF_COUNT_HW_BRANCH_MISSES,
+            PERF_TYPE_HARDWARE,
+        },
+        ioctls, perf_event_open,
+    };
+
+    /// User space hardware counter of this process
+    pub struct Counter(File);
+
+    impl Counter {
+        fn open(config: u32) -> std::io::Result<Self> {
+            let mut attr = perf_event_attr {
+                type_: PERF_TYPE_HARDWARE,
+                size: u32::try_from(std::mem::size_of::<perf_event_attr>()).expect("attr size"),
+                config: config.into(),
+                ..perf_event_attr::default()
+            };
+            attr.set_disabled(1);
+            attr.set_exclude_kernel(1);
+            attr.set_exclude_hv(1);
+            let fd = unsafe { perf_event_open(&raw mut attr, 0, -1, -1, 0) };
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(Self(unsafe { File::from_raw_fd(fd) }))
+        }
+
+        pub fn count<T>(&mut self, f: impl FnOnce() -> T) -> (T, u64) {
+            let fd = self.0.as_raw_fd();
+            unsafe {
+                ioctls::RESET(fd, 0);
+                ioctls::ENABLE(fd, 0);
+            }
+            let result = std::hint::black_box(f());
+            unsafe { ioctls::DISABLE(fd, 0) };
+            let mut value = [0u8; 8];
+            self.0.read_exact(&mut value).expect("read counter");
+            (result, u64::from_ne_bytes(value))
+        }
+    }
+
+    /// Branch instruction and branch miss counters
+    pub fn branch_counters() -> std::io::Result<(Counter, Counter)> {
+        Ok((
+            Counter::open(PERF_COUNT_HW_BRANCH_INSTRUCTIONS)?,
+            Counter::open(PERF_COUNT_HW_BRANCH_MISSES)?,
+        ))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod counter {
+    pub struct Counter;
+
+    impl Counter {
+        pub fn count<T>(&mut self, _: impl FnOnce() -> T) -> (T, u64) {
+            unreachable!()
+        }
+    }
+
+    pub fn branch_counters() -> std::io::Result<(Counter, Counter)> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// Validation checked per pair, the pipeline's original loop
+fn branch_per_pair(pairs: &[HaversineDataPoint], answers: Option<&[f64]>) -> Option<f64> {
+    let mut sum = 0f64;
+    for (index, point) in pairs.iter().enumerate() {
+        let dist = reference_haversine(point, EARTH_RADIUS);
+        sum += dist;
+        if let Some(answers) = answers {
+            if (dist - answers[index]).abs() > 1e-10 {
+                return None;
+            }
+        }
+    }
+    Some(sum)
+}
+
+#[cold]
+#[inline(never)]
+fn mismatch() -> Option<f64> {
+    None
+}
+
+/// Validation decided once outside the loop, mismatches on a cold path, as
+/// the pipeline does now
+fn hoisted(pairs: &[HaversineDataPoint], answers: Option<&[f64]>) -> Option<f64> {
+    let mut sum = 0f64;
+    match answers {
+        None => {
+            for point in pairs {
+                sum += reference_haversine(point, EARTH_RADIUS);
+            }
+        }
+        Some(answers) => {
+            for (point, expected) in pairs.iter().zip(answers) {
+                let dist = reference_haversine(point, EARTH_RADIUS);
+                sum += dist;
+                if (dist - expected).abs() > 1e-10 {
+                    return mismatch();
+                }
+            }
+        }
+    }
+    Some(sum)
+}
+
+type Layout = fn(&[HaversineDataPoint], Option<&[f64]>) -> Option<f64>;
+
+fn main() {
+    let args = Arguments::parse();
+    let numa_start = args.numa_node.map(|node| {
+        numa::bind(node).expect("Unable to bind to NUMA node");
+        (
+            node,
+            numa::NodeStat::read(node).expect("Unable to read NUMA node stats"),
+        )
+    });
+    let datasets: [(&str, HaversineData); 2] = [
+        ("uniform", generator::uniform(args.pairs, args.seed)),
+        ("cluster", generator::cluster(args.pairs, args.seed)),
+    ];
+    let layouts: [(&str, Layout); 2] = [("per pair", branch_per_pair), ("hoisted", hoisted)];
+    let mut counters = counter::branch_counters()
+        .map_err(|e| eprintln!("Branch counters unavailable: {e}"))
+        .ok();
+    perf::begin_profile();
+
+    #[allow(clippy::cast_precision_loss)]
+    let pair_count = args.pairs as f64;
+    println!(
+        "{:>8} {:>9} {:>9} {:>12} {:>15} {:>12}",
+        "data", "layout", "validate", "cycles/pair", "branches/pair", "miss rate"
+    );
+    for (name, data) in &datasets {
+        let answers: Vec<f64> = data
+            .pairs
+            .iter()
+            .map(|point| reference_haversine(point, EARTH_RADIUS))
+            .collect();
+        for (layout_name, layout) in layouts {
+            for validate in [false, true] {
+                let answers = validate.then_some(answers.as_slice());
+                let tester = RepetitionTester::new(0).try_for_seconds(args.seconds);
+                let results = tester.run(|| layout(&data.pairs, answers));
+                #[allow(clippy::cast_precision_loss)]
+                let cycles = results.min_time as f64 / pair_count;
+                let branches = counters.as_mut().map_or(String::from("-"), |(b, m)| {
+                    let ((_, branches), misses) =
+                        m.count(|| b.count(|| layout(&data.pairs, answers)));
+                    #[allow(clippy::cast_precision_loss)]
+                    let (branches, misses) = (branches as f64, misses as f64);
+                    format!(
+                        "{:>15.2} {:>11.3}%",
+                        branches / pair_count,
+                        100.0 * misses / branches
+                    )
+                });
+                println!("{name:>8} {layout_name:>9} {validate:>9} {cycles:>12.2} {branches}");
+            }
+        }
+    }
+    if let Some((node, start)) = numa_start {
+        let end = numa::NodeStat::read(node).expect("Unable to read NUMA node stats");
+        println!("NUMA node {node}: {}", end.since(&start));
+    }
+}
@@ -0,0 +1,55 @@
+use std::{path::PathBuf, time::Instant};
+
+use clap::Args;
+use haversine::{
+    pipeline::{Pipeline, PipelineError, PipelineOptions},
+    PairBuffer,
+};
+
+use crate::{exit_with_error, exit_with_io_error, SharedArgs};
+
+#[derive(Args)]
+pub struct BenchArgs {
+    #[arg(name = "haversine_input.json")]
+    data_file: PathBuf,
+    /// Number of timed runs
+    #[arg(long, default_value_t = 10)]
+    repetitions: u32,
+}
+
+pub fn run(args: &BenchArgs, shared: SharedArgs) {
+    let pipeline = Pipeline::new(PipelineOptions {
+        model: shared.model(),
+        ..PipelineOptions::default()
+    });
+
+    // the runs after the first parse over its pairs
+    let mut buffer = PairBuffer::default();
+    let mut input_size = 0;
+    let mut timings = Vec::with_capacity(args.repetitions as usize);
+    for _ in 0..args.repetitions {
+        let start = Instant::now();
+        let report = match pipeline.run_with(&args.data_file, &mut buffer) {
+            Ok(report) => report,
+            Err(e @ PipelineError::Io { .. }) => exit_with_io_error(e),
+            Err(e) => exit_with_error(e),
+        };
+        timings.push(start.elapsed().as_secs_f64());
+        input_size = report.input_size;
+    }
+    if timings.is_empty() {
+        return;
+    }
+
+    let min = timings.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = timings.iter().copied().fold(0f64, f64::max);
+    #[allow(clippy::cast_precision_loss)]
+    let avg = timings.iter().sum::<f64>() / timings.len() as f64;
+    #[allow(clippy::cast_precision_loss)]
+    let megabytes = input_size as f64 / (1024.0 * 1024.0);
+    println!("Input size: {input_size}");
+    println!("Repetitions: {}", timings.len());
+    println!("Min: {:.3} ms ({:.2} mb/s)", min * 1000.0, megabytes / min);
+    println!("Avg: {:.3} ms", avg * 1000.0);
+    println!("Max: {:.3} ms", max * 1000.0);
+}
@@ -0,0 +1,283 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, IsTerminal, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Args;
+use haversine::{
+    format::Format,
+    pipeline::{
+        ComputeBackend, Mismatch, ParserBackend, Pipeline, PipelineError, PipelineOptions,
+        ReadStrategy, RunReport,
+    },
+    progress::{Progress, ProgressHook},
+};
+
+use perf::{
+    prometheus::{Exporter, Metrics},
+    ProfileFormat, TimeUnit,
+};
+use tracing::info;
+
+use crate::{exit_with_error, exit_with_io_error, report::Report, SharedArgs};
+
+#[derive(Args)]
+pub struct ComputeArgs {
+    #[arg(name = "haversine_input.json")]
+    data_file: PathBuf,
+    #[arg(name = "answers.f64")]
+    answer_file: Option<PathBuf>,
+    #[command(flatten)]
+    process: ProcessArgs,
+    /// Write the profile as Prometheus metrics to this file
+    #[arg(long)]
+    metrics: Option<PathBuf>,
+    /// Serve the profile as Prometheus metrics on this localhost port
+    /// or address (e.g. `0.0.0.0:9100`), until interrupted
+    #[arg(long, alias = "metrics-port", value_parser = perf::prometheus::listen_addr)]
+    metrics_addr: Option<std::net::SocketAddr>,
+    /// Print the profile as a tree, each anchor under its caller with its share of it
+    #[arg(long)]
+    profile_tree: bool,
+    /// Subtract each anchor's estimated profiler overhead (hits times the cost of an
+    /// empty anchor, measured at startup) from its self time in the printed profile
+    #[arg(long)]
+    subtract_overhead: bool,
+    /// Unit of the printed anchor times: cycles (timer ticks, with their milliseconds),
+    /// ms or us
+    #[arg(long, default_value = "cycles")]
+    time_unit: TimeUnit,
+    /// Also write the profile to this file
+    #[arg(long)]
+    profile_out: Option<PathBuf>,
+    /// Format of `--profile-out`: json, csv, or chrome for Perfetto and `chrome://tracing`
+    #[arg(long, default_value = "json")]
+    profile_format: ProfileFormat,
+    /// Write the results as one CSV row, `csv` on stdout or `csv=<path>` appended to a
+    /// file, instead of printing them and the profile
+    #[arg(long, conflicts_with = "profile_tree")]
+    report: Option<Report>,
+}
+
+/// How the processor reads the input and computes the distances
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Args)]
+pub struct ProcessArgs {
+    /// Input format: json, binary, csv, ndjson, or parquet with the `parquet` feature
+    #[arg(long, default_value = "json")]
+    format: Format,
+    /// mmap, read, direct (`O_DIRECT`), or io-uring when built with the `io-uring` feature
+    #[arg(long, default_value = "mmap")]
+    read: ReadStrategy,
+    /// Reads kept in flight by the io-uring strategy
+    #[arg(long, default_value_t = 8)]
+    queue_depth: u32,
+    /// JSON parser: nom, serde, simd with the `simd-json` feature or simd-scan with `simd-parse`
+    #[arg(long, default_value = "nom")]
+    parser: ParserBackend,
+    /// scalar, simd, or gpu when built with the `gpu` feature
+    #[arg(long, default_value = "scalar")]
+    backend: ComputeBackend,
+    /// Threads the scalar backend splits the pairs across
+    #[arg(long, default_value_t = NonZeroUsize::MIN)]
+    threads: NonZeroUsize,
+    /// Periodically save progress to this file, removed once the run completes
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    /// Seconds between checkpoint saves
+    #[arg(long, default_value_t = 60)]
+    checkpoint_every: u64,
+    /// Continue from the checkpoint file if an earlier run left one
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+    /// Compute each pair as it is parsed instead of holding all of them in memory
+    #[arg(long, visible_alias = "fused", conflicts_with_all = ["parser", "backend", "checkpoint"])]
+    stream: bool,
+    /// Reject inputs with a longitude outside -180..=180 or a latitude outside -90..=90
+    #[arg(long)]
+    strict: bool,
+    /// Read the answers file as written before it had a header and checksum
+    #[arg(long)]
+    legacy_answers: bool,
+    /// Max absolute difference between a computed distance and its answer
+    #[arg(long, default_value_t = PipelineOptions::default().tolerance)]
+    tolerance: f64,
+    /// Write every pair off by more than the tolerance to this CSV file and carry on,
+    /// instead of stopping at the first; the run still fails if there are any
+    #[arg(long, requires = "answers.f64")]
+    validation_report: Option<PathBuf>,
+    /// Print the share of the input parsed and of the pairs computed, with the time
+    /// left, to stderr
+    #[arg(long)]
+    progress: bool,
+}
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    #[arg(name = "haversine_input.json")]
+    data_file: PathBuf,
+    #[arg(name = "answers.f64")]
+    answer_file: PathBuf,
+    #[command(flatten)]
+    process: ProcessArgs,
+}
+
+fn run_pipeline(
+    data_file: &PathBuf,
+    answers: Option<PathBuf>,
+    process: &ProcessArgs,
+    shared: SharedArgs,
+) -> RunReport {
+    let pipeline = Pipeline::new(PipelineOptions {
+        format: process.format,
+        read: process.read,
+        queue_depth: process.queue_depth,
+        parser: process.parser,
+        compute: process.backend,
+        threads: process.threads,
+        checkpoint: process.checkpoint.clone(),
+        checkpoint_interval: Duration::from_secs(process.checkpoint_every),
+        resume: process.resume,
+        stream: process.stream,
+        strict: process.strict,
+        answers,
+        legacy_answers: process.legacy_answers,
+        tolerance: process.tolerance,
+        collect_mismatches: process.validation_report.is_some(),
+        model: shared.model(),
+        progress: process.progress.then_some(print_progress as ProgressHook),
+    });
+    let report = match pipeline.run(data_file) {
+        Ok(report) => {
+            info!(
+                input_size = report.input_size,
+                pair_count = report.pair_count,
+                "Processed `{}`",
+                data_file.display()
+            );
+            report
+        }
+        Err(e @ PipelineError::Io { .. }) => exit_with_io_error(e),
+        Err(e) => exit_with_error(e),
+    };
+    if let Some(path) = &process.validation_report {
+        write_validation_report(path, &report.mismatches).unwrap_or_else(|e| {
+            exit_with_io_error(format!("Unable to write `{}`: {e}", path.display()))
+        });
+    }
+    report
+}
+
+/// `--progress`: one line per stage on stderr, redrawn in a terminal
+fn print_progress(progress: &Progress) {
+    let eta = match progress.eta() {
+        Some(eta) if !progress.is_done() => format!(", {:.1}s left", eta.as_secs_f64()),
+        _ => format!(" in {:.1}s", progress.elapsed.as_secs_f64()),
+    };
+    // rounded down, 100% only once done
+    let percent = (progress.fraction() * 1000.0).floor() / 10.0;
+    let line = format!("{}: {percent:5.1}%{eta}", progress.stage.name());
+    let mut stderr = io::stderr().lock();
+    let _ = if !stderr.is_terminal() {
+        writeln!(stderr, "{line}")
+    } else if progress.is_done() {
+        writeln!(stderr, "\r\x1b[2K{line}")
+    } else {
+        write!(stderr, "\r\x1b[2K{line}").and_then(|()| stderr.flush())
+    };
+}
+
+/// One `index,computed,expected,diff` row per mismatch, under that header
+fn write_validation_report(path: &Path, mismatches: &[Mismatch]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "index,computed,expected,diff")?;
+    for mismatch in mismatches {
+        writeln!(
+            writer,
+            "{},{:?},{:?},{:?}",
+            mismatch.index,
+            mismatch.computed,
+            mismatch.expected,
+            mismatch.difference()
+        )?;
+    }
+    writer.flush()
+}
+
+/// Fails the run after its results if `--validation-report` collected mismatches
+fn exit_on_mismatches(report: &RunReport, process: &ProcessArgs) {
+    if let (Some(path), false) = (&process.validation_report, report.mismatches.is_empty()) {
+        exit_with_error(format!(
+            "Failed validation for {} of {} pairs, see `{}`",
+            report.mismatches.len(),
+            report.pair_count,
+            path.display()
+        ));
+    }
+}
+
+pub fn run(args: ComputeArgs, shared: SharedArgs) {
+    let exporter = Exporter::new(args.metrics, args.metrics_addr)
+        .unwrap_or_else(|e| exit_with_io_error(format!("Unable to export metrics: {e}")));
+    perf::subtract_overhead(args.subtract_overhead);
+    perf::set_time_unit(args.time_unit);
+    perf::begin_profile();
+    let report = run_pipeline(&args.data_file, args.answer_file, &args.process, shared);
+
+    if let Some(csv) = &args.report {
+        crate::report::write(csv, &args.data_file, &report)
+            .unwrap_or_else(|e| exit_with_io_error(format!("Unable to write the report: {e}")));
+    } else {
+        print_results(&report, args.profile_tree);
+    }
+    if let Some(path) = &args.profile_out {
+        perf::end_and_export_profile(path, args.profile_format).unwrap_or_else(|e| {
+            exit_with_io_error(format!("Unable to write `{}`: {e}", path.display()))
+        });
+    }
+
+    let mut metrics = Metrics::new();
+    metrics.add_profile();
+    exporter
+        .update(&metrics)
+        .and_then(|()| exporter.finish())
+        .unwrap_or_else(|e| exit_with_io_error(format!("Unable to export metrics: {e}")));
+    exit_on_mismatches(&report, &args.process);
+}
+
+fn print_results(report: &RunReport, profile_tree: bool) {
+    println!("Haversine avg: {}", report.average);
+
+    if let Some(validation) = &report.validation {
+        println!();
+        println!("Validation:");
+        println!("Reference avg: {}", validation.reference_average);
+        println!("Difference: {}", validation.difference);
+    }
+    println!();
+    if profile_tree {
+        perf::end_and_print_profile_tree();
+    } else {
+        perf::end_and_print_profile();
+    }
+}
+
+pub fn validate(args: ValidateArgs, shared: SharedArgs) {
+    let report = run_pipeline(
+        &args.data_file,
+        Some(args.answer_file),
+        &args.process,
+        shared,
+    );
+    let validation = report
+        .validation
+        .expect("pipeline validates when given answers");
+    println!("Haversine avg: {}", report.average);
+    println!("Reference avg: {}", validation.reference_average);
+    println!("Difference: {}", validation.difference);
+    exit_on_mismatches(&report, &args.process);
+    println!("Validation passed");
+}
@@ -0,0 +1,112 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use haversine::{format::Format, HaversineData};
+
+use tracing::info;
+
+use crate::{
+    exit_with_error, exit_with_io_error,
+    generate::{write_answers, write_json},
+    SharedArgs,
+};
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    input: PathBuf,
+    output: PathBuf,
+    /// Input format (json, binary, csv, ndjson, parquet), detected from the contents by default
+    #[arg(long)]
+    from: Option<Format>,
+    /// Output format, taken from the output file extension by default
+    #[arg(long)]
+    to: Option<Format>,
+    /// Write JSON without whitespace instead of the generator's pretty layout
+    #[arg(long)]
+    compact: bool,
+    /// Also write a freshly computed answers file for the converted pairs
+    #[arg(long)]
+    answers: Option<PathBuf>,
+}
+
+pub fn create(path: &Path) -> File {
+    File::create(path).unwrap_or_else(|e| {
+        exit_with_io_error(format!("Unable to create `{}`: {e}", path.display()))
+    })
+}
+
+/// Reads a dataset in `from`, or the format detected from its contents or extension
+pub fn read_dataset(path: &Path, from: Option<Format>) -> (HaversineData, Format) {
+    let bytes = std::fs::read(path).unwrap_or_else(|e| {
+        exit_with_io_error(format!("Unable to open `{}`: {e}", path.display()))
+    });
+    let Some(from) = from
+        .or_else(|| Format::detect(&bytes))
+        .or_else(|| Format::from_extension(path))
+    else {
+        exit_with_error(format!(
+            "Unable to detect the format of `{}`, pass --from",
+            path.display()
+        ))
+    };
+    let data = HaversineData::parse_from_slice(&bytes, from).unwrap_or_else(|e| {
+        exit_with_error(format!(
+            "Unable to deserialize `{}` as {from}: {e}",
+            path.display()
+        ))
+    });
+    (data, from)
+}
+
+/// Output format `to`, or the one implied by the output's extension
+pub fn output_format(path: &Path, to: Option<Format>) -> Format {
+    to.or_else(|| Format::from_extension(path))
+        .unwrap_or_else(|| {
+            exit_with_error(format!(
+                "Unable to infer the output format of `{}`, pass --to",
+                path.display()
+            ))
+        })
+}
+
+/// Writes `data` as `to`, JSON in the generator's pretty layout unless `compact`
+pub fn write_dataset(data: &HaversineData, path: &Path, to: Format, compact: bool) {
+    let mut writer = BufWriter::new(create(path));
+    let written = match to {
+        Format::Json if compact => serde_json::to_writer(&mut writer, data).map_err(Into::into),
+        Format::Json => {
+            write_json(data, &mut writer);
+            Ok(())
+        }
+        Format::Binary => data.write_binary(&mut writer),
+        Format::Csv => data.write_csv(&mut writer),
+        Format::Ndjson => data.write_ndjson(&mut writer),
+        #[cfg(feature = "parquet")]
+        Format::Parquet => data.write_parquet(&mut writer),
+    };
+    written.and_then(|()| writer.flush()).unwrap_or_else(|e| {
+        exit_with_io_error(format!("Unable to write `{}`: {e}", path.display()))
+    });
+}
+
+pub fn run(args: &ConvertArgs, shared: SharedArgs) {
+    let (data, from) = read_dataset(&args.input, args.from);
+    let to = output_format(&args.output, args.to);
+    write_dataset(&data, &args.output, to, args.compact);
+
+    info!(
+        %from,
+        %to,
+        pair_count = data.pairs.len(),
+        "Converted `{}`",
+        args.input.display()
+    );
+    if let Some(answers) = &args.answers {
+        let avg = write_answers(&data, shared.model(), None, create(answers));
+        println!("Average: {avg:.16}");
+    }
+}
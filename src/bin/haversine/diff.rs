@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use haversine::{pipeline::PipelineError, validation::AnswerFile};
+
+use crate::{exit_with_error, exit_with_io_error};
+
+#[derive(Args)]
+pub struct DiffArgs {
+    #[arg(name = "answers_a.f64")]
+    a: PathBuf,
+    #[arg(name = "answers_b.f64")]
+    b: PathBuf,
+    /// Read both answers files as written before they had a header and checksum
+    #[arg(long)]
+    legacy_answers: bool,
+}
+
+/// Largest and mean absolute difference between two runs' distances
+struct Difference {
+    max: f64,
+    /// pair with the largest difference
+    worst: usize,
+    mean: f64,
+}
+
+fn difference(a: &[f64], b: &[f64]) -> Option<Difference> {
+    let (mut max, mut worst, mut sum) = (0f64, 0, 0f64);
+    for (index, (a, b)) in a.iter().zip(b).enumerate() {
+        let difference = (a - b).abs();
+        sum += difference;
+        if difference > max {
+            (max, worst) = (difference, index);
+        }
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mean = sum / a.len() as f64;
+    (!a.is_empty()).then_some(Difference { max, worst, mean })
+}
+
+pub fn run(args: &DiffArgs) {
+    let read = |path| {
+        AnswerFile::read(path, args.legacy_answers).unwrap_or_else(|e| match e {
+            PipelineError::Io { .. } => exit_with_io_error(e),
+            e => exit_with_error(e),
+        })
+    };
+    let (a, b) = (read(&args.a), read(&args.b));
+    let (Some(average_a), Some(average_b)) = (a.average(), b.average()) else {
+        exit_with_error("Answers files without an average")
+    };
+    let (a, b) = (a.distances(), b.distances());
+    if a.len() != b.len() {
+        exit_with_error(format!(
+            "`{}` has {} distances and `{}` has {}",
+            args.a.display(),
+            a.len(),
+            args.b.display(),
+            b.len()
+        ));
+    }
+    println!("Pair count: {}", a.len());
+    if let Some(Difference { max, worst, mean }) = difference(a, b) {
+        println!(
+            "Max difference: {max} at pair {worst} ({} vs {})",
+            a[worst], b[worst]
+        );
+        println!("Mean difference: {mean}");
+    }
+    println!("Average difference: {}", average_a - average_b);
+}
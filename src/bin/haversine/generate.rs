@@ -0,0 +1,581 @@
+use core::fmt;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, ValueEnum};
+use haversine::{
+    answers::{AnswersHeader, AnswersWriter, Distribution},
+    format::Format,
+    generator::{ExponentialParams, GaussianParams, GeneratorConfig, HubParams},
+    shard, EarthModel, HaversineData, JsonWriter,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use tracing::info;
+
+use crate::{
+    convert::{create, write_dataset},
+    exit_with_error, exit_with_io_error,
+    sample::save,
+    SharedArgs,
+};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum HaversineDist {
+    Uniform,
+    Cluster,
+    /// normally distributed around `--center-x`/`--center-y`
+    Gaussian,
+    /// exponentially distributed distances from `--center-x`/`--center-y`
+    Exponential,
+    /// antipodes, identical points, pole crossings and antimeridian wraps
+    EdgeCases,
+    /// pairs between `--hubs` random points, each end up to `--jitter` km off its hub
+    Hub,
+}
+
+impl fmt::Display for HaversineDist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cluster => write!(f, "cluster"),
+            Self::Uniform => write!(f, "uniform"),
+            Self::Gaussian => write!(f, "gaussian"),
+            Self::Exponential => write!(f, "exponential"),
+            Self::EdgeCases => write!(f, "edge-cases"),
+            Self::Hub => write!(f, "hub"),
+        }
+    }
+}
+
+impl From<HaversineDist> for Distribution {
+    fn from(dist: HaversineDist) -> Self {
+        match dist {
+            HaversineDist::Uniform => Self::Uniform,
+            HaversineDist::Cluster => Self::Cluster,
+            HaversineDist::Gaussian => Self::Gaussian,
+            HaversineDist::Exponential => Self::Exponential,
+            HaversineDist::EdgeCases => Self::EdgeCases,
+            HaversineDist::Hub => Self::Hub,
+        }
+    }
+}
+
+/// Shape of the gaussian, exponential and hub distributions
+#[derive(Args, Clone, Copy)]
+pub struct ShapeArgs {
+    /// Longitude of the gaussian mean or the exponential origin
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    center_x: f64,
+    /// Latitude of the gaussian mean or the exponential origin
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    center_y: f64,
+    /// Standard deviation of the gaussian longitudes, in degrees
+    #[arg(long, default_value_t = 30.0, value_parser = non_negative, allow_negative_numbers = true)]
+    sigma_x: f64,
+    /// Standard deviation of the gaussian latitudes, in degrees
+    #[arg(long, default_value_t = 15.0, value_parser = non_negative, allow_negative_numbers = true)]
+    sigma_y: f64,
+    /// Mean great-circle distance of the exponential points from the origin, in km
+    #[arg(long, default_value_t = 1000.0, value_parser = non_negative, allow_negative_numbers = true)]
+    mean_distance: f64,
+    /// Number of hubs the hub pairs join
+    #[arg(long, default_value_t = HubParams::default().hubs)]
+    hubs: NonZeroUsize,
+    /// Largest distance of a hub pair's end from its hub, in km
+    #[arg(long, default_value_t = HubParams::default().jitter, value_parser = non_negative, allow_negative_numbers = true)]
+    jitter: f64,
+}
+
+fn non_negative(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(value) if value >= 0.0 => Ok(value),
+        Ok(_) => Err(String::from("must not be negative")),
+        Err(e) => Err(format!("{e}")),
+    }
+}
+
+impl ShapeArgs {
+    /// `n` pairs of `dist` for `seed`
+    pub fn generate(&self, dist: HaversineDist, n: usize, seed: u64) -> HaversineData {
+        let center = (self.center_x, self.center_y);
+        GeneratorConfig::new(dist.into(), n)
+            .seed(seed)
+            .gaussian(GaussianParams {
+                mean: center,
+                std_dev: (self.sigma_x, self.sigma_y),
+            })
+            .exponential(ExponentialParams {
+                origin: center,
+                mean_distance: self.mean_distance,
+            })
+            .hub(self.hub_params())
+            .generate()
+    }
+
+    fn hub_params(&self) -> HubParams {
+        HubParams {
+            hubs: self.hubs,
+            jitter: self.jitter,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct GenerateArgs {
+    #[arg(name = "uniform/cluster/gaussian/exponential/edge-cases/hub")]
+    dist: HaversineDist,
+    #[arg(name = "random seed")]
+    seed: u64,
+    #[arg(name = "number of coordinate pairs to generate")]
+    pair_count: usize,
+    /// Also write this many corrupted copies of the JSON (`data_<n>_corrupt<i>.json`)
+    #[arg(long, default_value_t = 0)]
+    corrupt: usize,
+    /// Format of the dataset (json, binary, csv, ndjson, or parquet with the `parquet`
+    /// feature), written as `data_<n>_flex.<ext>`
+    #[arg(long, visible_alias = "output-format", default_value = "json")]
+    format: Format,
+    /// Split the pairs in order across this many files (`data_<n>_part<i>_flex.<ext>`),
+    /// each with its own answers file
+    #[arg(long, default_value_t = 1)]
+    shards: usize,
+    /// Round the coordinates to this many decimals, for smaller files
+    #[arg(long)]
+    precision: Option<usize>,
+    /// Generate this many datasets with seeds `seed..seed + count`, named
+    /// `data_<n>_seed<s>_flex.<ext>`, and list them in `data_<n>_seed<first>-<last>_manifest.json`
+    #[arg(long)]
+    batch: Option<usize>,
+    /// Directory the files are written to, created if missing
+    #[arg(long, default_value = ".")]
+    out_dir: PathBuf,
+    /// Name of the dataset without its extension, with `{count}`, `{seed}` and `{dist}`
+    /// replaced; the answers file is named after it (`<name>_haveranswer.f64`)
+    #[arg(long)]
+    name_template: Option<String>,
+    /// Overwrite existing files
+    #[arg(long)]
+    force: bool,
+    /// Only write the datasets, without computing the distances for answers files
+    #[arg(long)]
+    no_answers: bool,
+    #[command(flatten)]
+    shape: ShapeArgs,
+}
+
+/// A dataset and its answers file, unless `--no-answers`
+#[derive(Serialize)]
+struct ManifestFile {
+    data: PathBuf,
+    answers: Option<PathBuf>,
+}
+
+/// Everything generated for one seed of a `--batch` run
+#[derive(Serialize)]
+struct ManifestEntry {
+    seed: u64,
+    distribution: String,
+    pair_count: usize,
+    /// one per shard
+    files: Vec<ManifestFile>,
+    corrupt: Vec<PathBuf>,
+    /// reference average over all the pairs, unless `--no-answers`
+    average: Option<f64>,
+    /// of a hub distribution
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    hub: Option<ManifestHub>,
+}
+
+#[derive(Serialize)]
+struct ManifestHub {
+    hubs: NonZeroUsize,
+    jitter: f64,
+}
+
+pub fn write_json(data: &HaversineData, writer: impl Write) {
+    data.write_json(writer, None).expect("Unable to write data");
+}
+
+/// Rounds the coordinates to `precision` decimals, through the same
+/// formatting as `HaversineData::write_json`, so that the answers are
+/// computed on the values a parser reads back
+fn round_coordinates(data: &mut HaversineData, precision: usize) {
+    let round = |value: &mut f64| {
+        *value = format!("{value:.precision$}")
+            .parse()
+            .expect("a formatted f64 parses");
+    };
+    for point in &mut data.pairs {
+        round(&mut point.p0.lon);
+        round(&mut point.p0.lat);
+        round(&mut point.p1.lon);
+        round(&mut point.p1.lat);
+    }
+}
+
+/// Applies one random mutation (byte flip, truncation, deletion or
+/// duplication of a range) to a copy of `json`
+fn corrupt(json: &[u8], rng: &mut impl Rng) -> Vec<u8> {
+    let mut bytes = json.to_vec();
+    if bytes.is_empty() {
+        return bytes;
+    }
+    let at = rng.gen_range(0..bytes.len());
+    let end = rng.gen_range(at..=bytes.len().min(at + 64));
+    match rng.gen_range(0..4) {
+        0 => bytes[at] = rng.gen(),
+        1 => bytes.truncate(at),
+        2 => drop(bytes.drain(at..end)),
+        _ => {
+            let range = bytes[at..end].to_vec();
+            bytes.splice(at..at, range);
+        }
+    }
+    bytes
+}
+
+/// `data_1000_flex.json` -> `data_1000_corrupt<index>.json`
+fn corrupt_path(path: &Path, index: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let stem = stem.strip_suffix("_flex").unwrap_or(stem);
+    path.with_file_name(format!("{stem}_corrupt{index}.json"))
+}
+
+/// Writes `count` corrupted copies of the JSON next to `path`, returns their paths
+fn save_corrupted_files(
+    data: &HaversineData,
+    path: &Path,
+    count: usize,
+    seed: u64,
+) -> Vec<PathBuf> {
+    if count == 0 {
+        return Vec::new();
+    }
+    let mut json = Vec::new();
+    write_json(data, &mut json);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    (0..count)
+        .map(|i| {
+            let corrupt_path = corrupt_path(path, i);
+            std::fs::write(&corrupt_path, corrupt(&json, &mut rng)).expect("Unable to create file");
+            corrupt_path
+        })
+        .collect()
+}
+
+/// `<out-dir>/data_<n>_flex.<ext>`, `data_<n>_seed<s>_flex.<ext>` in a batch, or the
+/// name template
+fn data_path(args: &GenerateArgs, seed: u64, batch: bool) -> PathBuf {
+    let name = match &args.name_template {
+        Some(template) => template
+            .replace("{count}", &args.pair_count.to_string())
+            .replace("{seed}", &seed.to_string())
+            .replace("{dist}", &args.dist.to_string()),
+        None if batch => format!("data_{}_seed{seed}_flex", args.pair_count),
+        None => format!("data_{}_flex", args.pair_count),
+    };
+    args.out_dir
+        .join(format!("{name}.{}", args.format.extension()))
+}
+
+/// Every file `generate` writes for `seed`
+fn output_paths(args: &GenerateArgs, seed: u64, batch: bool) -> Vec<PathBuf> {
+    let path = data_path(args, seed, batch);
+    let datasets = if args.shards == 1 {
+        vec![path.clone()]
+    } else {
+        (0..args.shards)
+            .map(|index| shard::path(&path, index, args.format))
+            .collect()
+    };
+    let mut paths: Vec<PathBuf> = datasets
+        .into_iter()
+        .flat_map(|data| {
+            let answers = (!args.no_answers).then(|| answers_path(&data));
+            [Some(data), answers]
+        })
+        .flatten()
+        .collect();
+    paths.extend((0..args.corrupt).map(|index| corrupt_path(&path, index)));
+    paths
+}
+
+/// Exits if one of `paths` exists, unless `--force`
+fn refuse_overwrite(args: &GenerateArgs, paths: &[PathBuf]) {
+    if args.force {
+        return;
+    }
+    if let Some(path) = paths.iter().find(|path| path.exists()) {
+        exit_with_error(format!(
+            "`{}` already exists, pass --force to overwrite it",
+            path.display()
+        ))
+    }
+}
+
+/// Writes the pairs in order as `shards` datasets (see `shard::split`) and,
+/// unless `--no-answers`, answers files next to `path`; returns them and the
+/// average over all the pairs
+fn save_shards(
+    args: &GenerateArgs,
+    data: &HaversineData,
+    path: &Path,
+    model: EarthModel,
+    generated: (HaversineDist, u64),
+) -> (Vec<ManifestFile>, Option<f64>) {
+    let count = NonZeroUsize::new(args.shards).expect("checked by `run`");
+    let mut averages = Vec::with_capacity(args.shards);
+    let files = shard::split(data, count)
+        .enumerate()
+        .map(|(index, shard)| {
+            let shard_path = shard::path(path, index, args.format);
+            if args.no_answers {
+                write_dataset(&shard, &shard_path, args.format, false);
+                return ManifestFile {
+                    data: shard_path,
+                    answers: None,
+                };
+            }
+            let (answers, average) = save(&shard, &shard_path, args.format, model, Some(generated));
+            averages.push((shard.pairs.len(), average));
+            ManifestFile {
+                data: shard_path,
+                answers: Some(answers),
+            }
+        })
+        .collect();
+    let average = (!args.no_answers).then(|| shard::average(averages));
+    (files, average)
+}
+
+/// Writes the answers file of `data`, with the distribution and seed it was
+/// `generated` with if any, returns the average
+pub fn write_answers(
+    data: &HaversineData,
+    model: EarthModel,
+    generated: Option<(HaversineDist, u64)>,
+    writer: impl Write,
+) -> f64 {
+    let header = AnswersHeader {
+        count: data.pairs.len() as u64,
+        model,
+        distribution: generated.map(|(dist, _)| dist.into()),
+        seed: generated.map(|(_, seed)| seed),
+    };
+    let mut writer =
+        AnswersWriter::new(BufWriter::new(writer), &header).expect("Failed to write to file");
+
+    let mut sum = 0f64;
+    for point in &data.pairs {
+        let dist = model.distance(point);
+        sum += dist;
+        writer
+            .write_distance(dist)
+            .expect("Failed to write to file");
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let avg = sum / data.pairs.len() as f64;
+    writer
+        .finish(avg)
+        .expect("Failed to write to file")
+        .flush()
+        .expect("Failed to flush buffer");
+    avg
+}
+
+/// Writes `data` as JSON and its answers file in a single pass over the pairs,
+/// each distance written as its pair is serialized; returns the average
+pub fn write_json_and_answers(
+    data: &HaversineData,
+    model: EarthModel,
+    generated: Option<(HaversineDist, u64)>,
+    json: impl Write,
+    answers: impl Write,
+) -> f64 {
+    let header = AnswersHeader {
+        count: data.pairs.len() as u64,
+        model,
+        distribution: generated.map(|(dist, _)| dist.into()),
+        seed: generated.map(|(_, seed)| seed),
+    };
+    let mut json = JsonWriter::new(BufWriter::new(json), None).expect("Unable to write data");
+    let mut answers =
+        AnswersWriter::new(BufWriter::new(answers), &header).expect("Failed to write to file");
+
+    let mut sum = 0f64;
+    for point in &data.pairs {
+        json.write_pair(point).expect("Unable to write data");
+        let dist = model.distance(point);
+        sum += dist;
+        answers
+            .write_distance(dist)
+            .expect("Failed to write to file");
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let avg = sum / data.pairs.len() as f64;
+    json.finish()
+        .expect("Unable to write data")
+        .flush()
+        .expect("Failed to flush buffer");
+    answers
+        .finish(avg)
+        .expect("Failed to write to file")
+        .flush()
+        .expect("Failed to flush buffer");
+    avg
+}
+
+/// Writes `data` as `format` to `path` and its answers file next to it,
+/// returns the answers file and the average. JSON is written in the same pass
+/// as the answers, the other formats before them.
+pub fn save_with_answers(
+    data: &HaversineData,
+    path: &Path,
+    format: Format,
+    model: EarthModel,
+    generated: Option<(HaversineDist, u64)>,
+) -> (PathBuf, f64) {
+    let answers = answers_path(path);
+    let avg = if format == Format::Json {
+        write_json_and_answers(data, model, generated, create(path), create(&answers))
+    } else {
+        write_dataset(data, path, format, false);
+        write_answers(data, model, generated, create(&answers))
+    };
+    (answers, avg)
+}
+
+/// `data_1000_flex.json` -> `data_1000_haveranswer.f64`
+pub fn answers_path(data_file: &Path) -> PathBuf {
+    let stem = data_file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let stem = stem.strip_suffix("_flex").unwrap_or(stem);
+    data_file.with_file_name(format!("{stem}_haveranswer.f64"))
+}
+
+/// Generates and writes the dataset for `seed`, named for a batch if `batch`
+fn generate(args: &GenerateArgs, seed: u64, batch: bool, model: EarthModel) -> ManifestEntry {
+    let mut data = args.shape.generate(args.dist, args.pair_count, seed);
+    if let Some(precision) = args.precision {
+        round_coordinates(&mut data, precision);
+    }
+    let path = data_path(args, seed, batch);
+    let (files, average) = if args.shards != 1 {
+        save_shards(args, &data, &path, model, (args.dist, seed))
+    } else if args.no_answers {
+        write_dataset(&data, &path, args.format, false);
+        let file = ManifestFile {
+            data: path.clone(),
+            answers: None,
+        };
+        (vec![file], None)
+    } else {
+        let (answers, average) =
+            save_with_answers(&data, &path, args.format, model, Some((args.dist, seed)));
+        let file = ManifestFile {
+            data: path.clone(),
+            answers: Some(answers),
+        };
+        (vec![file], Some(average))
+    };
+    let corrupt = save_corrupted_files(&data, &path, args.corrupt, seed);
+    info!(
+        method = %args.dist,
+        seed,
+        pair_count = args.pair_count,
+        "Generated dataset"
+    );
+    ManifestEntry {
+        seed,
+        distribution: args.dist.to_string(),
+        pair_count: args.pair_count,
+        files,
+        corrupt,
+        average,
+        hub: matches!(args.dist, HaversineDist::Hub).then(|| {
+            let HubParams { hubs, jitter } = args.shape.hub_params();
+            ManifestHub { hubs, jitter }
+        }),
+    }
+}
+
+pub fn run(args: &GenerateArgs, shared: SharedArgs) {
+    if args.shards != 1 && !(2..=args.pair_count).contains(&args.shards) {
+        exit_with_error(format!(
+            "Unable to split {} pairs into {} shards",
+            args.pair_count, args.shards
+        ))
+    }
+    if let Some(template) = &args.name_template {
+        let unknown = template
+            .replace("{count}", "")
+            .replace("{seed}", "")
+            .replace("{dist}", "");
+        if unknown.contains(['{', '}']) {
+            exit_with_error(
+                "Unknown placeholder in --name-template, expected {count}, {seed} or {dist}",
+            )
+        }
+        if args.batch.is_some() && !template.contains("{seed}") {
+            exit_with_error("--name-template needs {seed} to name the datasets of a --batch")
+        }
+    }
+    std::fs::create_dir_all(&args.out_dir).unwrap_or_else(|e| {
+        exit_with_io_error(format!(
+            "Unable to create `{}`: {e}",
+            args.out_dir.display()
+        ))
+    });
+    let Some(count) = args.batch else {
+        refuse_overwrite(args, &output_paths(args, args.seed, false));
+        let entry = generate(args, args.seed, false, shared.model());
+        if let Some(average) = entry.average {
+            println!("Average: {average:.16}");
+        }
+        return;
+    };
+    let Some(last) = u64::try_from(count)
+        .ok()
+        .filter(|&count| count > 0)
+        .and_then(|count| args.seed.checked_add(count - 1))
+    else {
+        exit_with_error(format!(
+            "Unable to generate a batch of {count} from seed {}",
+            args.seed
+        ))
+    };
+    let manifest = args.out_dir.join(format!(
+        "data_{}_seed{}-{last}_manifest.json",
+        args.pair_count, args.seed
+    ));
+    let mut outputs: Vec<_> = (args.seed..=last)
+        .flat_map(|seed| output_paths(args, seed, true))
+        .collect();
+    outputs.push(manifest.clone());
+    refuse_overwrite(args, &outputs);
+    let entries: Vec<_> = (args.seed..=last)
+        .map(|seed| {
+            let entry = generate(args, seed, true, shared.model());
+            if let Some(average) = entry.average {
+                println!("Seed {seed} average: {average:.16}");
+            }
+            entry
+        })
+        .collect();
+    let file = BufWriter::new(File::create(&manifest).expect("Unable to create file"));
+    serde_json::to_writer_pretty(file, &entries).expect("Unable to write manifest");
+    println!("Manifest: {}", manifest.display());
+}
@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use haversine::{
+    answers::{self, AnswersError},
+    format::Format,
+    pipeline::PipelineOptions,
+    vincenty_distance, EarthModel, HaversineData, WGS84_A, WGS84_F,
+};
+
+use crate::{exit_with_error, exit_with_io_error, generate, SharedArgs};
+
+#[derive(Args)]
+pub struct InspectArgs {
+    #[arg(name = "dataset")]
+    data_file: PathBuf,
+    /// Answers file to check, defaults to the generator's name next to the dataset
+    #[arg(long)]
+    answers: Option<PathBuf>,
+}
+
+/// min, max
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    })
+}
+
+pub fn run(args: &InspectArgs, shared: SharedArgs) {
+    let bytes = std::fs::read(&args.data_file).unwrap_or_else(|e| {
+        exit_with_io_error(format!(
+            "Unable to open `{}`: {e}",
+            args.data_file.display()
+        ))
+    });
+    let Some(format) = Format::detect(&bytes).or_else(|| Format::from_extension(&args.data_file))
+    else {
+        exit_with_error(format!(
+            "Unable to detect the format of `{}`",
+            args.data_file.display()
+        ))
+    };
+    println!("File size: {}", bytes.len());
+    println!("Format: {format}");
+
+    let data = HaversineData::parse_from_slice(&bytes, format).unwrap_or_else(|e| {
+        exit_with_error(format!(
+            "Unable to deserialize `{}` as {format}: {e}",
+            args.data_file.display()
+        ))
+    });
+    let pairs = &data.pairs;
+    println!("Pair count: {}", pairs.len());
+    if pairs.is_empty() {
+        return;
+    }
+
+    let (x_min, x_max) = bounds(pairs.iter().flat_map(|p| [p.p0.lon, p.p1.lon]));
+    let (y_min, y_max) = bounds(pairs.iter().flat_map(|p| [p.p0.lat, p.p1.lat]));
+    println!("Longitude: [{x_min}, {x_max}]");
+    println!("Latitude: [{y_min}, {y_max}]");
+    println!("Out of range: {} pairs", data.validate().len());
+
+    let model = shared.model();
+    let distances: Vec<f64> = pairs.iter().map(|point| model.distance(point)).collect();
+    let (d_min, d_max) = bounds(distances.iter().copied());
+    #[allow(clippy::cast_precision_loss)]
+    let count = distances.len() as f64;
+    let mean = distances.iter().sum::<f64>() / count;
+    let std_dev = (distances.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / count).sqrt();
+    println!("Distance: min {d_min} max {d_max} avg {mean} std dev {std_dev}");
+
+    // The distances on the model against the WGS-84 ellipsoid's
+    let (mut vincenty_sum, mut max_difference, mut converged) = (0f64, 0f64, 0usize);
+    for (point, &distance) in pairs.iter().zip(&distances) {
+        if let Ok(vincenty) = vincenty_distance(point, WGS84_A, WGS84_F) {
+            vincenty_sum += vincenty;
+            max_difference = max_difference.max((distance - vincenty).abs());
+            converged += 1;
+        }
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let vincenty_mean = vincenty_sum / converged as f64;
+    println!(
+        "Vincenty (WGS-84): avg {vincenty_mean} max difference {max_difference}, {} pairs did not converge",
+        pairs.len() - converged
+    );
+
+    let answers_path = args
+        .answers
+        .clone()
+        .unwrap_or_else(|| generate::answers_path(&args.data_file));
+    println!();
+    check_answers(&answers_path, &distances, mean, model);
+}
+
+/// Reports the header of the answers file at `path` and whether it matches
+/// the `distances` and their `mean` on `model`
+fn check_answers(path: &Path, distances: &[f64], mean: f64, model: EarthModel) {
+    if !path.exists() {
+        println!("Answers: `{}` not found", path.display());
+        return;
+    }
+    let bytes = std::fs::read(path).unwrap_or_else(|e| {
+        exit_with_io_error(format!("Unable to open `{}`: {e}", path.display()))
+    });
+    println!("Answers: `{}`", path.display());
+    let answers = match answers::decode(&bytes) {
+        Ok((header, answers)) => {
+            let origin = match (header.distribution, header.seed) {
+                (Some(distribution), Some(seed)) => format!("{distribution:?} seed {seed}"),
+                _ => "not generated".to_owned(),
+            };
+            println!("Header: {}, {origin}", header.model);
+            if header.model != model {
+                println!("Note: the distances here are on the {model}, see --model");
+            }
+            answers
+        }
+        Err(AnswersError::MissingHeader) => {
+            println!("Header: none (legacy file)");
+            answers::decode_legacy(&bytes)
+        }
+        Err(e) => {
+            println!("Inconsistent: {e}");
+            return;
+        }
+    };
+    if answers.len() != distances.len() + 1 {
+        println!(
+            "Inconsistent: {} entries, expected {} (one per pair and the average)",
+            answers.len(),
+            distances.len() + 1
+        );
+        return;
+    }
+    // Same tolerance as the processor's validation by default.
+    let tolerance = PipelineOptions::default().tolerance;
+    let mismatches = distances
+        .iter()
+        .zip(&answers)
+        .filter(|(dist, expected)| (*dist - *expected).abs() > tolerance)
+        .count();
+    let reference_average = answers[distances.len()];
+    println!("Mismatched distances: {mismatches}");
+    println!("Reference avg: {reference_average}");
+    println!("Difference: {}", reference_average - mean);
+    if mismatches == 0 {
+        println!("Consistent");
+    } else {
+        println!("Inconsistent");
+    }
+}
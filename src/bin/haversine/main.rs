@@ -0,0 +1,149 @@
+mod bench;
+mod compute;
+mod convert;
+mod diff;
+mod generate;
+mod inspect;
+mod pipeline;
+mod report;
+mod sample;
+
+use std::{fmt::Display, io::IsTerminal};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use haversine::{numa, EarthModel, EARTH_RADIUS};
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+/// Counts the allocations of each anchor for the profile
+#[cfg(feature = "enable-perf")]
+#[global_allocator]
+static ALLOCATOR: perf::TrackingAllocator = perf::TrackingAllocator;
+
+/// Haversine Distance Processor
+#[derive(Parser)]
+struct Cli {
+    /// Diagnostics on stderr as text or as one JSON object per line, filtered by `RUST_LOG`
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    #[command(flatten)]
+    shared: SharedArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Flags accepted by every subcommand
+#[derive(Args, Clone, Copy)]
+struct SharedArgs {
+    /// Sphere radius the distances are computed on
+    #[arg(long, global = true, default_value_t = EARTH_RADIUS)]
+    radius: f64,
+    /// Earth model the distances are computed on instead of the `--radius` sphere:
+    /// sphere, sphere=<radius>, wgs84 or ellipsoid=<a>,<f> with 0 <= f < 1
+    #[arg(long, global = true, conflicts_with = "radius")]
+    model: Option<EarthModel>,
+    /// Bind threads and memory to this NUMA node and report its allocations
+    #[arg(long, global = true)]
+    numa_node: Option<u32>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a dataset and its answers file
+    Generate(generate::GenerateArgs),
+    /// Compute the average distance of a dataset, optionally validating it
+    Compute(compute::ComputeArgs),
+    /// Validate a dataset against its answers file
+    Validate(compute::ValidateArgs),
+    /// Generate, compute and validate a temporary dataset in one go
+    Pipeline(pipeline::PipelineArgs),
+    /// Report the format, statistics and answers file consistency of a dataset
+    Inspect(inspect::InspectArgs),
+    /// Compare the distances of two answers files, e.g. of two distance implementations
+    Diff(diff::DiffArgs),
+    /// Time repeated runs of the pipeline over a dataset
+    Bench(bench::BenchArgs),
+    /// Convert a dataset between json, binary, csv, ndjson and parquet
+    Convert(convert::ConvertArgs),
+    /// Extract a seeded random subset of a dataset with its answers file
+    Sample(sample::SampleArgs),
+    /// Shuffle a dataset into shards (80/20 by default), each with its answers file
+    Split(sample::SplitArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Events go to stderr so that stdout only carries the results
+fn init_logging(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_env_filter(filter)
+        .with_target(false);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+impl SharedArgs {
+    fn model(&self) -> EarthModel {
+        self.model.unwrap_or(EarthModel::Sphere {
+            radius: self.radius,
+        })
+    }
+}
+
+fn exit_with_error(message: impl Display) -> ! {
+    tracing::error!("{message}");
+    std::process::exit(1)
+}
+
+/// Exits with the status of clap's errors, as unreadable or unwritable
+/// paths are usage errors
+fn exit_with_io_error(message: impl Display) -> ! {
+    tracing::error!("{message}");
+    std::process::exit(2)
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_logging(cli.log_format);
+    let numa_start = cli.shared.numa_node.map(|node| {
+        numa::bind(node)
+            .and_then(|()| numa::NodeStat::read(node))
+            .map_or_else(
+                |e| exit_with_io_error(format!("Unable to bind to NUMA node {node}: {e}")),
+                |stat| (node, stat),
+            )
+    });
+    match cli.command {
+        Command::Generate(args) => generate::run(&args, cli.shared),
+        Command::Compute(args) => compute::run(args, cli.shared),
+        Command::Validate(args) => compute::validate(args, cli.shared),
+        Command::Pipeline(args) => pipeline::run(&args, cli.shared),
+        Command::Inspect(args) => inspect::run(&args, cli.shared),
+        Command::Diff(args) => diff::run(&args),
+        Command::Bench(args) => bench::run(&args, cli.shared),
+        Command::Convert(args) => convert::run(&args, cli.shared),
+        Command::Sample(args) => sample::sample(&args, cli.shared),
+        Command::Split(args) => sample::split(&args, cli.shared),
+    }
+    if let Some((node, start)) = numa_start {
+        if let Ok(end) = numa::NodeStat::read(node) {
+            let stat = end.since(&start);
+            info!(
+                node,
+                local = stat.local,
+                remote = stat.remote,
+                missed = stat.miss,
+                "NUMA node page allocations"
+            );
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{fs::File, time::Instant};
+
+use clap::Args;
+use haversine::pipeline::{Pipeline, PipelineError, PipelineOptions};
+
+use tracing::info;
+
+use crate::{
+    exit_with_error, exit_with_io_error,
+    generate::{write_json_and_answers, HaversineDist, ShapeArgs},
+    SharedArgs,
+};
+
+#[derive(Args)]
+pub struct PipelineArgs {
+    /// Number of coordinate pairs to generate
+    #[arg(long)]
+    pairs: usize,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    #[arg(long, default_value_t = HaversineDist::Cluster)]
+    dist: HaversineDist,
+    #[command(flatten)]
+    shape: ShapeArgs,
+}
+
+/// Generates a dataset into a temporary directory, runs and validates the
+/// pipeline on it, prints one report and removes the directory.
+pub fn run(args: &PipelineArgs, shared: SharedArgs) {
+    let dir = std::env::temp_dir().join(format!("haversine-pipeline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap_or_else(|e| exit_with_io_error(e));
+    let data_file = dir.join(format!("data_{}_flex.json", args.pairs));
+    let answer_file = dir.join(format!("data_{}_haveranswer.f64", args.pairs));
+
+    let start = Instant::now();
+    let data = args.shape.generate(args.dist, args.pairs, args.seed);
+    let generate_time = start.elapsed();
+
+    let start = Instant::now();
+    let create = |path| File::create(path).unwrap_or_else(|e| exit_with_io_error(e));
+    write_json_and_answers(
+        &data,
+        shared.model(),
+        Some((args.dist, args.seed)),
+        create(&data_file),
+        create(&answer_file),
+    );
+    let write_time = start.elapsed();
+    drop(data);
+
+    let pipeline = Pipeline::new(PipelineOptions {
+        model: shared.model(),
+        answers: Some(answer_file),
+        ..PipelineOptions::default()
+    });
+    let start = Instant::now();
+    let result = pipeline.run(&data_file);
+    let compute_time = start.elapsed();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let report = match result {
+        Ok(report) => report,
+        Err(e @ PipelineError::Io { .. }) => exit_with_io_error(e),
+        Err(e) => exit_with_error(e),
+    };
+    let validation = report
+        .validation
+        .expect("pipeline validates when given answers");
+
+    #[allow(clippy::cast_precision_loss)]
+    let megabytes = report.input_size as f64 / (1024.0 * 1024.0);
+    info!(
+        method = %args.dist,
+        seed = args.seed,
+        pair_count = report.pair_count,
+        input_size = report.input_size,
+        "Generated dataset"
+    );
+    info!(
+        generate_ms = generate_time.as_secs_f64() * 1000.0,
+        write_ms = write_time.as_secs_f64() * 1000.0,
+        compute_ms = compute_time.as_secs_f64() * 1000.0,
+        compute_mb_per_s = megabytes / compute_time.as_secs_f64(),
+        "Timings"
+    );
+    println!("Haversine avg: {}", report.average);
+    println!("Reference avg: {}", validation.reference_average);
+    println!("Difference: {}", validation.difference);
+    println!("Validation passed");
+}
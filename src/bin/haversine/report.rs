@@ -0,0 +1,141 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use haversine::pipeline::RunReport;
+use perf::ProfileTotals;
+
+/// Where `--report` writes the results of a run instead of printing them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Report {
+    /// one CSV row under its header, on stdout or appended to a file
+    Csv(Option<PathBuf>),
+}
+
+impl FromStr for Report {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            None if s == "csv" => Ok(Self::Csv(None)),
+            Some(("csv", path)) if !path.is_empty() => Ok(Self::Csv(Some(path.into()))),
+            _ => Err(format!("unknown report `{s}`, expected csv or csv=<path>")),
+        }
+    }
+}
+
+/// Column names and values of a run: the input, its size and pair count,
+/// the average, the wall time, then the cycles of every anchor
+fn columns(data_file: &Path, run: &RunReport, profile: &ProfileTotals) -> Vec<(String, String)> {
+    #[allow(clippy::cast_precision_loss)]
+    let wall_seconds = profile.elapsed as f64 / profile.timer_frequency as f64;
+    let mut columns = vec![
+        ("input".to_owned(), data_file.display().to_string()),
+        ("input_size".to_owned(), run.input_size.to_string()),
+        ("pair_count".to_owned(), run.pair_count.to_string()),
+        ("average".to_owned(), run.average.to_string()),
+        ("wall_seconds".to_owned(), wall_seconds.to_string()),
+    ];
+    columns.extend(
+        profile
+            .traces
+            .iter()
+            .map(|(name, ticks)| (format!("{name} cycles"), ticks.to_string())),
+    );
+    columns
+}
+
+/// `field` quoted if it holds a separator, quote or line break
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn record<'a>(fields: impl IntoIterator<Item = &'a str>) -> String {
+    let fields: Vec<_> = fields.into_iter().map(escape).collect();
+    fields.join(",") + "\n"
+}
+
+/// Fields of a line written by `record`
+fn split_record(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("there is a current field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Appends the row to the CSV at `path`, creating it with a header. Anchors
+/// missing from the header, e.g. those of another parser, are added to it and
+/// left empty in the earlier rows.
+fn append(path: &Path, row: &[(String, String)]) -> io::Result<()> {
+    let existing = match fs::read_to_string(path) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut lines = existing.lines();
+    let header = lines.next().map(split_record).unwrap_or_default();
+    let mut names = header.clone();
+    for (name, _) in row {
+        if !names.contains(name) {
+            names.push(name.clone());
+        }
+    }
+    let values = names.iter().map(|name| {
+        row.iter()
+            .find(|(column, _)| column == name)
+            .map_or("", |(_, value)| value.as_str())
+    });
+    let record = record(values);
+    if names.len() == header.len() {
+        return OpenOptions::new()
+            .append(true)
+            .open(path)?
+            .write_all(record.as_bytes());
+    }
+
+    let padding = ",".repeat(names.len() - header.len());
+    let mut rewritten = self::record(names.iter().map(String::as_str));
+    for line in lines {
+        rewritten.push_str(line);
+        rewritten.push_str(&padding);
+        rewritten.push('\n');
+    }
+    rewritten.push_str(&record);
+    fs::write(path, rewritten)
+}
+
+/// Writes the results of the run on `data_file` as `report` asks
+///
+/// # Safety
+///
+/// Reads the profile, like `perf::end_and_print_profile`.
+pub fn write(report: &Report, data_file: &Path, run: &RunReport) -> io::Result<()> {
+    let row = columns(data_file, run, &perf::profile_totals());
+    match report {
+        Report::Csv(None) => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(record(row.iter().map(|(name, _)| name.as_str())).as_bytes())?;
+            stdout.write_all(record(row.iter().map(|(_, value)| value.as_str())).as_bytes())
+        }
+        Report::Csv(Some(path)) => append(path, &row),
+    }
+}
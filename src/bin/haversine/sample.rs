@@ -0,0 +1,129 @@
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use haversine::{format::Format, shard, EarthModel, HaversineData};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    convert::{output_format, read_dataset},
+    exit_with_error,
+    generate::{save_with_answers, HaversineDist},
+    SharedArgs,
+};
+
+#[derive(Args)]
+pub struct SampleArgs {
+    input: PathBuf,
+    output: PathBuf,
+    /// Number of pairs to keep
+    count: usize,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Input format (json, binary, csv, ndjson, parquet), detected from the contents by default
+    #[arg(long)]
+    from: Option<Format>,
+    /// Output format, taken from the output file extension by default
+    #[arg(long)]
+    to: Option<Format>,
+}
+
+#[derive(Args)]
+pub struct SplitArgs {
+    input: PathBuf,
+    /// Share of the pairs in each shard, normalized to their sum
+    #[arg(long, value_delimiter = ',', default_values_t = [0.8, 0.2])]
+    fractions: Vec<f64>,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Input format (json, binary, csv, ndjson, parquet), detected from the contents by default
+    #[arg(long)]
+    from: Option<Format>,
+    /// Shard format, the input's by default
+    #[arg(long)]
+    to: Option<Format>,
+}
+
+/// Pairs at `indices`, kept in their original order
+fn select(data: &HaversineData, mut indices: Vec<usize>) -> HaversineData {
+    indices.sort_unstable();
+    HaversineData {
+        pairs: indices.into_iter().map(|i| data.pairs[i]).collect(),
+    }
+}
+
+/// Writes `data` and its answers file, returns the answers file and the average
+pub fn save(
+    data: &HaversineData,
+    path: &Path,
+    to: Format,
+    model: EarthModel,
+    generated: Option<(HaversineDist, u64)>,
+) -> (PathBuf, f64) {
+    let (answers, avg) = save_with_answers(data, path, to, model, generated);
+    println!(
+        "{}: {} pairs, average {avg:.16}, answers `{}`",
+        path.display(),
+        data.pairs.len(),
+        answers.display()
+    );
+    (answers, avg)
+}
+
+pub fn sample(args: &SampleArgs, shared: SharedArgs) {
+    let (data, _) = read_dataset(&args.input, args.from);
+    let to = output_format(&args.output, args.to);
+    if args.count > data.pairs.len() {
+        exit_with_error(format!(
+            "Unable to sample {} pairs from {}",
+            args.count,
+            data.pairs.len()
+        ))
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(args.seed);
+    let indices = rand::seq::index::sample(&mut rng, data.pairs.len(), args.count).into_vec();
+    save(
+        &select(&data, indices),
+        &args.output,
+        to,
+        shared.model(),
+        None,
+    );
+}
+
+pub fn split(args: &SplitArgs, shared: SharedArgs) {
+    let (data, from) = read_dataset(&args.input, args.from);
+    let to = args.to.unwrap_or(from);
+    let total: f64 = args.fractions.iter().sum();
+    if args.fractions.iter().any(|&f| f < 0.0) || total <= 0.0 {
+        exit_with_error("Fractions must be non-negative with a positive sum");
+    }
+
+    let mut indices: Vec<usize> = (0..data.pairs.len()).collect();
+    indices.shuffle(&mut ChaCha8Rng::seed_from_u64(args.seed));
+    let mut start = 0;
+    let mut cumulative = 0.0;
+    for (index, fraction) in args.fractions.iter().enumerate() {
+        cumulative += fraction;
+        // Cut at the rounded cumulative share, the last shard takes the rest
+        let end = if index + 1 == args.fractions.len() {
+            data.pairs.len()
+        } else {
+            #[allow(
+                clippy::cast_precision_loss,
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss
+            )]
+            let end = (cumulative / total * data.pairs.len() as f64).round() as usize;
+            end.min(data.pairs.len())
+        };
+        save(
+            &select(&data, indices[start..end].to_vec()),
+            &shard::path(&args.input, index, to),
+            to,
+            shared.model(),
+            None,
+        );
+        start = end;
+    }
+}
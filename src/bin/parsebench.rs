@@ -0,0 +1,200 @@
+//! JSON parser shoot-out: every parser over the same mmap'd input, timed with
+//! the repetition tester, followed by `reference_haversine` over the parsed
+//! pairs for comparison. `nom-into` parses into one buffer reused across
+//! repetitions, the others into a fresh `Vec` every time.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs::File,
+    io::IsTerminal,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use clap::Parser;
+use haversine::{numa, reference_haversine, HaversineData, PairBuffer, ParseOptions, EARTH_RADIUS};
+use memmap2::MmapOptions;
+use perf::{
+    prometheus::{Exporter, Metrics},
+    repetition::{RepetitionResults, RepetitionTester},
+};
+
+/// JSON Parser Shoot-out
+#[derive(Parser)]
+struct Arguments {
+    #[arg(name = "haversine_input.json")]
+    data_file: PathBuf,
+    /// Stop a parser once no new minimum was found for this many seconds
+    #[arg(long, default_value_t = 3)]
+    seconds: u64,
+    /// Bind threads and memory to this NUMA node and report its allocations
+    #[arg(long)]
+    numa_node: Option<u32>,
+    /// Write the results as Prometheus metrics to this file, updated after every test
+    #[arg(long)]
+    metrics: Option<PathBuf>,
+    /// Serve the results as Prometheus metrics on this localhost port
+    /// or address (e.g. `0.0.0.0:9100`), until interrupted once done
+    #[arg(long, alias = "metrics-port", value_parser = perf::prometheus::listen_addr)]
+    metrics_addr: Option<std::net::SocketAddr>,
+}
+
+/// Counts allocations so each parser's allocation behavior can be reported
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+type ParseFn = fn(&[u8]) -> HaversineData;
+
+fn parsers() -> Vec<(&'static str, ParseFn)> {
+    #[cfg_attr(
+        not(any(feature = "simd-json", feature = "simd-parse")),
+        allow(unused_mut)
+    )]
+    let mut parsers: Vec<(&'static str, ParseFn)> = vec![
+        ("nom", |bytes| {
+            HaversineData::parse_from_json_slice(bytes).expect("nom parser")
+        }),
+        ("serde_json", |bytes| {
+            serde_json::from_slice(bytes).expect("serde_json")
+        }),
+        ("scanner", |bytes| {
+            HaversineData::parse_from_json_slice_scanner(bytes).expect("scanner")
+        }),
+    ];
+    #[cfg(feature = "simd-json")]
+    // simd-json parses in place, the copy of the input is part of its time
+    parsers.push(("simd-json", |bytes| {
+        simd_json::serde::from_slice(&mut bytes.to_vec()).expect("simd-json")
+    }));
+    #[cfg(feature = "simd-parse")]
+    parsers.push(("simd-scan", |bytes| {
+        HaversineData::parse_from_json_slice_simd_scan(bytes).expect("simd-scan")
+    }));
+    parsers
+}
+
+/// Allocation count and bytes of `f`
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, u64, u64) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let value = f();
+    (
+        value,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated,
+    )
+}
+
+fn print_row(name: &str, results: &RepetitionResults, pair_count: f64, allocations: (u64, u64)) {
+    let (allocations, allocated) = allocations;
+    #[allow(clippy::cast_precision_loss)]
+    let (cycles, megabytes) = (
+        results.min_time as f64 / pair_count,
+        allocated as f64 / (1024.0 * 1024.0),
+    );
+    println!(
+        "{name:>10} {:>10.3} {cycles:>12.1} {allocations:>12} {megabytes:>14.2}",
+        results.best_bandwidth() / (1024.0 * 1024.0 * 1024.0)
+    );
+}
+
+fn main() {
+    let args = Arguments::parse();
+    let numa_start = args.numa_node.map(|node| {
+        numa::bind(node).expect("Unable to bind to NUMA node");
+        (
+            node,
+            numa::NodeStat::read(node).expect("Unable to read NUMA node stats"),
+        )
+    });
+    let exporter =
+        Exporter::new(args.metrics.clone(), args.metrics_addr).expect("Unable to export metrics");
+    let mut metrics = Metrics::new();
+    let file = File::open(&args.data_file).expect("Unable to open input file");
+    let mmap = unsafe {
+        MmapOptions::new()
+            .map(&file)
+            .expect("Unable to mmap input file")
+    };
+    let bytes: &[u8] = &mmap;
+    let reference = HaversineData::parse_from_json_slice(bytes).expect("Unable to parse input");
+    #[allow(clippy::cast_precision_loss)]
+    let pair_count = reference.pairs.len() as f64;
+    perf::begin_profile();
+
+    println!(
+        "{:>10} {:>10} {:>12} {:>12} {:>14}",
+        "parser", "best gb/s", "cycles/pair", "allocations", "allocated mb"
+    );
+    let tester = RepetitionTester::new(bytes.len() as u64)
+        .try_for_seconds(args.seconds)
+        .live(std::io::stderr().is_terminal());
+    for (name, parse) in parsers() {
+        let (data, allocations, allocated) = allocations(|| parse(bytes));
+        assert!(data == reference, "{name} disagrees with the nom parser");
+        drop(data);
+
+        let results = tester.run(|| parse(bytes));
+        print_row(name, &results, pair_count, (allocations, allocated));
+        metrics.add_repetition(name, &results);
+        exporter.update(&metrics).expect("Unable to write metrics");
+    }
+    let name = "nom-into";
+    let options = ParseOptions::default();
+    let mut buffer = PairBuffer::with_capacity(reference.pairs.len());
+    // the allocations of a repetition, once the buffer holds the pairs
+    let (pairs, allocations, allocated) =
+        allocations(|| buffer.parse(bytes, options).expect("nom parser").len());
+    assert_eq!(pairs, reference.pairs.len(), "{name} disagrees");
+
+    let results = tester.run(|| buffer.parse(bytes, options).expect("nom parser").len());
+    print_row(name, &results, pair_count, (allocations, allocated));
+    metrics.add_repetition(name, &results);
+    exporter.update(&metrics).expect("Unable to write metrics");
+
+    // The math alone, over the pairs every parser produced
+    let results = RepetitionTester::new(reference.pairs.len() as u64 * 32)
+        .try_for_seconds(args.seconds)
+        .live(std::io::stderr().is_terminal())
+        .run(|| {
+            reference
+                .pairs
+                .iter()
+                .map(|point| reference_haversine(point, EARTH_RADIUS))
+                .sum::<f64>()
+        });
+    #[allow(clippy::cast_precision_loss)]
+    let cycles = results.min_time as f64 / pair_count;
+    println!("reference_haversine: {cycles:.1} cycles/pair");
+    metrics.add_repetition("reference_haversine", &results);
+    exporter.update(&metrics).expect("Unable to write metrics");
+
+    if let Some((node, start)) = numa_start {
+        let end = numa::NodeStat::read(node).expect("Unable to read NUMA node stats");
+        println!("NUMA node {node}: {}", end.since(&start));
+    }
+    exporter.finish().expect("Unable to serve metrics");
+}
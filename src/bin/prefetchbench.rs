@@ -0,0 +1,95 @@
+//! Times the haversine kernel over structure of arrays data without and with `_mm_prefetch`
+//! at several distances ahead of the current pair.
+
+use clap::Parser;
+use haversine::{generator, numa, soa::HaversineSoA, EARTH_RADIUS};
+use perf::repetition::RepetitionTester;
+
+/// Prefetch Experiment
+#[derive(Parser)]
+struct Arguments {
+    /// Number of pairs, large enough to spill out of the caches by default
+    #[arg(long, default_value_t = 8_000_000)]
+    pairs: usize,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Prefetch distances in pairs, 0 disables prefetching
+    #[arg(long, value_delimiter = ',', default_values_t = [0, 8, 16, 32, 64, 128, 256, 512])]
+    distances: Vec<usize>,
+    /// Stop a distance once no new minimum was found for this many seconds
+    #[arg(long, default_value_t = 3)]
+    seconds: u64,
+    /// Bind threads and memory to this NUMA node and report its allocations
+    #[arg(long)]
+    numa_node: Option<u32>,
+}
+
+/// Pairs per 64 byte cache line of each coordinate array
+#[cfg(target_arch = "x86_64")]
+const LINE_PAIRS: usize = 8;
+
+#[cfg(target_arch = "x86_64")]
+fn sum_with_prefetch(soa: &HaversineSoA, radius: f64, distance: usize) -> f64 {
+    use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+    let mut sum = 0f64;
+    for start in (0..soa.len()).step_by(LINE_PAIRS) {
+        let ahead = start + distance;
+        if ahead < soa.len() {
+            for array in [&soa.x0, &soa.y0, &soa.x1, &soa.y1] {
+                unsafe { _mm_prefetch::<_MM_HINT_T0>(array.as_ptr().add(ahead).cast()) };
+            }
+        }
+        for i in start..(start + LINE_PAIRS).min(soa.len()) {
+            sum += haversine::reference_haversine(&soa.point(i), radius);
+        }
+    }
+    sum
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn sum_with_prefetch(soa: &HaversineSoA, radius: f64, _distance: usize) -> f64 {
+    soa.sum_distances(radius)
+}
+
+fn main() {
+    let args = Arguments::parse();
+    let numa_start = args.numa_node.map(|node| {
+        numa::bind(node).expect("Unable to bind to NUMA node");
+        (
+            node,
+            numa::NodeStat::read(node).expect("Unable to read NUMA node stats"),
+        )
+    });
+    let soa = HaversineSoA::from(&generator::uniform(args.pairs, args.seed));
+    let expected = soa.sum_distances(EARTH_RADIUS);
+    perf::begin_profile();
+
+    #[allow(clippy::cast_precision_loss)]
+    let pairs = soa.len() as f64;
+    let byte_count = (soa.len() * 4 * std::mem::size_of::<f64>()) as u64;
+    let tester = RepetitionTester::new(byte_count).try_for_seconds(args.seconds);
+    println!("{:>10} {:>12} {:>10}", "distance", "cycles/pair", "gb/s");
+    for distance in args.distances {
+        let results = if distance == 0 {
+            tester.run(|| soa.sum_distances(EARTH_RADIUS))
+        } else {
+            let sum = sum_with_prefetch(&soa, EARTH_RADIUS, distance);
+            assert!(
+                (sum - expected).abs() < 1e-6,
+                "prefetch kernel diverged at distance {distance}"
+            );
+            tester.run(|| sum_with_prefetch(&soa, EARTH_RADIUS, distance))
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let cycles_per_pair = results.min_time as f64 / pairs;
+        println!(
+            "{distance:>10} {cycles_per_pair:>12.2} {:>10.2}",
+            results.best_bandwidth() / (1024.0 * 1024.0 * 1024.0)
+        );
+    }
+    if let Some((node, start)) = numa_start {
+        let end = numa::NodeStat::read(node).expect("Unable to read NUMA node stats");
+        println!("NUMA node {node}: {}", end.since(&start));
+    }
+}
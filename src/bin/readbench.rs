@@ -0,0 +1,175 @@
+//! Upper bounds for consuming the input: times byte loops of increasing width
+//! over the mmap'd file with the repetition tester.
+
+use std::path::PathBuf;
+
+use clap::Parser;
+#[cfg(target_arch = "x86_64")]
+use {
+    haversine::numa,
+    memmap2::MmapOptions,
+    perf::{
+        prometheus::{Exporter, Metrics},
+        repetition::RepetitionTester,
+    },
+    std::{fs::File, hint::black_box},
+};
+
+/// Read-loop Variant Benchmark
+#[derive(Parser)]
+struct Arguments {
+    #[arg(name = "haversine_input.json")]
+    data_file: PathBuf,
+    /// Stop a variant once no new minimum was found for this many seconds
+    #[arg(long, default_value_t = 3)]
+    seconds: u64,
+    /// Bind threads and memory to this NUMA node and report its allocations
+    #[arg(long)]
+    numa_node: Option<u32>,
+    /// Write the results as Prometheus metrics to this file, updated after every test
+    #[arg(long)]
+    metrics: Option<PathBuf>,
+    /// Serve the results as Prometheus metrics on this localhost port
+    /// or address (e.g. `0.0.0.0:9100`), until interrupted once done
+    #[arg(long, alias = "metrics-port", value_parser = perf::prometheus::listen_addr)]
+    metrics_addr: Option<std::net::SocketAddr>,
+}
+
+#[cfg(target_arch = "x86_64")]
+mod loops {
+    use std::arch::{
+        asm,
+        x86_64::{
+            _mm256_extract_epi32, _mm256_loadu_si256, _mm256_setzero_si256, _mm256_xor_si256,
+            _mm_cvtsi128_si32, _mm_loadu_si128, _mm_setzero_si128, _mm_xor_si128,
+        },
+    };
+
+    pub fn scalar(bytes: &[u8]) -> u8 {
+        let mut acc = 0u8;
+        for b in bytes {
+            acc ^= unsafe { std::ptr::read_volatile(b) };
+        }
+        acc
+    }
+
+    pub fn unrolled_2x(bytes: &[u8]) -> u8 {
+        let mut acc = [0u8; 2];
+        for pair in bytes.chunks_exact(2) {
+            unsafe {
+                acc[0] ^= std::ptr::read_volatile(pair.as_ptr());
+                acc[1] ^= std::ptr::read_volatile(pair.as_ptr().add(1));
+            }
+        }
+        acc[0] ^ acc[1]
+    }
+
+    pub fn unrolled_4x(bytes: &[u8]) -> u8 {
+        let mut acc = [0u8; 4];
+        for quad in bytes.chunks_exact(4) {
+            unsafe {
+                acc[0] ^= std::ptr::read_volatile(quad.as_ptr());
+                acc[1] ^= std::ptr::read_volatile(quad.as_ptr().add(1));
+                acc[2] ^= std::ptr::read_volatile(quad.as_ptr().add(2));
+                acc[3] ^= std::ptr::read_volatile(quad.as_ptr().add(3));
+            }
+        }
+        acc.iter().fold(0, |x, a| x ^ a)
+    }
+
+    pub fn sse(bytes: &[u8]) -> i32 {
+        unsafe {
+            let mut acc = _mm_setzero_si128();
+            for chunk in bytes.chunks_exact(16) {
+                acc = _mm_xor_si128(acc, _mm_loadu_si128(chunk.as_ptr().cast()));
+            }
+            _mm_cvtsi128_si32(acc)
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support AVX2.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn avx2(bytes: &[u8]) -> i32 {
+        let mut acc = _mm256_setzero_si256();
+        for chunk in bytes.chunks_exact(32) {
+            acc = _mm256_xor_si256(acc, _mm256_loadu_si256(chunk.as_ptr().cast()));
+        }
+        _mm256_extract_epi32::<0>(acc)
+    }
+
+    pub fn rep_movsb(bytes: &[u8], destination: &mut [u8]) {
+        assert!(destination.len() >= bytes.len());
+        unsafe {
+            asm!(
+                "rep movsb",
+                inout("rcx") bytes.len() => _,
+                inout("rsi") bytes.as_ptr() => _,
+                inout("rdi") destination.as_mut_ptr() => _,
+                options(nostack, preserves_flags)
+            );
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn main() {
+    let args = Arguments::parse();
+    let numa_start = args.numa_node.map(|node| {
+        numa::bind(node).expect("Unable to bind to NUMA node");
+        (
+            node,
+            numa::NodeStat::read(node).expect("Unable to read NUMA node stats"),
+        )
+    });
+    let exporter =
+        Exporter::new(args.metrics.clone(), args.metrics_addr).expect("Unable to export metrics");
+    let mut metrics = Metrics::new();
+    let file = File::open(&args.data_file).expect("Unable to open input file");
+    let mmap = unsafe {
+        MmapOptions::new()
+            .map(&file)
+            .expect("Unable to mmap input file")
+    };
+    let bytes: &[u8] = &mmap;
+    let mut destination = vec![0u8; bytes.len()];
+    perf::begin_profile();
+
+    let tester = RepetitionTester::new(bytes.len() as u64).try_for_seconds(args.seconds);
+    let mut report = |name: &str, results: perf::repetition::RepetitionResults| {
+        const GIGABYTE: f64 = 1024.0 * 1024.0 * 1024.0;
+        println!(
+            "{name:>10} {:>10.2} {:>10.2}",
+            results.best_bandwidth() / GIGABYTE,
+            results.avg_bandwidth() / GIGABYTE
+        );
+        metrics.add_repetition(name, &results);
+        exporter.update(&metrics).expect("Unable to write metrics");
+    };
+
+    println!("{:>10} {:>10} {:>10}", "loop", "best gb/s", "avg gb/s");
+    report("scalar", tester.run(|| loops::scalar(bytes)));
+    report("2x", tester.run(|| loops::unrolled_2x(bytes)));
+    report("4x", tester.run(|| loops::unrolled_4x(bytes)));
+    report("sse", tester.run(|| loops::sse(bytes)));
+    if is_x86_feature_detected!("avx2") {
+        report("avx2", tester.run(|| unsafe { loops::avx2(bytes) }));
+    }
+    report(
+        "rep movsb",
+        tester.run(|| loops::rep_movsb(bytes, black_box(&mut destination))),
+    );
+    if let Some((node, start)) = numa_start {
+        let end = numa::NodeStat::read(node).expect("Unable to read NUMA node stats");
+        println!("NUMA node {node}: {}", end.since(&start));
+    }
+    exporter.finish().expect("Unable to serve metrics");
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn main() {
+    let _ = Arguments::parse();
+    eprintln!("readbench is only implemented for x86_64");
+    std::process::exit(1);
+}
@@ -0,0 +1,125 @@
+//! Progress of a checkpointed pipeline run, persisted so an interrupted run
+//! can resume where it stopped instead of starting over.
+
+use std::{io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::EarthModel;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Checkpoint {
+    /// size of the input the checkpoint was taken on, checked when resuming
+    pub input_size: u64,
+    pub model: EarthModel,
+    /// offset just past the last processed pair
+    pub bytes_consumed: u64,
+    pub pairs_processed: u64,
+    pub sum: f64,
+    /// low order bits lost by `sum` (Neumaier summation)
+    pub compensation: f64,
+}
+
+impl Checkpoint {
+    #[must_use]
+    pub fn new(input_size: u64, model: EarthModel) -> Self {
+        Self {
+            input_size,
+            model,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a distance with compensated summation, so that a sum resumed from
+    /// a checkpoint is as accurate as an uninterrupted one
+    pub fn add(&mut self, distance: f64) {
+        let sum = self.sum + distance;
+        self.compensation += if self.sum.abs() >= distance.abs() {
+            (self.sum - sum) + distance
+        } else {
+            (distance - sum) + self.sum
+        };
+        self.sum = sum;
+    }
+
+    #[must_use]
+    pub fn total(&self) -> f64 {
+        self.sum + self.compensation
+    }
+
+    /// `None` if there is no checkpoint at `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not a checkpoint.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(Into::into),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes through a temporary file in the same directory, so a crash
+    /// while saving leaves the previous checkpoint intact
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the write or of the rename.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        std::fs::write(&temporary, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(&temporary, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::VALID,
+        pipeline::{Pipeline, PipelineError, PipelineOptions},
+        scanner::PairStream,
+        EarthModel,
+    };
+
+    #[test]
+    fn resumed_run_matches_uninterrupted_run() {
+        let dir = std::env::temp_dir().join(format!("haversine-checkpoint-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, answers) = VALID.write_to(&dir).unwrap();
+        let checkpoint = dir.join("run.checkpoint");
+        let pipeline = Pipeline::new(PipelineOptions {
+            answers: Some(answers),
+            checkpoint: Some(checkpoint.clone()),
+            resume: true,
+            ..PipelineOptions::default()
+        });
+        let uninterrupted = pipeline.run(&input).unwrap();
+
+        // What a run interrupted after three pairs leaves behind
+        let mut state = Checkpoint::new(VALID.json.len() as u64, EarthModel::SPHERE_DEFAULT);
+        let mut pairs = PairStream::new(VALID.json).unwrap();
+        for point in pairs.by_ref().take(3) {
+            state.add(EarthModel::SPHERE_DEFAULT.distance(&point.unwrap()));
+            state.pairs_processed += 1;
+        }
+        state.bytes_consumed = pairs.offset() as u64;
+        state.save(&checkpoint).unwrap();
+        assert_eq!(Checkpoint::load(&checkpoint).unwrap(), Some(state));
+
+        let resumed = pipeline.run(&input).unwrap();
+        assert_eq!(resumed, uninterrupted);
+        assert!(!checkpoint.exists());
+
+        Checkpoint::new(1, EarthModel::SPHERE_DEFAULT)
+            .save(&checkpoint)
+            .unwrap();
+        assert!(matches!(
+            pipeline.run(&input),
+            Err(PipelineError::Checkpoint { .. })
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
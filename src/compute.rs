@@ -0,0 +1,312 @@
+//! The distance loop of the scalar backend and its validation against an
+//! answers file, for callers that already hold the pairs. `Pipeline` runs
+//! the same code after reading and parsing a dataset.
+
+use std::fmt;
+
+use crate::{EarthModel, HaversineDataPoint};
+
+/// Distances of a set of pairs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DistanceSummary {
+    pub pair_count: usize,
+    pub sum: f64,
+    /// `sum / pair_count`, NaN without pairs
+    pub average: f64,
+}
+
+impl DistanceSummary {
+    #[must_use]
+    pub fn new(pair_count: usize, sum: f64) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        let average = sum / pair_count as f64;
+        Self {
+            pair_count,
+            sum,
+            average,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValidationReport {
+    pub reference_average: f64,
+    /// `reference_average - average`
+    pub difference: f64,
+}
+
+impl ValidationReport {
+    /// Compares the average of `summary` to the one that follows its pairs'
+    /// distances in `answers`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ValidationError::AnswersExhausted`] if `answers` ends before
+    /// the average.
+    pub fn new(answers: &[f64], summary: &DistanceSummary) -> Result<Self, ValidationError> {
+        let reference_average = *answers
+            .get(summary.pair_count)
+            .ok_or(ValidationError::AnswersExhausted)?;
+        Ok(Self {
+            reference_average,
+            difference: reference_average - summary.average,
+        })
+    }
+}
+
+/// Result of `validate_against`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Validation {
+    pub summary: DistanceSummary,
+    pub report: ValidationReport,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValidationError {
+    /// the answers have fewer entries than pairs + average
+    AnswersExhausted,
+    /// the distance of the pair at `index` is off by more than the tolerance
+    Mismatch {
+        index: usize,
+        point: HaversineDataPoint,
+        computed: f64,
+        expected: f64,
+    },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AnswersExhausted => write!(f, "validation input exhausted"),
+            Self::Mismatch {
+                point,
+                computed,
+                expected,
+                ..
+            } => write!(
+                f,
+                "Failed validation for {point:?}. Got {computed} Expected {expected} Diff {}",
+                (computed - expected).abs()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// A distance off by more than the tolerance from its answer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mismatch {
+    pub index: usize,
+    pub point: HaversineDataPoint,
+    pub computed: f64,
+    pub expected: f64,
+}
+
+impl Mismatch {
+    /// `|computed - expected|`
+    #[must_use]
+    pub fn difference(&self) -> f64 {
+        (self.computed - self.expected).abs()
+    }
+}
+
+impl From<Mismatch> for ValidationError {
+    fn from(mismatch: Mismatch) -> Self {
+        let Mismatch {
+            index,
+            point,
+            computed,
+            expected,
+        } = mismatch;
+        Self::Mismatch {
+            index,
+            point,
+            computed,
+            expected,
+        }
+    }
+}
+
+/// What a validating loop does with a mismatch: fail on it, or record it and
+/// carry on when `collect`ing
+pub(crate) struct Mismatches {
+    pub(crate) collect: bool,
+    pub(crate) found: Vec<Mismatch>,
+}
+
+impl Mismatches {
+    pub(crate) fn new(collect: bool) -> Self {
+        Self {
+            collect,
+            found: Vec::new(),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns the mismatch unless collecting.
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn report(
+        &mut self,
+        index: usize,
+        point: &HaversineDataPoint,
+        computed: f64,
+        expected: f64,
+    ) -> Result<(), ValidationError> {
+        let mismatch = Mismatch {
+            index,
+            point: *point,
+            computed,
+            expected,
+        };
+        if !self.collect {
+            return Err(mismatch.into());
+        }
+        self.found.push(mismatch);
+        Ok(())
+    }
+}
+
+/// Sum of the distances of `pairs` on `model`
+#[must_use]
+#[perf::instrument]
+pub fn sum_distances(pairs: &[HaversineDataPoint], model: EarthModel) -> f64 {
+    let mut sum = 0f64;
+    #[perf::instrument_loop("calculate distance")]
+    for point in pairs {
+        sum += model.distance(point);
+    }
+    sum
+}
+
+#[must_use]
+pub fn average_distance(pairs: &[HaversineDataPoint], model: EarthModel) -> DistanceSummary {
+    DistanceSummary::new(pairs.len(), sum_distances(pairs, model))
+}
+
+/// Sum of the distances of `pairs`, each checked against the answer at its
+/// index and passed to `mismatches` if off by more than `tolerance`
+///
+/// # Errors
+///
+/// Returns the error of `mismatches`, or [`ValidationError::AnswersExhausted`]
+/// if there are fewer answers than pairs.
+#[perf::instrument]
+pub(crate) fn sum_validated(
+    pairs: &[HaversineDataPoint],
+    model: EarthModel,
+    answers: &[f64],
+    tolerance: f64,
+    mismatches: &mut Mismatches,
+) -> Result<f64, ValidationError> {
+    // Validation is decided once instead of per pair and mismatches are on a
+    // cold path, which keeps the hot loop branch free (see `branchbench`).
+    if answers.len() < pairs.len() {
+        return Err(ValidationError::AnswersExhausted);
+    }
+    let mut sum = 0f64;
+    #[perf::instrument_loop("calculate distance")]
+    for (index, (point, &expected)) in pairs.iter().zip(answers).enumerate() {
+        let dist = model.distance(point);
+        sum += dist;
+        if (dist - expected).abs() > tolerance {
+            mismatches.report(index, point, dist, expected)?;
+        }
+    }
+    Ok(sum)
+}
+
+/// Computes the distances of `pairs` and checks them and their average
+/// against `answers`, as `validation::AnswerFile::values` holds them. See
+/// `AnswerFile::validate` for every mismatch rather than the first.
+///
+/// # Errors
+///
+/// Returns the first distance off by more than `tolerance`, or
+/// [`ValidationError::AnswersExhausted`] if `answers` ends before the average.
+pub fn validate_against(
+    pairs: &[HaversineDataPoint],
+    model: EarthModel,
+    answers: &[f64],
+    tolerance: f64,
+) -> Result<Validation, ValidationError> {
+    let sum = sum_validated(
+        pairs,
+        model,
+        answers,
+        tolerance,
+        &mut Mismatches::new(false),
+    )?;
+    let summary = DistanceSummary::new(pairs.len(), sum);
+    Ok(Validation {
+        summary,
+        report: ValidationReport::new(answers, &summary)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{reference_haversine, Coord, EARTH_RADIUS};
+
+    #[test]
+    fn validates_distances_and_their_average() {
+        let pairs = [
+            HaversineDataPoint {
+                p0: Coord { lon: 0.0, lat: 0.0 },
+                p1: Coord {
+                    lon: 90.0,
+                    lat: 0.0,
+                },
+            },
+            HaversineDataPoint {
+                p0: Coord {
+                    lon: 10.0,
+                    lat: 20.0,
+                },
+                p1: Coord {
+                    lon: -30.0,
+                    lat: 40.0,
+                },
+            },
+        ];
+        let distances: Vec<f64> = pairs
+            .iter()
+            .map(|point| reference_haversine(point, EARTH_RADIUS))
+            .collect();
+        let summary = average_distance(&pairs, EarthModel::SPHERE_DEFAULT);
+        assert_eq!(summary.pair_count, 2);
+        assert_eq!(
+            summary.sum.to_bits(),
+            (distances[0] + distances[1]).to_bits()
+        );
+        assert_eq!(summary.average.to_bits(), (summary.sum / 2.0).to_bits());
+        assert!(average_distance(&[], EarthModel::SPHERE_DEFAULT)
+            .average
+            .is_nan());
+
+        let mut answers = distances.clone();
+        answers.push(summary.average);
+        let validation =
+            validate_against(&pairs, EarthModel::SPHERE_DEFAULT, &answers, 0.0).unwrap();
+        assert_eq!(validation.summary, summary);
+        assert_eq!(validation.report.difference.to_bits(), 0f64.to_bits());
+
+        assert_eq!(
+            validate_against(&pairs, EarthModel::SPHERE_DEFAULT, &distances, 0.0),
+            Err(ValidationError::AnswersExhausted)
+        );
+        answers[1] += 1.0;
+        assert_eq!(
+            validate_against(&pairs, EarthModel::SPHERE_DEFAULT, &answers, 0.5),
+            Err(ValidationError::Mismatch {
+                index: 1,
+                point: pairs[1],
+                computed: distances[1],
+                expected: distances[1] + 1.0,
+            })
+        );
+    }
+}
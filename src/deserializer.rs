@@ -1,4 +1,4 @@
-use crate::{HaversineData, HaversineDataPoint};
+use crate::{Coord, HaversineData, HaversineDataPoint};
 
 /// Sample `HaversineData` JSON:
 /// {
@@ -17,141 +17,685 @@ use crate::{HaversineData, HaversineDataPoint};
 ///    }
 ///  ]
 ///}
+use std::fmt;
+
 use nom::{
-    bytes::complete::take_while1,
+    branch::alt,
+    bytes::complete::{escaped, tag, tag_no_case, take_while1},
     character::{
-        complete::{char, multispace0},
+        complete::{char, multispace0, one_of},
         is_alphanumeric,
     },
-    combinator::opt,
-    error::ParseError,
+    combinator::{cut, opt},
+    error::ErrorKind,
     multi::many0,
-    number::complete::double,
+    number::complete::recognize_float,
     sequence::{delimited, preceded, terminated, tuple},
-    IResult, Parser,
+    Parser,
 };
 
-fn ws<'a, F, O, E: ParseError<&'a [u8]>>(inner: F) -> impl Parser<&'a [u8], O, E>
+/// Which numbers and keys `HaversineData::parse_from_json_slice_with` accepts
+/// besides JSON's. The default rejects NaN and infinities but, like
+/// `parse_from_json_slice`, reads `+1`, `.5` and `5.`, and rejects datapoints
+/// with a repeated or unknown key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// accept `NaN` in any case, which JSON cannot express
+    pub allow_nan: bool,
+    /// accept `inf` and `Infinity` in any case, optionally signed
+    pub allow_inf: bool,
+    /// only accept the JSON number grammar: no leading `+` or zeros, and
+    /// digits on both sides of a `.`
+    pub strict_numbers: bool,
+    /// what a datapoint that repeats one of `x0`, `y0`, `x1` and `y1` is
+    pub duplicate_keys: DuplicateKeyPolicy,
+    /// what a datapoint with keys besides `x0`, `y0`, `x1` and `y1` is
+    pub unknown_keys: UnknownKeyPolicy,
+}
+
+/// How a datapoint that repeats a coordinate key is parsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    /// `ParseError::DuplicateKey` at the repeated key
+    #[default]
+    Error,
+    /// the first value of the key is kept
+    FirstWins,
+    /// the last value of the key is kept, as by `serde_json`
+    LastWins,
+}
+
+/// How a datapoint with a key other than the coordinates is parsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownKeyPolicy {
+    /// `ParseError::UnknownKey` at the key
+    #[default]
+    Error,
+    /// the key and its value, which may be any JSON value, are skipped
+    Ignore,
+}
+
+/// What the parser was looking for where the input stopped matching
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Token {
+    Char(char),
+    /// a quoted alphanumeric key
+    Key,
+    Number,
+    /// any JSON value, of an ignored key
+    Value,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Char(c) => write!(f, "`{c}`"),
+            Self::Key => write!(f, "a key"),
+            Self::Number => write!(f, "a number"),
+            Self::Value => write!(f, "a value"),
+        }
+    }
+}
+
+/// Why `HaversineData::parse_from_json_slice` rejected its input; positions
+/// are byte offsets into it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnexpectedEnd {
+        position: usize,
+        expected: Token,
+    },
+    Unexpected {
+        position: usize,
+        expected: Token,
+        found: u8,
+    },
+    /// the datapoint starting at `position` lacks one of `x0`, `y0`, `x1`
+    /// and `y1`
+    InvalidDatapoint {
+        position: usize,
+    },
+    /// the key at `position` repeats a coordinate of its datapoint, see
+    /// `DuplicateKeyPolicy`
+    DuplicateKey {
+        position: usize,
+    },
+    /// the key at `position` isn't a coordinate, see `UnknownKeyPolicy`
+    UnknownKey {
+        position: usize,
+    },
+}
+
+impl ParseError {
+    #[must_use]
+    pub fn position(&self) -> usize {
+        match *self {
+            Self::UnexpectedEnd { position, .. }
+            | Self::Unexpected { position, .. }
+            | Self::InvalidDatapoint { position }
+            | Self::DuplicateKey { position }
+            | Self::UnknownKey { position } => position,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd { position, expected } => {
+                write!(
+                    f,
+                    "expected {expected} at byte {position}, found end of input"
+                )
+            }
+            Self::Unexpected {
+                position,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {expected} at byte {position}, found `{}`",
+                found.escape_ascii()
+            ),
+            Self::InvalidDatapoint { position } => write!(
+                f,
+                "datapoint at byte {position} needs each of x0, y0, x1 and y1"
+            ),
+            Self::DuplicateKey { position } => {
+                write!(f, "key at byte {position} repeats a coordinate")
+            }
+            Self::UnknownKey { position } => {
+                write!(f, "key at byte {position} isn't one of x0, y0, x1 and y1")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorCause {
+    Expected(Token),
+    InvalidDatapoint,
+    DuplicateKey,
+    UnknownKey,
+}
+
+/// nom error that remembers the token it failed on, so that it can be turned
+/// into a `ParseError` against the whole input
+#[derive(Debug, PartialEq)]
+pub(crate) struct JsonError<'a> {
+    input: &'a [u8],
+    cause: ErrorCause,
+}
+
+impl JsonError<'_> {
+    fn at(self, bytes: &[u8]) -> ParseError {
+        let position = bytes.len() - self.input.len();
+        match (self.cause, self.input.first()) {
+            (ErrorCause::InvalidDatapoint, _) => ParseError::InvalidDatapoint { position },
+            (ErrorCause::DuplicateKey, _) => ParseError::DuplicateKey { position },
+            (ErrorCause::UnknownKey, _) => ParseError::UnknownKey { position },
+            (ErrorCause::Expected(expected), None) => {
+                ParseError::UnexpectedEnd { position, expected }
+            }
+            (ErrorCause::Expected(expected), Some(&found)) => ParseError::Unexpected {
+                position,
+                expected,
+                found,
+            },
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a [u8]> for JsonError<'a> {
+    fn from_error_kind(input: &'a [u8], kind: ErrorKind) -> Self {
+        let expected = match kind {
+            ErrorKind::TakeWhile1 => Token::Key,
+            _ => Token::Number,
+        };
+        Self {
+            input,
+            cause: ErrorCause::Expected(expected),
+        }
+    }
+
+    fn append(_: &'a [u8], _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn from_char(input: &'a [u8], c: char) -> Self {
+        Self {
+            input,
+            cause: ErrorCause::Expected(Token::Char(c)),
+        }
+    }
+}
+
+type IResult<'a, O> = nom::IResult<&'a [u8], O, JsonError<'a>>;
+
+/// Converts the error of a parser run over `bytes`
+fn parse_error(bytes: &[u8], err: nom::Err<JsonError<'_>>) -> ParseError {
+    match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e.at(bytes),
+        // only the streaming parsers report `Incomplete`
+        nom::Err::Incomplete(_) => unreachable!(),
+    }
+}
+
+fn ws<'a, F, O>(inner: F) -> impl Parser<&'a [u8], O, JsonError<'a>>
 where
-    F: Parser<&'a [u8], O, E>,
+    F: Parser<&'a [u8], O, JsonError<'a>>,
 {
     delimited(multispace0, inner, multispace0)
 }
 
-fn eat_char(c: char) -> impl Fn(&[u8]) -> IResult<&[u8], char> {
+fn eat_char<'a>(c: char) -> impl Fn(&'a [u8]) -> IResult<'a, char> {
     move |i: &[u8]| ws(char(c)).parse(i)
 }
 
-fn key(i: &[u8]) -> IResult<&[u8], String> {
-    let (rem, key) = ws(terminated(
+/// `"key":`, borrowed from the input so that parsing a pair doesn't allocate
+fn key(i: &[u8]) -> IResult<'_, &[u8]> {
+    ws(terminated(
         delimited(char('"'), take_while1(is_alphanumeric), char('"')),
         ws(eat_char(':')),
     ))
-    .parse(i)?;
-    Ok((rem, unsafe { String::from_utf8_unchecked(key.to_vec()) }))
+    .parse(i)
 }
 
-fn coordinate() -> impl Fn(&[u8]) -> IResult<&[u8], (String, f64)> {
+/// `-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?`
+fn is_json_number(text: &[u8]) -> bool {
+    let digits = |text: &[u8]| text.iter().take_while(|c| c.is_ascii_digit()).count();
+    let text = text.strip_prefix(b"-").unwrap_or(text);
+    let int = digits(text);
+    if int == 0 || (int > 1 && text[0] == b'0') {
+        return false;
+    }
+    let mut rest = &text[int..];
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let count = digits(fraction);
+        if count == 0 {
+            return false;
+        }
+        rest = &fraction[count..];
+    }
+    if let Some(exponent) = rest.strip_prefix(b"e").or_else(|| rest.strip_prefix(b"E")) {
+        let exponent = exponent
+            .strip_prefix(b"+")
+            .or_else(|| exponent.strip_prefix(b"-"))
+            .unwrap_or(exponent);
+        let count = digits(exponent);
+        return count > 0 && count == exponent.len();
+    }
+    rest.is_empty()
+}
+
+fn number<'a>(options: ParseOptions) -> impl Fn(&'a [u8]) -> IResult<'a, f64> {
     move |i: &[u8]| {
-        let (rem, (key, val)) = tuple((key, double)).parse(i)?;
-        Ok((rem, (key, val)))
-    }
-}
-
-fn haversine_datapoint(i: &[u8]) -> IResult<&[u8], HaversineDataPoint> {
-    let (input, (_, c1, _, c2, _, c3, _, c4, _)) = tuple((
-        eat_char('{'),
-        coordinate(),
-        eat_char(','),
-        coordinate(),
-        eat_char(','),
-        coordinate(),
-        eat_char(','),
-        coordinate(),
-        eat_char('}'),
-    ))
-    .parse(i)?;
-
-    let mut x0 = None;
-    let mut y0 = None;
-    let mut x1 = None;
-    let mut y1 = None;
-
-    for (key, val) in [c1, c2, c3, c4] {
-        match key.as_str() {
-            "x0" => x0 = Some(val),
-            "x1" => x1 = Some(val),
-            "y0" => y0 = Some(val),
-            "y1" => y1 = Some(val),
-            _ => (),
+        let rejected = || {
+            nom::Err::Error(JsonError {
+                input: i,
+                cause: ErrorCause::Expected(Token::Number),
+            })
+        };
+        let (rest, sign) = opt(one_of("+-")).parse(i)?;
+        if options.strict_numbers && sign == Some('+') {
+            return Err(rejected());
+        }
+        let negative = sign == Some('-');
+        if let Ok((rest, _)) = alt((
+            tag_no_case::<_, _, JsonError>("infinity"),
+            tag_no_case("inf"),
+        ))
+        .parse(rest)
+        {
+            if !options.allow_inf {
+                return Err(rejected());
+            }
+            let value = if negative {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            };
+            return Ok((rest, value));
         }
+        if let Ok((rest, _)) = tag_no_case::<_, _, JsonError>("nan").parse(rest) {
+            if !options.allow_nan {
+                return Err(rejected());
+            }
+            return Ok((rest, f64::NAN));
+        }
+        let (rest, text) = recognize_float(i).map_err(|_: nom::Err<JsonError>| rejected())?;
+        if options.strict_numbers && !is_json_number(text) {
+            return Err(rejected());
+        }
+        // `recognize_float` only matches ASCII that `f64::from_str` reads
+        let value = unsafe { std::str::from_utf8_unchecked(text) }
+            .parse()
+            .map_err(|_| rejected())?;
+        Ok((rest, value))
     }
+}
 
-    let validation_error = Err(nom::Err::Failure(nom::error::Error::new(
-        input,
-        nom::error::ErrorKind::Fail,
-    )));
+pub(crate) fn haversine_datapoint(i: &[u8]) -> IResult<'_, HaversineDataPoint> {
+    datapoint(ParseOptions::default())(i)
+}
 
-    if x0.is_none() || x1.is_none() || y0.is_none() || y1.is_none() {
-        return validation_error;
+fn datapoint<'a>(options: ParseOptions) -> impl Fn(&'a [u8]) -> IResult<'a, HaversineDataPoint> {
+    move |i: &[u8]| {
+        let (mut input, _) = eat_char('{')(i)?;
+        // x0, y0, x1 and y1
+        let mut coordinates = [None; 4];
+        // past the `{` the input can only be a datapoint, so errors point into it
+        loop {
+            let key_start = multispace0::<_, JsonError>(input).map_or(input, |(start, _)| start);
+            let failure = |cause| {
+                nom::Err::Failure(JsonError {
+                    input: key_start,
+                    cause,
+                })
+            };
+            let (rest, name) = cut(key)(input)?;
+            let slot = match name {
+                b"x0" => Some(0),
+                b"y0" => Some(1),
+                b"x1" => Some(2),
+                b"y1" => Some(3),
+                _ => None,
+            };
+            input = match slot {
+                Some(slot) => {
+                    let (rest, value) = cut(number(options))(rest)?;
+                    let coordinate = &mut coordinates[slot];
+                    match (coordinate.is_some(), options.duplicate_keys) {
+                        (false, _) | (true, DuplicateKeyPolicy::LastWins) => {
+                            *coordinate = Some(value);
+                        }
+                        (true, DuplicateKeyPolicy::FirstWins) => (),
+                        (true, DuplicateKeyPolicy::Error) => {
+                            return Err(failure(ErrorCause::DuplicateKey))
+                        }
+                    }
+                    rest
+                }
+                None => match options.unknown_keys {
+                    UnknownKeyPolicy::Error => return Err(failure(ErrorCause::UnknownKey)),
+                    UnknownKeyPolicy::Ignore => {
+                        cut(|rest| ignored_value(rest, options, 0))(rest)?.0
+                    }
+                },
+            };
+            if let Ok((rest, _)) = eat_char('}')(input) {
+                return fields(i, rest, coordinates);
+            }
+            input = cut(eat_char(','))(input)?.0;
+        }
     }
+}
+
+/// Nesting of arrays and objects that an ignored value may have, past which
+/// it's rejected rather than overflowing the stack
+const MAX_IGNORED_DEPTH: usize = 128;
 
+/// A JSON string, escapes left as they are
+fn string(i: &[u8]) -> IResult<'_, &[u8]> {
+    delimited(
+        char('"'),
+        escaped(
+            take_while1(|c| c != b'"' && c != b'\\'),
+            '\\',
+            one_of("\"\\/bfnrtu"),
+        ),
+        char('"'),
+    )
+    .parse(i)
+}
+
+/// Skips the value of an ignored key, which unlike a coordinate may be any
+/// JSON value, `depth` arrays and objects deep
+fn ignored_value(i: &[u8], options: ParseOptions, depth: usize) -> IResult<'_, ()> {
+    let (i, _) = multispace0(i)?;
+    let unexpected = || {
+        nom::Err::Error(JsonError {
+            input: i,
+            cause: ErrorCause::Expected(Token::Value),
+        })
+    };
+    let (rest, ()) = match i.first() {
+        Some(b'"') => (string(i)?.0, ()),
+        Some(b'[' | b'{') if depth == MAX_IGNORED_DEPTH => return Err(unexpected()),
+        Some(&open @ (b'[' | b'{')) => {
+            let close = if open == b'[' { ']' } else { '}' };
+            let mut rest = &i[1..];
+            if let Ok((after, _)) = eat_char(close)(rest) {
+                (after, ())
+            } else {
+                loop {
+                    if open == b'{' {
+                        rest = tuple((ws(string), eat_char(':'))).parse(rest)?.0;
+                    }
+                    rest = ignored_value(rest, options, depth + 1)?.0;
+                    if let Ok((after, _)) = eat_char(close)(rest) {
+                        break (after, ());
+                    }
+                    rest = eat_char(',')(rest)?.0;
+                }
+            }
+        }
+        _ => alt((tag("true"), tag("false"), tag("null")))
+            .map(|_| ())
+            .parse(i)
+            .or_else(|_: nom::Err<JsonError>| number(options)(i).map(|(rest, _)| (rest, ())))
+            .map_err(|_| unexpected())?,
+    };
+    multispace0(rest).map(|(rest, _)| (rest, ()))
+}
+
+/// The datapoint of the `coordinates` x0, y0, x1 and y1 parsed from `i`,
+/// ending at `input`
+fn fields<'a>(
+    i: &'a [u8],
+    input: &'a [u8],
+    coordinates: [Option<f64>; 4],
+) -> IResult<'a, HaversineDataPoint> {
+    let [Some(x0), Some(y0), Some(x1), Some(y1)] = coordinates else {
+        let start = multispace0::<_, JsonError>(i).map_or(i, |(start, _)| start);
+        return Err(nom::Err::Failure(JsonError {
+            input: start,
+            cause: ErrorCause::InvalidDatapoint,
+        }));
+    };
     Ok((
         input,
         HaversineDataPoint {
-            x0: unsafe { x0.unwrap_unchecked() },
-            x1: unsafe { x1.unwrap_unchecked() },
-            y0: unsafe { y0.unwrap_unchecked() },
-            y1: unsafe { y1.unwrap_unchecked() },
+            p0: Coord { lon: x0, lat: y0 },
+            p1: Coord { lon: x1, lat: y1 },
         },
     ))
 }
 
-fn haversine_datapoint_array(i: &[u8]) -> IResult<&[u8], Vec<HaversineDataPoint>> {
-    let (rem, (_, mut any, last, _)) = tuple((
-        eat_char('['),
-        many0(terminated(haversine_datapoint, eat_char(','))),
-        opt(haversine_datapoint),
-        eat_char(']'),
-    ))
-    .parse(i)?;
-    if last.is_some() {
-        unsafe {
-            any.push(last.unwrap_unchecked());
+fn haversine_datapoint_array(
+    options: ParseOptions,
+) -> impl Fn(&[u8]) -> IResult<'_, Vec<HaversineDataPoint>> {
+    move |i: &[u8]| {
+        let (rem, (_, mut any, last, _)) = tuple((
+            eat_char('['),
+            many0(terminated(datapoint(options), eat_char(','))),
+            opt(datapoint(options)),
+            eat_char(']'),
+        ))
+        .parse(i)?;
+        if last.is_some() {
+            unsafe {
+                any.push(last.unwrap_unchecked());
+            }
         }
+        Ok((rem, any))
     }
-    Ok((rem, any))
 }
 
-fn haversine_data(i: &[u8]) -> IResult<&[u8], HaversineData> {
-    let (rem, pairs) = delimited(
-        eat_char('{'),
-        preceded(key, haversine_datapoint_array),
-        eat_char('}'),
-    )
-    .parse(i)?;
-    Ok((rem, HaversineData { pairs }))
+fn haversine_data(options: ParseOptions) -> impl Fn(&[u8]) -> IResult<'_, HaversineData> {
+    move |i: &[u8]| {
+        let (rem, pairs) = delimited(
+            eat_char('{'),
+            preceded(key, haversine_datapoint_array(options)),
+            eat_char('}'),
+        )
+        .parse(i)?;
+        Ok((rem, HaversineData { pairs }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Expect {
+    /// a datapoint or the `]` closing the array
+    Element,
+    /// the `,` after a datapoint or the `]` closing the array
+    Separator,
+    Done,
+}
+
+/// The pairs of a `HaversineData` JSON document parsed one per `next()`, so
+/// that a sum over them never holds more than one pair. Accepts the same
+/// input as `HaversineData::parse_from_json_slice`; a malformed document
+/// yields a single `Err` after its valid pairs.
+pub struct HaversineDataIter<'a> {
+    bytes: &'a [u8],
+    rest: &'a [u8],
+    expect: Expect,
+    options: ParseOptions,
+}
+
+impl<'a> HaversineDataIter<'a> {
+    /// Parses with the default `ParseOptions`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` does not start with `{"<key>": [`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        Self::with_options(bytes, ParseOptions::default())
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` does not start with `{"<key>": [`.
+    pub fn with_options(bytes: &'a [u8], options: ParseOptions) -> Result<Self, ParseError> {
+        let (rest, _) = tuple((eat_char('{'), key, eat_char('[')))
+            .parse(bytes)
+            .map_err(|e| parse_error(bytes, e))?;
+        Ok(Self {
+            bytes,
+            rest,
+            expect: Expect::Element,
+            options,
+        })
+    }
+
+    /// Bytes of the input parsed so far, for progress reporting
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.bytes.len() - self.rest.len()
+    }
+
+    /// Consumes the `]}` closing the document
+    fn close(&mut self) -> Option<Result<HaversineDataPoint, ParseError>> {
+        self.expect = Expect::Done;
+        match tuple((eat_char(']'), eat_char('}'))).parse(self.rest) {
+            Ok((rest, _)) => {
+                self.rest = rest;
+                None
+            }
+            Err(e) => Some(Err(parse_error(self.bytes, e))),
+        }
+    }
+}
+
+impl Iterator for HaversineDataIter<'_> {
+    type Item = Result<HaversineDataPoint, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.expect {
+            Expect::Done => None,
+            Expect::Separator => {
+                let Ok((rest, _)) = eat_char(',')(self.rest) else {
+                    return self.close();
+                };
+                self.rest = rest;
+                self.expect = Expect::Element;
+                self.next()
+            }
+            Expect::Element => match datapoint(self.options)(self.rest) {
+                Ok((rest, point)) => {
+                    self.rest = rest;
+                    self.expect = Expect::Separator;
+                    Some(Ok(point))
+                }
+                Err(nom::Err::Failure(e)) => {
+                    self.expect = Expect::Done;
+                    Some(Err(e.at(self.bytes)))
+                }
+                // a trailing comma is accepted, as by `haversine_datapoint_array`
+                Err(_) => self.close(),
+            },
+        }
+    }
 }
 
 impl HaversineData {
-    #[allow(clippy::pedantic)]
-    #[allow(clippy::result_unit_err)]
-    pub fn parse_from_json_slice(bytes: &[u8]) -> Result<HaversineData, ()> {
-        haversine_data(bytes).map(|(_, data)| data).map_err(|_| ())
+    /// Parses with the default `ParseOptions`
+    ///
+    /// # Errors
+    ///
+    /// Returns where and why `bytes` stopped being a `HaversineData` document.
+    pub fn parse_from_json_slice(bytes: &[u8]) -> Result<HaversineData, ParseError> {
+        Self::parse_from_json_slice_with(bytes, ParseOptions::default())
+    }
+
+    /// # Errors
+    ///
+    /// Returns where and why `bytes` stopped being a `HaversineData` document
+    /// with numbers as `options` allow.
+    pub fn parse_from_json_slice_with(
+        bytes: &[u8],
+        options: ParseOptions,
+    ) -> Result<HaversineData, ParseError> {
+        haversine_data(options)(bytes)
+            .map(|(_, data)| data)
+            .map_err(|e| parse_error(bytes, e))
+    }
+
+    /// Parses the pairs of `bytes` into `pairs`, cleared first, with numbers
+    /// and keys as `options` allow. Once `pairs` is large enough, parsing the
+    /// same or a smaller input again doesn't allocate.
+    ///
+    /// # Errors
+    ///
+    /// Returns where and why `bytes` stopped being a `HaversineData` document,
+    /// `pairs` then holds the pairs before it.
+    pub fn parse_into(
+        bytes: &[u8],
+        options: ParseOptions,
+        pairs: &mut Vec<HaversineDataPoint>,
+    ) -> Result<(), ParseError> {
+        pairs.clear();
+        for point in HaversineDataIter::with_options(bytes, options)? {
+            pairs.push(point?);
+        }
+        Ok(())
+    }
+}
+
+/// The pairs of the last of repeated parses (e.g. benchmark iterations),
+/// each parsed over the pairs of the one before so that the buffer is only
+/// allocated once it is large enough
+#[derive(Debug, Default)]
+pub struct PairBuffer {
+    pub(crate) pairs: Vec<HaversineDataPoint>,
+}
+
+impl PairBuffer {
+    #[must_use]
+    pub fn with_capacity(pair_count: usize) -> Self {
+        Self {
+            pairs: Vec::with_capacity(pair_count),
+        }
+    }
+
+    /// Pairs the buffer holds without growing
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.pairs.capacity()
+    }
+
+    /// Parses `bytes` over the pairs of the previous parse, see
+    /// `HaversineData::parse_into`
+    ///
+    /// # Errors
+    ///
+    /// Returns where and why `bytes` stopped being a `HaversineData` document.
+    pub fn parse(
+        &mut self,
+        bytes: &[u8],
+        options: ParseOptions,
+    ) -> Result<&[HaversineDataPoint], ParseError> {
+        HaversineData::parse_into(bytes, options, &mut self.pairs)?;
+        Ok(&self.pairs)
     }
 }
 
 #[cfg(test)]
 #[allow(clippy::unreadable_literal)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
     fn deserialize_coordinate() {
         let coordinate_slice = br#""x0": 123.456"#;
-        let out = coordinate()(coordinate_slice);
+        let out = tuple((key, number(ParseOptions::default()))).parse(coordinate_slice);
         assert!(out.is_ok());
-        assert_eq!(out.unwrap().1, (String::from("x0"), 123.456f64));
+        assert_eq!(out.unwrap().1, (&b"x0"[..], 123.456f64));
     }
 
     #[test]
@@ -168,10 +712,14 @@ mod tests {
         assert_eq!(
             out.unwrap().1,
             HaversineDataPoint {
-                x0: 177.74381301443074,
-                y0: 67.14837062236548,
-                x1: 176.66072571102146,
-                y1: 62.52409931003097
+                p0: Coord {
+                    lon: 177.74381301443074,
+                    lat: 67.14837062236548
+                },
+                p1: Coord {
+                    lon: 176.66072571102146,
+                    lat: 62.52409931003097
+                }
             }
         );
     }
@@ -192,22 +740,30 @@ mod tests {
                 "y1": 62.52409931003097
             }
         ]"#;
-        let out = haversine_datapoint_array(slice);
+        let out = haversine_datapoint_array(ParseOptions::default())(slice);
         assert!(out.is_ok());
         assert_eq!(
             out.unwrap().1,
             vec![
                 HaversineDataPoint {
-                    x0: 33.645001259581676,
-                    y0: -22.58786090058659,
-                    x1: -7.917869055261946,
-                    y1: 50.3982354259912
+                    p0: Coord {
+                        lon: 33.645001259581676,
+                        lat: -22.58786090058659
+                    },
+                    p1: Coord {
+                        lon: -7.917869055261946,
+                        lat: 50.3982354259912
+                    }
                 },
                 HaversineDataPoint {
-                    x0: 177.74381301443074,
-                    y0: 67.14837062236548,
-                    x1: 176.66072571102146,
-                    y1: 62.52409931003097
+                    p0: Coord {
+                        lon: 177.74381301443074,
+                        lat: 67.14837062236548
+                    },
+                    p1: Coord {
+                        lon: 176.66072571102146,
+                        lat: 62.52409931003097
+                    }
                 }
             ]
         );
@@ -231,26 +787,358 @@ mod tests {
                 }
             ]
         }"#;
-        let out = haversine_data(slice);
+        let out = haversine_data(ParseOptions::default())(slice);
         assert!(out.is_ok());
         assert_eq!(
             out.unwrap().1,
             HaversineData {
                 pairs: vec![
                     HaversineDataPoint {
-                        x0: 33.645001259581676,
-                        y0: -22.58786090058659,
-                        x1: -7.917869055261946,
-                        y1: 50.3982354259912
+                        p0: Coord {
+                            lon: 33.645001259581676,
+                            lat: -22.58786090058659
+                        },
+                        p1: Coord {
+                            lon: -7.917869055261946,
+                            lat: 50.3982354259912
+                        }
                     },
                     HaversineDataPoint {
-                        x0: 177.74381301443074,
-                        y0: 67.14837062236548,
-                        x1: 176.66072571102146,
-                        y1: 62.52409931003097
+                        p0: Coord {
+                            lon: 177.74381301443074,
+                            lat: 67.14837062236548
+                        },
+                        p1: Coord {
+                            lon: 176.66072571102146,
+                            lat: 62.52409931003097
+                        }
                     }
                 ]
             }
         );
     }
+
+    #[test]
+    fn iter_yields_the_parsed_pairs() {
+        use crate::fixtures::{EDGE_CASES, MALFORMED, VALID};
+
+        for fixture in [VALID, EDGE_CASES] {
+            let pairs: Result<Vec<_>, _> = HaversineDataIter::new(fixture.json).unwrap().collect();
+            assert_eq!(pairs.unwrap(), fixture.data().pairs, "{}", fixture.name);
+        }
+        for (name, bytes) in MALFORMED {
+            let pairs: Result<Vec<_>, _> =
+                HaversineDataIter::new(bytes).and_then(Iterator::collect);
+            assert_eq!(
+                pairs.map(|_| ()),
+                HaversineData::parse_from_json_slice(bytes).map(|_| ()),
+                "{name}"
+            );
+        }
+
+        let slice = br#"{"pairs": [{"x0": 1.5, "y0": 2.5, "x1": 3.5, "y1": 4.5},]}"#;
+        let pairs: Result<Vec<_>, _> = HaversineDataIter::new(slice).unwrap().collect();
+        assert_eq!(pairs.map(|pairs| pairs.len()), Ok(1));
+        for end in 0..slice.len() {
+            let pairs: Result<Vec<_>, _> =
+                HaversineDataIter::new(&slice[..end]).and_then(Iterator::collect);
+            assert!(pairs.is_err(), "{end}");
+        }
+    }
+
+    // Shapes of input found by the `fuzz/` targets.
+    #[test]
+    fn reject_corrupted_haversine_data() {
+        let slice = br#"{"pairs": [{"x0": 1.5, "y0": 2.5, "x1": 3.5, "y1": 4.5}]}"#;
+        for end in 0..slice.len() {
+            let err = HaversineData::parse_from_json_slice(&slice[..end]).unwrap_err();
+            assert!(
+                matches!(err, ParseError::UnexpectedEnd { position, .. } if position <= end),
+                "{end}: {err}"
+            );
+        }
+
+        let duplicate_key = br#"{"pairs": [{"x0": 1.5, "x0": 2.5, "x1": 3.5, "y1": 4.5}]}"#;
+        assert_eq!(
+            HaversineData::parse_from_json_slice(duplicate_key),
+            Err(ParseError::DuplicateKey { position: 23 })
+        );
+
+        let non_ascii_key =
+            b"{\"pairs\": [{\"x\xff\": 1.5, \"y0\": 2.5, \"x1\": 3.5, \"y1\": 4.5}]}";
+        assert_eq!(
+            HaversineData::parse_from_json_slice(non_ascii_key),
+            Err(ParseError::Unexpected {
+                position: 14,
+                expected: Token::Char('"'),
+                found: 0xff,
+            })
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_offending_byte() {
+        let cases: [(&[u8], ParseError); 4] = [
+            (
+                br#"{"pairs": [{"x0": 1.5, "y0": 2.5, "x1": 3.5 "y1": 4.5}]}"#,
+                ParseError::Unexpected {
+                    position: 44,
+                    expected: Token::Char(','),
+                    found: b'"',
+                },
+            ),
+            (
+                br#"{"pairs": [{"x0": 1.5, "y0": north, "x1": 3.5, "y1": 4.5}]}"#,
+                ParseError::Unexpected {
+                    position: 29,
+                    expected: Token::Number,
+                    found: b'n',
+                },
+            ),
+            (
+                br#"{"pairs": [{"x0": 1.5, "": 2.5, "x1": 3.5, "y1": 4.5}]}"#,
+                ParseError::Unexpected {
+                    position: 24,
+                    expected: Token::Key,
+                    found: b'"',
+                },
+            ),
+            (
+                br#"{"pairs": {}}"#,
+                ParseError::Unexpected {
+                    position: 10,
+                    expected: Token::Char('['),
+                    found: b'{',
+                },
+            ),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(HaversineData::parse_from_json_slice(bytes), Err(expected));
+            let pairs: Result<Vec<_>, _> =
+                HaversineDataIter::new(bytes).and_then(Iterator::collect);
+            assert_eq!(pairs, Err(expected));
+        }
+    }
+
+    #[test]
+    fn number_policy() {
+        let strict = ParseOptions {
+            strict_numbers: true,
+            ..ParseOptions::default()
+        };
+        let special = ParseOptions {
+            allow_nan: true,
+            allow_inf: true,
+            ..ParseOptions::default()
+        };
+        let parse = |number: &str, options| {
+            let json = format!(r#"{{"pairs": [{{"x0": {number}, "y0": 0, "x1": 0, "y1": 0}}]}}"#);
+            HaversineData::parse_from_json_slice_with(json.as_bytes(), options)
+                .map(|data| data.pairs[0].p0.lon)
+        };
+        // errors point at the start of the number
+        let rejected = |number: &str| {
+            Err(ParseError::Unexpected {
+                position: 18,
+                expected: Token::Number,
+                found: number.as_bytes()[0],
+            })
+        };
+
+        for (number, value) in [
+            ("1e3", 1e3),
+            ("-2.5E-3", -2.5e-3),
+            ("0.5e+1", 5.0),
+            ("-0", -0.0),
+        ] {
+            assert_eq!(parse(number, strict), Ok(value), "{number}");
+            assert_eq!(
+                parse(number, ParseOptions::default()),
+                Ok(value),
+                "{number}"
+            );
+        }
+        for (number, value) in [("+1.5", 1.5), (".5", 0.5), ("5.", 5.0), ("01", 1.0)] {
+            assert_eq!(parse(number, strict), rejected(number), "{number}");
+            assert_eq!(
+                parse(number, ParseOptions::default()),
+                Ok(value),
+                "{number}"
+            );
+        }
+
+        for number in ["NaN", "nan", "Infinity", "-inf", "+INF"] {
+            assert_eq!(
+                parse(number, ParseOptions::default()),
+                rejected(number),
+                "{number}"
+            );
+        }
+        assert!(parse("NaN", special).unwrap().is_nan());
+        assert_eq!(parse("Infinity", special), Ok(f64::INFINITY));
+        assert_eq!(parse("-inf", special), Ok(f64::NEG_INFINITY));
+        assert_eq!(parse("+Infinity", special), Ok(f64::INFINITY));
+        let strict_special = ParseOptions {
+            strict_numbers: true,
+            ..special
+        };
+        assert_eq!(parse("-Infinity", strict_special), Ok(f64::NEG_INFINITY));
+        assert_eq!(parse("+Infinity", strict_special), rejected("+"));
+    }
+
+    #[test]
+    fn key_policies() {
+        let parse = |json: &[u8], duplicate_keys, unknown_keys| {
+            let options = ParseOptions {
+                duplicate_keys,
+                unknown_keys,
+                ..ParseOptions::default()
+            };
+            // the iterator parse_into reads with takes the same policies
+            let mut pairs = Vec::new();
+            let into =
+                HaversineData::parse_into(json, options, &mut pairs).map(|()| pairs[0].p0.lon);
+            let whole = HaversineData::parse_from_json_slice_with(json, options)
+                .map(|data| data.pairs[0].p0.lon);
+            assert_eq!(into, whole);
+            whole
+        };
+        let (error, ignore) = (UnknownKeyPolicy::Error, UnknownKeyPolicy::Ignore);
+
+        let duplicate = br#"{"pairs": [{"x0": 1, "y0": 0, "x0": 2, "x1": 0, "y1": 0}]}"#;
+        for (policy, expected) in [
+            (
+                DuplicateKeyPolicy::Error,
+                Err(ParseError::DuplicateKey { position: 30 }),
+            ),
+            (DuplicateKeyPolicy::FirstWins, Ok(1.0)),
+            (DuplicateKeyPolicy::LastWins, Ok(2.0)),
+        ] {
+            assert_eq!(parse(duplicate, policy, error), expected, "{policy:?}");
+        }
+
+        let unknown = br#"{"pairs": [{"x0": 1, "id": "a\"b", "y0": 0, "x1": 0, "y1": 0,
+            "tags": [true, null, {"k": [-1.5e3, {}]}, []], "meta": {}}]}"#;
+        assert_eq!(
+            parse(unknown, DuplicateKeyPolicy::Error, error),
+            Err(ParseError::UnknownKey { position: 21 })
+        );
+        assert_eq!(parse(unknown, DuplicateKeyPolicy::Error, ignore), Ok(1.0));
+        // unknown keys don't stand in for missing coordinates
+        let missing = br#"{"pairs": [{"x0": 1, "y0": 0, "x1": 0, "z": 0}]}"#;
+        assert_eq!(
+            parse(missing, DuplicateKeyPolicy::Error, ignore),
+            Err(ParseError::InvalidDatapoint { position: 11 })
+        );
+
+        let malformed: [(&[u8], ParseError); 3] = [
+            (
+                br#"{"pairs": [{"x0": 1, "id": tru, "y0": 0, "x1": 0, "y1": 0}]}"#,
+                ParseError::Unexpected {
+                    position: 27,
+                    expected: Token::Value,
+                    found: b't',
+                },
+            ),
+            (
+                br#"{"pairs": [{"x0": 1, "id": [1 2], "y0": 0, "x1": 0, "y1": 0}]}"#,
+                ParseError::Unexpected {
+                    position: 30,
+                    expected: Token::Char(','),
+                    found: b'2',
+                },
+            ),
+            (
+                br#"{"pairs": [{"x0": 1, "id": "a\""#,
+                ParseError::UnexpectedEnd {
+                    position: 31,
+                    expected: Token::Char('"'),
+                },
+            ),
+        ];
+        for (json, expected) in malformed {
+            assert_eq!(
+                parse(json, DuplicateKeyPolicy::Error, ignore),
+                Err(expected)
+            );
+        }
+
+        let deep = format!(
+            r#"{{"pairs": [{{"x0": 1, "id": {}{}, "y0": 0, "x1": 0, "y1": 0}}]}}"#,
+            "[".repeat(10_000),
+            "]".repeat(10_000)
+        );
+        assert!(matches!(
+            parse(deep.as_bytes(), DuplicateKeyPolicy::Error, ignore),
+            Err(ParseError::Unexpected {
+                expected: Token::Value,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn buffer_keeps_its_capacity() {
+        let options = ParseOptions::default();
+        let data = crate::fixtures::VALID.data();
+        let json = serde_json::to_vec(&data).unwrap();
+        let fewer = serde_json::to_vec(&HaversineData {
+            pairs: data.pairs[1..].to_vec(),
+        })
+        .unwrap();
+        let mut buffer = PairBuffer::default();
+        assert_eq!(buffer.parse(&json, options), Ok(&data.pairs[..]));
+        let capacity = buffer.capacity();
+        assert!(capacity >= data.pairs.len());
+        assert_eq!(buffer.parse(&json, options), Ok(&data.pairs[..]));
+        assert_eq!(buffer.parse(&fewer, options), Ok(&data.pairs[1..]));
+        assert_eq!(buffer.capacity(), capacity);
+    }
+
+    proptest! {
+        #[test]
+        fn serde_output_round_trips(data in any::<HaversineData>()) {
+            // one buffer across both documents, the second parse reuses it
+            let mut buffer = PairBuffer::default();
+            for json in [
+                serde_json::to_vec(&data).unwrap(),
+                serde_json::to_vec_pretty(&data).unwrap(),
+            ] {
+                let parsed = HaversineData::parse_from_json_slice(&json);
+                prop_assert_eq!(parsed.map(|parsed| parsed.pairs), Ok(data.pairs.clone()));
+                let strict = ParseOptions { strict_numbers: true, ..ParseOptions::default() };
+                let parsed = HaversineData::parse_from_json_slice_with(&json, strict);
+                prop_assert_eq!(parsed.map(|parsed| parsed.pairs), Ok(data.pairs.clone()));
+                let pairs: Result<Vec<_>, _> = HaversineDataIter::new(&json).unwrap().collect();
+                prop_assert_eq!(pairs, Ok(data.pairs.clone()));
+                let parsed = buffer.parse(&json, ParseOptions::default()).map(<[_]>::to_vec);
+                prop_assert_eq!(parsed, Ok(data.pairs.clone()));
+            }
+        }
+
+        // any finite f64, so serde also writes exponents and subnormals
+        #[test]
+        fn serde_numbers_parse_strictly(
+            [x0, y0, x1, y1] in [prop::num::f64::NORMAL | prop::num::f64::SUBNORMAL | prop::num::f64::ZERO; 4]
+        ) {
+            let pairs = vec![HaversineDataPoint::from(((x0, y0), (x1, y1)))];
+            let json = serde_json::to_vec(&HaversineData { pairs: pairs.clone() }).unwrap();
+            let strict = ParseOptions { strict_numbers: true, ..ParseOptions::default() };
+            let parsed = HaversineData::parse_from_json_slice_with(&json, strict);
+            prop_assert_eq!(parsed.map(|parsed| parsed.pairs), Ok(pairs));
+        }
+
+        #[test]
+        fn truncated_input_ends_early(data in any::<HaversineData>(), cut in any::<prop::sample::Index>()) {
+            let json = serde_json::to_vec_pretty(&data).unwrap();
+            // the closing `}` is the last byte
+            let end = cut.index(json.len() - 1);
+            let err = HaversineData::parse_from_json_slice(&json[..end]).unwrap_err();
+            prop_assert!(err.position() <= end, "{}", err);
+            let mut pairs = data.pairs.clone();
+            let parsed = HaversineData::parse_into(&json[..end], ParseOptions::default(), &mut pairs);
+            prop_assert!(parsed.is_err());
+            prop_assert!(pairs.len() <= data.pairs.len());
+        }
+    }
 }
@@ -0,0 +1,82 @@
+//! C ABI for the haversine kernels, see `include/haversine.h`.
+
+use std::ptr;
+
+use crate::{reference_haversine, HaversineData, HaversineDataPoint};
+
+/// Status codes returned by the fallible FFI functions
+#[repr(C)]
+pub enum HaversineStatus {
+    Ok = 0,
+    NullPointer = 1,
+    ParseError = 2,
+}
+
+/// Great circle distance between (`x0`, `y0`) and (`x1`, `y1`) in degrees,
+/// on a sphere of the given radius.
+#[no_mangle]
+pub extern "C" fn haversine_distance(x0: f64, y0: f64, x1: f64, y1: f64, radius: f64) -> f64 {
+    reference_haversine(&((x0, y0), (x1, y1)).into(), radius)
+}
+
+/// Parses `{"pairs": [...]}` JSON into a newly allocated array of pairs.
+/// On success `*out_pairs` and `*out_len` describe the array, which must be
+/// released with `haversine_free_pairs`.
+///
+/// # Safety
+///
+/// `json` must be valid for reads of `len` bytes, `out_pairs` and `out_len`
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn haversine_parse_json(
+    json: *const u8,
+    len: usize,
+    out_pairs: *mut *mut HaversineDataPoint,
+    out_len: *mut usize,
+) -> HaversineStatus {
+    if json.is_null() || out_pairs.is_null() || out_len.is_null() {
+        return HaversineStatus::NullPointer;
+    }
+    let bytes = std::slice::from_raw_parts(json, len);
+    let Ok(data) = HaversineData::parse_from_json_slice(bytes) else {
+        return HaversineStatus::ParseError;
+    };
+    let pairs = Box::into_raw(data.pairs.into_boxed_slice());
+    *out_len = pairs.len();
+    *out_pairs = pairs.cast();
+    HaversineStatus::Ok
+}
+
+/// Releases an array returned by `haversine_parse_json`.
+///
+/// # Safety
+///
+/// `pairs` and `len` must come from a single successful `haversine_parse_json`
+/// call and must not be used afterwards. Null `pairs` is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn haversine_free_pairs(pairs: *mut HaversineDataPoint, len: usize) {
+    if !pairs.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(pairs, len)));
+    }
+}
+
+/// Sum of the distances of `len` pairs on a sphere of the given radius.
+/// Returns 0 for null `pairs`.
+///
+/// # Safety
+///
+/// `pairs` must be valid for reads of `len` elements.
+#[no_mangle]
+pub unsafe extern "C" fn haversine_sum(
+    pairs: *const HaversineDataPoint,
+    len: usize,
+    radius: f64,
+) -> f64 {
+    if pairs.is_null() {
+        return 0.0;
+    }
+    std::slice::from_raw_parts(pairs, len)
+        .iter()
+        .map(|point| reference_haversine(point, radius))
+        .sum()
+}
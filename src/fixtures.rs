@@ -0,0 +1,293 @@
+//! Small checked-in datasets for exercising the parsers and the pipeline
+//! without generating files. The sources live in `fixtures/`.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{answers, HaversineData};
+
+macro_rules! fixture {
+    ($file:literal) => {
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/", $file))
+    };
+}
+
+/// A JSON dataset and its answers file (see [`crate::answers`], as written by
+/// `haversine generate`)
+#[derive(Clone, Copy, Debug)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub json: &'static [u8],
+    pub answers: &'static [u8],
+}
+
+/// Eight clustered pairs from `haversine generate cluster 3 8`
+pub const VALID: Fixture = Fixture {
+    name: "valid",
+    json: fixture!("valid.json"),
+    answers: fixture!("valid.f64"),
+};
+
+/// Identical points, poles, antipodes, the antimeridian, negative zero,
+/// exponent notation and reordered keys
+pub const EDGE_CASES: Fixture = Fixture {
+    name: "edge_cases",
+    json: fixture!("edge_cases.json"),
+    answers: fixture!("edge_cases.f64"),
+};
+
+/// Inputs every parser must reject, by name
+pub const MALFORMED: [(&str, &[u8]); 5] = [
+    ("truncated", fixture!("malformed/truncated.json")),
+    ("missing_key", fixture!("malformed/missing_key.json")),
+    ("bad_number", fixture!("malformed/bad_number.json")),
+    ("unclosed", fixture!("malformed/unclosed.json")),
+    ("empty", fixture!("malformed/empty.json")),
+];
+
+impl Fixture {
+    /// The parsed dataset
+    ///
+    /// # Panics
+    ///
+    /// Never for the fixtures above, they are checked by the tests.
+    #[must_use]
+    pub fn data(&self) -> HaversineData {
+        serde_json::from_slice(self.json).expect("fixtures are valid JSON")
+    }
+
+    /// Expected distances followed by the average
+    ///
+    /// # Panics
+    ///
+    /// Never for the fixtures above, they are checked by the tests.
+    #[must_use]
+    pub fn answers(&self) -> Vec<f64> {
+        let (_, answers) = answers::decode(self.answers).expect("fixtures are valid answers files");
+        answers
+    }
+
+    /// Writes `<name>.json` and `<name>.f64` into `dir` and returns their
+    /// paths, for code that takes files such as the pipeline
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first write that fails.
+    pub fn write_to(&self, dir: &Path) -> io::Result<(PathBuf, PathBuf)> {
+        let json = dir.join(format!("{}.json", self.name));
+        let answers = dir.join(format!("{}.f64", self.name));
+        std::fs::write(&json, self.json)?;
+        std::fs::write(&answers, self.answers)?;
+        Ok((json, answers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{reference_haversine, EARTH_RADIUS};
+
+    type Parser = fn(&[u8]) -> Result<HaversineData, ()>;
+
+    const PARSERS: &[(&str, Parser)] = &[
+        ("nom", |bytes| {
+            HaversineData::parse_from_json_slice(bytes).map_err(|_| ())
+        }),
+        ("scanner", HaversineData::parse_from_json_slice_scanner),
+        #[cfg(all(feature = "simd-parse", not(target_arch = "wasm32")))]
+        ("simd-scan", HaversineData::parse_from_json_slice_simd_scan),
+        ("serde", |bytes| {
+            serde_json::from_slice(bytes).map_err(|_| ())
+        }),
+    ];
+
+    #[test]
+    fn parsers_agree_with_answers() {
+        for fixture in [VALID, EDGE_CASES] {
+            let answers = fixture.answers();
+            for (parser, parse) in PARSERS {
+                let data = parse(fixture.json).unwrap_or_else(|()| {
+                    panic!("{parser} rejected {}", fixture.name);
+                });
+                assert_eq!(data.pairs.len() + 1, answers.len(), "{}", fixture.name);
+                for (point, &expected) in data.pairs.iter().zip(&answers) {
+                    assert_eq!(
+                        reference_haversine(point, EARTH_RADIUS).to_bits(),
+                        expected.to_bits(),
+                        "{parser} {} {point:?}",
+                        fixture.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parsers_reject_malformed() {
+        for (name, bytes) in MALFORMED {
+            for (parser, parse) in PARSERS {
+                assert!(parse(bytes).is_err(), "{parser} accepted {name}");
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn pipeline_validates_fixtures() {
+        use crate::pipeline::{Pipeline, PipelineOptions};
+
+        let dir = std::env::temp_dir().join(format!("haversine-fixtures-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for fixture in [VALID, EDGE_CASES] {
+            let (json, answers) = fixture.write_to(&dir).unwrap();
+            let report = Pipeline::new(PipelineOptions {
+                answers: Some(answers),
+                tolerance: 0.0,
+                ..PipelineOptions::default()
+            })
+            .run(&json)
+            .unwrap_or_else(|e| panic!("{}: {e}", fixture.name));
+            assert_eq!(
+                report.average.to_bits(),
+                fixture.answers().last().unwrap().to_bits(),
+                "{}",
+                fixture.name
+            );
+
+            // the same answers without the header, as older generators wrote them
+            let legacy: Vec<u8> = fixture
+                .answers()
+                .iter()
+                .flat_map(|a| a.to_le_bytes())
+                .collect();
+            let legacy_path = dir.join(format!("{}_legacy.f64", fixture.name));
+            std::fs::write(&legacy_path, legacy).unwrap();
+            let options = PipelineOptions {
+                answers: Some(legacy_path),
+                tolerance: 0.0,
+                ..PipelineOptions::default()
+            };
+            assert!(Pipeline::new(options.clone()).run(&json).is_err());
+            let legacy_options = PipelineOptions {
+                legacy_answers: true,
+                ..options
+            };
+            let report = Pipeline::new(legacy_options).run(&json).unwrap();
+            assert!(report.validation.is_some(), "{}", fixture.name);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn pipeline_reports_progress() {
+        use std::cell::RefCell;
+
+        use crate::{
+            pipeline::{ComputeBackend, Pipeline, PipelineOptions},
+            progress::{Progress, Stage},
+        };
+
+        thread_local! {
+            static UPDATES: RefCell<Vec<Progress>> = const { RefCell::new(Vec::new()) };
+        }
+        let dir = std::env::temp_dir().join(format!("haversine-progress-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (json, answers) = EDGE_CASES.write_to(&dir).unwrap();
+        for (options, stages) in [
+            (
+                PipelineOptions::default(),
+                &[Stage::Parse, Stage::Compute][..],
+            ),
+            (
+                PipelineOptions {
+                    stream: true,
+                    ..PipelineOptions::default()
+                },
+                &[Stage::Stream],
+            ),
+            (
+                PipelineOptions {
+                    compute: ComputeBackend::Simd,
+                    ..PipelineOptions::default()
+                },
+                &[Stage::Parse, Stage::Compute],
+            ),
+        ] {
+            let options = PipelineOptions {
+                answers: Some(answers.clone()),
+                ..options
+            };
+            let quiet = Pipeline::new(options.clone()).run(&json).unwrap();
+            let reporting = Pipeline::new(PipelineOptions {
+                progress: Some(|progress| {
+                    UPDATES.with_borrow_mut(|updates| updates.push(*progress));
+                }),
+                ..options
+            })
+            .run(&json)
+            .unwrap();
+            assert_eq!(reporting, quiet, "{stages:?}");
+
+            let updates = UPDATES.take();
+            let mut reported: Vec<_> = updates.iter().map(|progress| progress.stage).collect();
+            reported.dedup();
+            assert_eq!(reported, stages);
+            for stage in updates.chunk_by(|a, b| a.stage == b.stage) {
+                assert!(
+                    stage.is_sorted_by_key(|progress| progress.done),
+                    "{stage:?}"
+                );
+                assert!(stage.last().unwrap().is_done(), "{stage:?}");
+                assert_eq!(
+                    stage.iter().filter(|progress| progress.is_done()).count(),
+                    1
+                );
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn strict_pipeline_rejects_out_of_range_coordinates() {
+        use crate::{
+            pipeline::{Pipeline, PipelineError, PipelineOptions},
+            CoordError,
+        };
+
+        let dir = std::env::temp_dir().join(format!("haversine-strict-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (valid, _) = VALID.write_to(&dir).unwrap();
+        let out_of_range = dir.join("out_of_range.json");
+        std::fs::write(
+            &out_of_range,
+            br#"{"pairs": [{"x0": 1, "y0": 2, "x1": 3, "y1": 4}, {"x0": 1, "y0": 95, "x1": 3, "y1": 4}]}"#,
+        )
+        .unwrap();
+        for stream in [false, true] {
+            let strict = Pipeline::new(PipelineOptions {
+                stream,
+                strict: true,
+                ..PipelineOptions::default()
+            });
+            assert!(strict.run(&valid).is_ok(), "stream={stream}");
+            assert!(
+                matches!(
+                    strict.run(&out_of_range),
+                    Err(PipelineError::InvalidCoordinates {
+                        index: 1,
+                        error: CoordError::Latitude { name: "y0", .. },
+                    })
+                ),
+                "stream={stream}"
+            );
+        }
+        assert!(Pipeline::new(PipelineOptions::default())
+            .run(&out_of_range)
+            .is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,537 @@
+//! On-disk dataset formats besides the generator's JSON.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    deserializer::haversine_datapoint, Coord, HaversineData, HaversineDataPoint, ParseError,
+};
+
+/// First bytes of a binary dataset, followed by the pair count as a little
+/// endian `u64` and the pairs as packed little endian `x0, y0, x1, y1` quads.
+pub const BINARY_MAGIC: &[u8; 8] = b"HAVRSIN1";
+pub const CSV_HEADER: &str = "x0,y0,x1,y1";
+/// First (and last) bytes of a Parquet file
+#[cfg(feature = "parquet")]
+pub const PARQUET_MAGIC: &[u8; 4] = b"PAR1";
+/// Pairs per row group of the Parquet files written by `write_parquet`
+#[cfg(feature = "parquet")]
+const PARQUET_ROW_GROUP: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Binary,
+    Csv,
+    /// one `{"x0":..,"y0":..,"x1":..,"y1":..}` object per line
+    Ndjson,
+    /// `x0`, `y0`, `x1` and `y1` double columns
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl Format {
+    /// Guesses the format from the leading bytes of a dataset
+    #[must_use]
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(BINARY_MAGIC) {
+            return Some(Self::Binary);
+        }
+        #[cfg(feature = "parquet")]
+        if bytes.starts_with(PARQUET_MAGIC) {
+            return Some(Self::Parquet);
+        }
+        let text = bytes.trim_ascii_start();
+        if let Some(object) = text.strip_prefix(b"{") {
+            if object.trim_ascii_start().starts_with(b"\"pairs\"") {
+                Some(Self::Json)
+            } else {
+                Some(Self::Ndjson)
+            }
+        } else if text.starts_with(CSV_HEADER.as_bytes()) {
+            Some(Self::Csv)
+        } else {
+            None
+        }
+    }
+
+    /// Format implied by the file extension (`json`, `bin`, `csv`, `ndjson`/`jsonl`,
+    /// `parquet`)
+    #[must_use]
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "bin" => Some(Self::Binary),
+            "csv" => Some(Self::Csv),
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            #[cfg(feature = "parquet")]
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+
+    /// Canonical file extension, the inverse of `from_extension`
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Binary => "bin",
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+            #[cfg(feature = "parquet")]
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::Binary => "binary",
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+            #[cfg(feature = "parquet")]
+            Self::Parquet => "parquet",
+        })
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "binary" | "bin" => Ok(Self::Binary),
+            "csv" => Ok(Self::Csv),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(Self::Parquet),
+            #[cfg(feature = "parquet")]
+            _ => Err(format!(
+                "unknown format `{s}`, expected json, binary, csv, ndjson or parquet"
+            )),
+            #[cfg(not(feature = "parquet"))]
+            _ => Err(format!(
+                "unknown format `{s}`, expected json, binary, csv or ndjson"
+            )),
+        }
+    }
+}
+
+/// Why `HaversineData::parse_from_slice` rejected a dataset, with the
+/// position of the error in JSON ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatasetError {
+    Json(ParseError),
+    Binary,
+    Csv,
+    Ndjson,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(e) => write!(f, "{e}"),
+            Self::Binary => write!(
+                f,
+                "no binary header, or a pair count that doesn't match the size"
+            ),
+            Self::Csv => write!(
+                f,
+                "no `{CSV_HEADER}` header, or a row without exactly four numbers"
+            ),
+            Self::Ndjson => write!(f, "a line that isn't a pair object"),
+            #[cfg(feature = "parquet")]
+            Self::Parquet => write!(
+                f,
+                "not a Parquet file with x0, y0, x1 and y1 double columns without nulls"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DatasetError {}
+
+impl HaversineData {
+    /// # Errors
+    ///
+    /// Returns a `DatasetError` if `bytes` is not a valid dataset in `format`.
+    pub fn parse_from_slice(bytes: &[u8], format: Format) -> Result<HaversineData, DatasetError> {
+        match format {
+            Format::Json => Self::parse_from_json_slice(bytes).map_err(DatasetError::Json),
+            Format::Binary => Self::read_binary(bytes).map_err(|()| DatasetError::Binary),
+            Format::Csv => Self::parse_from_csv_slice(bytes).map_err(|()| DatasetError::Csv),
+            Format::Ndjson => {
+                Self::parse_from_ndjson_slice(bytes).map_err(|()| DatasetError::Ndjson)
+            }
+            #[cfg(feature = "parquet")]
+            Format::Parquet => Self::read_parquet(bytes).map_err(|()| DatasetError::Parquet),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns `Err(())` on a missing magic header or a pair count that does
+    /// not match the payload length.
+    #[allow(clippy::result_unit_err)]
+    pub fn read_binary(bytes: &[u8]) -> Result<HaversineData, ()> {
+        let rest = bytes.strip_prefix(BINARY_MAGIC).ok_or(())?;
+        let (count, rest) = rest.split_first_chunk::<8>().ok_or(())?;
+        let count = usize::try_from(u64::from_le_bytes(*count)).map_err(|_| ())?;
+        if rest.len() != count.checked_mul(32).ok_or(())? {
+            return Err(());
+        }
+        let (values, _) = rest.as_chunks::<8>();
+        let pairs = values
+            .chunks_exact(4)
+            .map(|quad| HaversineDataPoint {
+                p0: Coord {
+                    lon: f64::from_le_bytes(quad[0]),
+                    lat: f64::from_le_bytes(quad[1]),
+                },
+                p1: Coord {
+                    lon: f64::from_le_bytes(quad[2]),
+                    lat: f64::from_le_bytes(quad[3]),
+                },
+            })
+            .collect();
+        Ok(HaversineData { pairs })
+    }
+
+    /// # Errors
+    ///
+    /// Returns any error of the underlying writer.
+    pub fn write_binary(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(BINARY_MAGIC)?;
+        writer.write_all(&(self.pairs.len() as u64).to_le_bytes())?;
+        for point in &self.pairs {
+            for value in [point.p0.lon, point.p0.lat, point.p1.lon, point.p1.lat] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `x0,y0,x1,y1` rows after a `CSV_HEADER` line.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` on a missing header or a row without exactly four numbers.
+    #[allow(clippy::result_unit_err)]
+    pub fn parse_from_csv_slice(bytes: &[u8]) -> Result<HaversineData, ()> {
+        let text = std::str::from_utf8(bytes).map_err(|_| ())?;
+        let mut lines = text.lines();
+        if lines.next().map(str::trim) != Some(CSV_HEADER) {
+            return Err(());
+        }
+        lines
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let mut values = line.split(',').map(|v| v.trim().parse::<f64>());
+                let mut next = || values.next().ok_or(())?.map_err(|_| ());
+                let point = HaversineDataPoint {
+                    p0: Coord {
+                        lon: next()?,
+                        lat: next()?,
+                    },
+                    p1: Coord {
+                        lon: next()?,
+                        lat: next()?,
+                    },
+                };
+                if values.next().is_some() {
+                    return Err(());
+                }
+                Ok(point)
+            })
+            .collect::<Result<_, _>>()
+            .map(|pairs| HaversineData { pairs })
+    }
+
+    /// # Errors
+    ///
+    /// Returns any error of the underlying writer.
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{CSV_HEADER}")?;
+        for point in &self.pairs {
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                point.p0.lon, point.p0.lat, point.p1.lon, point.p1.lat
+            )?;
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Returns `Err(())` if a non-empty line is not a pair object.
+    #[allow(clippy::result_unit_err)]
+    pub fn parse_from_ndjson_slice(bytes: &[u8]) -> Result<HaversineData, ()> {
+        bytes
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.trim_ascii().is_empty())
+            .map(|line| match haversine_datapoint(line) {
+                Ok((&[], point)) => Ok(point),
+                _ => Err(()),
+            })
+            .collect::<Result<_, _>>()
+            .map(|pairs| HaversineData { pairs })
+    }
+
+    /// # Errors
+    ///
+    /// Returns any error of the underlying writer.
+    pub fn write_ndjson(&self, mut writer: impl Write) -> io::Result<()> {
+        for point in &self.pairs {
+            serde_json::to_writer(&mut writer, point)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Reads the `x0`, `y0`, `x1` and `y1` columns of a Parquet file, in any
+    /// order and among others; nullable ones are fine without nulls, as
+    /// pandas and polars write them.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` if `bytes` is not a Parquet file, or one of the
+    /// columns is missing, not of doubles or has a null.
+    #[cfg(feature = "parquet")]
+    #[allow(clippy::result_unit_err)]
+    pub fn read_parquet(bytes: &[u8]) -> Result<HaversineData, ()> {
+        use parquet::{
+            basic::Type,
+            column::reader::ColumnReader,
+            file::reader::{FileReader, SerializedFileReader},
+        };
+
+        // the reader wants owned bytes, a copy like simd-json's
+        let reader =
+            SerializedFileReader::new(bytes::Bytes::copy_from_slice(bytes)).map_err(|_| ())?;
+        let schema = reader.metadata().file_metadata().schema_descr();
+        let mut columns = [0; 4];
+        for (index, name) in columns.iter_mut().zip(CSV_HEADER.split(',')) {
+            *index = (0..schema.num_columns())
+                .find(|&i| schema.column(i).path().string() == name)
+                .ok_or(())?;
+            let column = schema.column(*index);
+            if column.physical_type() != Type::DOUBLE || column.max_rep_level() != 0 {
+                return Err(());
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for group in 0..reader.num_row_groups() {
+            let group = reader.get_row_group(group).map_err(|_| ())?;
+            let rows = usize::try_from(group.metadata().num_rows()).map_err(|_| ())?;
+            let mut values = [const { Vec::new() }; 4];
+            for (values, &index) in values.iter_mut().zip(&columns) {
+                let ColumnReader::DoubleColumnReader(mut column) =
+                    group.get_column_reader(index).map_err(|_| ())?
+                else {
+                    return Err(());
+                };
+                let mut def_levels = Vec::new();
+                let nullable = schema.column(index).max_def_level() > 0;
+                column
+                    .read_records(rows, nullable.then_some(&mut def_levels), None, values)
+                    .map_err(|_| ())?;
+                // nulls leave fewer values than rows
+                if values.len() != rows {
+                    return Err(());
+                }
+            }
+            let [x0, y0, x1, y1] = values;
+            pairs.extend((0..rows).map(|i| HaversineDataPoint {
+                p0: Coord {
+                    lon: x0[i],
+                    lat: y0[i],
+                },
+                p1: Coord {
+                    lon: x1[i],
+                    lat: y1[i],
+                },
+            }));
+        }
+        Ok(HaversineData { pairs })
+    }
+
+    /// Writes the pairs as required `x0`, `y0`, `x1` and `y1` double columns,
+    /// uncompressed, in row groups of a million pairs.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error of the underlying writer.
+    #[cfg(feature = "parquet")]
+    pub fn write_parquet(&self, writer: impl Write + Send) -> io::Result<()> {
+        use std::sync::Arc;
+
+        use parquet::{
+            data_type::DoubleType,
+            file::{properties::WriterProperties, writer::SerializedFileWriter},
+            schema::parser::parse_message_type,
+        };
+
+        let schema = parse_message_type(
+            "message haversine {
+                required double x0;
+                required double y0;
+                required double x1;
+                required double y1;
+            }",
+        )
+        .map_err(io::Error::other)?;
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(PARQUET_ROW_GROUP)
+            .build();
+        let mut writer = SerializedFileWriter::new(writer, Arc::new(schema), Arc::new(properties))
+            .map_err(io::Error::other)?;
+        let columns: [fn(&HaversineDataPoint) -> f64; 4] = [
+            |point| point.p0.lon,
+            |point| point.p0.lat,
+            |point| point.p1.lon,
+            |point| point.p1.lat,
+        ];
+        for pairs in self.pairs.chunks(PARQUET_ROW_GROUP) {
+            let mut group = writer.next_row_group().map_err(io::Error::other)?;
+            let mut values = Vec::with_capacity(pairs.len());
+            for value in columns {
+                let mut column = group
+                    .next_column()
+                    .map_err(io::Error::other)?
+                    .ok_or_else(|| io::Error::other("the schema has four columns"))?;
+                values.clear();
+                values.extend(pairs.iter().map(value));
+                column
+                    .typed::<DoubleType>()
+                    .write_batch(&values, None, None)
+                    .map_err(io::Error::other)?;
+                column.close().map_err(io::Error::other)?;
+            }
+            group.close().map_err(io::Error::other)?;
+        }
+        writer.close().map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn formats_round_trip(data in any::<HaversineData>()) {
+            let mut binary = Vec::new();
+            data.write_binary(&mut binary).unwrap();
+            prop_assert_eq!(Format::detect(&binary), Some(Format::Binary));
+            prop_assert_eq!(&HaversineData::read_binary(&binary).unwrap(), &data);
+
+            let mut csv = Vec::new();
+            data.write_csv(&mut csv).unwrap();
+            prop_assert_eq!(Format::detect(&csv), Some(Format::Csv));
+            prop_assert_eq!(&HaversineData::parse_from_csv_slice(&csv).unwrap(), &data);
+
+            let mut ndjson = Vec::new();
+            data.write_ndjson(&mut ndjson).unwrap();
+            prop_assert_eq!(&HaversineData::parse_from_ndjson_slice(&ndjson).unwrap(), &data);
+        }
+
+        #[cfg(feature = "parquet")]
+        #[test]
+        fn parquet_round_trips(data in any::<HaversineData>()) {
+            let mut parquet = Vec::new();
+            data.write_parquet(&mut parquet).unwrap();
+            prop_assert_eq!(Format::detect(&parquet), Some(Format::Parquet));
+            prop_assert_eq!(&HaversineData::read_parquet(&parquet).unwrap(), &data);
+        }
+    }
+
+    #[test]
+    fn malformed_json_keeps_its_position() {
+        let error =
+            HaversineData::parse_from_slice(br#"{"pairs":[{"x0":1,}]}"#, Format::Json).unwrap_err();
+        assert_eq!(error.to_string(), "expected `\"` at byte 18, found `}`");
+        assert_eq!(
+            HaversineData::parse_from_slice(b"x0,y0,x1,y1\n1,2,3\n", Format::Csv),
+            Err(DatasetError::Csv)
+        );
+    }
+
+    /// Columns as pandas writes them: optional, in another order, with an index
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_reads_nullable_columns() {
+        use std::sync::Arc;
+
+        use parquet::{
+            data_type::{DoubleType, Int64Type},
+            file::{properties::WriterProperties, writer::SerializedFileWriter},
+            schema::parser::parse_message_type,
+        };
+
+        let write = |y1: &[f64], def_levels: &[i16]| {
+            let schema = parse_message_type(
+                "message pandas {
+                    optional int64 index;
+                    optional double y1;
+                    optional double x1;
+                    optional double y0;
+                    optional double x0;
+                }",
+            )
+            .unwrap();
+            let properties = Arc::new(WriterProperties::builder().build());
+            let mut bytes = Vec::new();
+            let mut writer =
+                SerializedFileWriter::new(&mut bytes, Arc::new(schema), properties).unwrap();
+            let mut group = writer.next_row_group().unwrap();
+            let mut column = group.next_column().unwrap().unwrap();
+            column
+                .typed::<Int64Type>()
+                .write_batch(&[0, 1], Some(&[1, 1]), None)
+                .unwrap();
+            column.close().unwrap();
+            for values in [y1, &[1.0, 2.0], &[3.0, 4.0], &[5.0, 6.0]] {
+                let mut column = group.next_column().unwrap().unwrap();
+                let def_levels = if values.len() == 2 {
+                    &[1, 1]
+                } else {
+                    def_levels
+                };
+                column
+                    .typed::<DoubleType>()
+                    .write_batch(values, Some(def_levels), None)
+                    .unwrap();
+                column.close().unwrap();
+            }
+            group.close().unwrap();
+            writer.close().unwrap();
+            bytes
+        };
+
+        let data = HaversineData::read_parquet(&write(&[7.0, 8.0], &[1, 1])).unwrap();
+        assert_eq!(
+            data.pairs[1],
+            HaversineDataPoint {
+                p0: Coord { lon: 6.0, lat: 4.0 },
+                p1: Coord { lon: 2.0, lat: 8.0 },
+            }
+        );
+        assert_eq!(
+            HaversineData::read_parquet(&write(&[7.0], &[1, 0])),
+            Err(())
+        );
+    }
+}
@@ -0,0 +1,453 @@
+//! Seeded dataset generation. The same seed always produces the same pairs.
+
+use std::num::NonZeroUsize;
+
+use rand::{
+    distributions::{Distribution as _, Uniform},
+    Rng, SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Exp, Normal};
+
+use crate::{
+    answers::Distribution, destination_point, Coord, HaversineData, HaversineDataPoint,
+    EARTH_RADIUS, X_HIGH, X_LOW, Y_HIGH, Y_LOW,
+};
+
+/// Rectangle of coordinates the pairs are drawn from or clamped to, in degrees
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    /// lowest and highest longitude
+    pub x: (f64, f64),
+    /// lowest and highest latitude
+    pub y: (f64, f64),
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self {
+            x: (X_LOW, X_HIGH),
+            y: (Y_LOW, Y_HIGH),
+        }
+    }
+}
+
+impl Bounds {
+    fn clamp(&self, x: f64, y: f64) -> (f64, f64) {
+        (x.clamp(self.x.0, self.x.1), y.clamp(self.y.0, self.y.1))
+    }
+}
+
+/// Dataset to generate, for tests and benchmarks that need one in memory:
+///
+/// ```
+/// use haversine::{answers::Distribution, generator::GeneratorConfig};
+///
+/// let data = GeneratorConfig::new(Distribution::Cluster, 1000).seed(7).generate();
+/// assert_eq!(data.pairs.len(), 1000);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GeneratorConfig {
+    distribution: Distribution,
+    count: usize,
+    seed: u64,
+    bounds: Bounds,
+    gaussian: GaussianParams,
+    exponential: ExponentialParams,
+    hub: HubParams,
+}
+
+impl GeneratorConfig {
+    /// `count` pairs of `distribution` from seed 0, over the whole globe
+    #[must_use]
+    pub fn new(distribution: Distribution, count: usize) -> Self {
+        Self {
+            distribution,
+            count,
+            seed: 0,
+            bounds: Bounds::default(),
+            gaussian: GaussianParams::default(),
+            exponential: ExponentialParams::default(),
+            hub: HubParams::default(),
+        }
+    }
+
+    /// Seed of the generator; the same seed gives the same pairs
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Draws uniform and cluster pairs and the hubs from `bounds` and clamps
+    /// gaussian, exponential and hub pairs to it. Edge cases ignore it, they need the poles and
+    /// the antimeridian.
+    #[must_use]
+    pub fn bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    /// Shape of a gaussian distribution
+    #[must_use]
+    pub fn gaussian(mut self, params: GaussianParams) -> Self {
+        self.gaussian = params;
+        self
+    }
+
+    /// Shape of an exponential distribution
+    #[must_use]
+    pub fn exponential(mut self, params: ExponentialParams) -> Self {
+        self.exponential = params;
+        self
+    }
+
+    /// Hubs of a hub distribution
+    #[must_use]
+    pub fn hub(mut self, params: HubParams) -> Self {
+        self.hub = params;
+        self
+    }
+
+    /// The pairs, the same for the same configuration. With the default
+    /// bounds they match `uniform`, `cluster`, `gaussian`, `exponential`,
+    /// `hub` and `edge_cases` for the same seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bounds are empty or outside the valid coordinate ranges,
+    /// or if the gaussian, exponential or hub parameters are negative or NaN.
+    #[must_use]
+    pub fn generate(&self) -> HaversineData {
+        let Bounds { x, y } = self.bounds;
+        assert!(
+            X_LOW <= x.0
+                && x.0 < x.1
+                && x.1 <= X_HIGH
+                && Y_LOW <= y.0
+                && y.0 < y.1
+                && y.1 <= Y_HIGH,
+            "bounds {:?} are not a rectangle of valid coordinates",
+            self.bounds
+        );
+        let (n, seed, bounds) = (self.count, self.seed, &self.bounds);
+        match self.distribution {
+            Distribution::Uniform => uniform_in(n, seed, bounds),
+            Distribution::Cluster => cluster_in(n, seed, bounds),
+            Distribution::Gaussian => gaussian_in(n, seed, self.gaussian, bounds),
+            Distribution::Exponential => exponential_in(n, seed, self.exponential, bounds),
+            Distribution::Hub => hub_in(n, seed, self.hub, bounds),
+            Distribution::EdgeCases => edge_cases(n, seed),
+        }
+    }
+}
+
+/// `n` pairs drawn uniformly from the valid coordinate ranges
+#[must_use]
+pub fn uniform(n: usize, seed: u64) -> HaversineData {
+    uniform_in(n, seed, &Bounds::default())
+}
+
+fn uniform_in(n: usize, seed: u64, bounds: &Bounds) -> HaversineData {
+    let mut pairs: Vec<HaversineDataPoint> = Vec::with_capacity(n);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let uniform_x = Uniform::new_inclusive(bounds.x.0, bounds.x.1);
+    let uniform_y = Uniform::new_inclusive(bounds.y.0, bounds.y.1);
+    for _ in 0..n {
+        pairs.push(HaversineDataPoint {
+            p0: Coord {
+                lon: uniform_x.sample(&mut rng),
+                lat: uniform_y.sample(&mut rng),
+            },
+            p1: Coord {
+                lon: uniform_x.sample(&mut rng),
+                lat: uniform_y.sample(&mut rng),
+            },
+        });
+    }
+    HaversineData { pairs }
+}
+
+fn distribution_clusters(
+    start: f64,
+    end: f64,
+    parts: usize,
+    rng: &mut impl Rng,
+) -> Vec<Uniform<f64>> {
+    let mut breakpoints: Vec<f64> = (0..parts - 1).map(|_| rng.gen_range(start..end)).collect();
+    breakpoints.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    breakpoints.insert(0, start);
+    breakpoints.push(end);
+
+    breakpoints
+        .windows(2)
+        .map(|range| Uniform::new(range[0], range[1]))
+        .collect()
+}
+
+/// `n` pairs in consecutive runs, each run drawn from its own sub-rectangle of the
+/// coordinate ranges
+#[must_use]
+pub fn cluster(n: usize, seed: u64) -> HaversineData {
+    cluster_in(n, seed, &Bounds::default())
+}
+
+fn cluster_in(n: usize, seed: u64, bounds: &Bounds) -> HaversineData {
+    let mut pairs: Vec<HaversineDataPoint> = Vec::with_capacity(n);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let cluster_size: usize = match n {
+        0..=1000 => 4,
+        1001..=100_000 => 8,
+        100_001..=1_000_000 => 16,
+        1_000_001..=10_000_000 => 32,
+        _ => 64,
+    };
+    debug_assert!(cluster_size.is_power_of_two());
+    #[allow(clippy::pedantic)]
+    let parts = (cluster_size as f64).sqrt() as usize;
+
+    let x_clusters = distribution_clusters(bounds.x.0, bounds.x.1, parts, &mut rng);
+    let y_clusters = distribution_clusters(bounds.y.0, bounds.y.1, parts, &mut rng);
+
+    let step = n.div_ceil(parts);
+
+    for i in 0..n {
+        pairs.push(HaversineDataPoint {
+            p0: Coord {
+                lon: x_clusters[i / step].sample(&mut rng),
+                lat: y_clusters[i / step].sample(&mut rng),
+            },
+            p1: Coord {
+                lon: x_clusters[i / step].sample(&mut rng),
+                lat: y_clusters[i / step].sample(&mut rng),
+            },
+        });
+    }
+    HaversineData { pairs }
+}
+
+/// Mean and standard deviation of each axis of `gaussian`, in degrees
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GaussianParams {
+    /// longitude and latitude
+    pub mean: (f64, f64),
+    /// of the longitude and of the latitude
+    pub std_dev: (f64, f64),
+}
+
+impl Default for GaussianParams {
+    fn default() -> Self {
+        Self {
+            mean: (0.0, 0.0),
+            std_dev: (30.0, 15.0),
+        }
+    }
+}
+
+/// `n` pairs with each coordinate drawn from its axis' normal distribution,
+/// clamped to the valid coordinate ranges
+///
+/// # Panics
+///
+/// Panics if a standard deviation is negative or NaN.
+#[must_use]
+pub fn gaussian(n: usize, seed: u64, params: GaussianParams) -> HaversineData {
+    gaussian_in(n, seed, params, &Bounds::default())
+}
+
+fn gaussian_in(n: usize, seed: u64, params: GaussianParams, bounds: &Bounds) -> HaversineData {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let normal =
+        |mean, std_dev| Normal::new(mean, std_dev).expect("standard deviation is non-negative");
+    let normal_x = normal(params.mean.0, params.std_dev.0);
+    let normal_y = normal(params.mean.1, params.std_dev.1);
+    let pairs = (0..n)
+        .map(|_| {
+            let (x0, y0) = bounds.clamp(normal_x.sample(&mut rng), normal_y.sample(&mut rng));
+            let (x1, y1) = bounds.clamp(normal_x.sample(&mut rng), normal_y.sample(&mut rng));
+            HaversineDataPoint {
+                p0: Coord { lon: x0, lat: y0 },
+                p1: Coord { lon: x1, lat: y1 },
+            }
+        })
+        .collect();
+    HaversineData { pairs }
+}
+
+/// Origin and mean distance from it of `exponential`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExponentialParams {
+    /// longitude and latitude
+    pub origin: (f64, f64),
+    /// along the great circle, in the unit of `EARTH_RADIUS`
+    pub mean_distance: f64,
+}
+
+impl Default for ExponentialParams {
+    fn default() -> Self {
+        Self {
+            origin: (0.0, 0.0),
+            mean_distance: 1000.0,
+        }
+    }
+}
+
+/// `n` pairs of points in a uniformly random direction from the origin, at
+/// exponentially distributed great-circle distances from it
+///
+/// # Panics
+///
+/// Panics if the mean distance is negative or NaN.
+#[must_use]
+pub fn exponential(n: usize, seed: u64, params: ExponentialParams) -> HaversineData {
+    exponential_in(n, seed, params, &Bounds::default())
+}
+
+fn exponential_in(
+    n: usize,
+    seed: u64,
+    params: ExponentialParams,
+    bounds: &Bounds,
+) -> HaversineData {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let distance = Exp::new(params.mean_distance.recip()).expect("mean distance is non-negative");
+    let bearing = Uniform::new(0.0, 360.0);
+    let mut point = || {
+        let bearing = bearing.sample(&mut rng);
+        let distance = distance.sample(&mut rng);
+        let (lon, lat) = destination_point(params.origin, bearing, distance, EARTH_RADIUS);
+        bounds.clamp(lon, lat)
+    };
+    let pairs = (0..n)
+        .map(|_| {
+            let (x0, y0) = point();
+            let (x1, y1) = point();
+            HaversineDataPoint {
+                p0: Coord { lon: x0, lat: y0 },
+                p1: Coord { lon: x1, lat: y1 },
+            }
+        })
+        .collect();
+    HaversineData { pairs }
+}
+
+/// Hubs of `hub` and how far from them its points are
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HubParams {
+    pub hubs: NonZeroUsize,
+    /// largest great-circle distance of a point from its hub, in the unit of
+    /// `EARTH_RADIUS`
+    pub jitter: f64,
+}
+
+impl Default for HubParams {
+    fn default() -> Self {
+        Self {
+            hubs: NonZeroUsize::new(100).expect("100 is not zero"),
+            jitter: 5.0,
+        }
+    }
+}
+
+/// `n` pairs between hubs like flights between airports: `hubs` points are
+/// drawn uniformly from the valid coordinate ranges, then each pair joins two
+/// of them, distinct unless there is only one, with each end moved up to
+/// `jitter` away from its hub in a random direction
+///
+/// # Panics
+///
+/// Panics if the jitter is negative or NaN.
+#[must_use]
+pub fn hub(n: usize, seed: u64, params: HubParams) -> HaversineData {
+    hub_in(n, seed, params, &Bounds::default())
+}
+
+fn hub_in(n: usize, seed: u64, params: HubParams, bounds: &Bounds) -> HaversineData {
+    assert!(params.jitter >= 0.0, "jitter {} is negative", params.jitter);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let uniform_x = Uniform::new_inclusive(bounds.x.0, bounds.x.1);
+    let uniform_y = Uniform::new_inclusive(bounds.y.0, bounds.y.1);
+    let hubs: Vec<_> = (0..params.hubs.get())
+        .map(|_| (uniform_x.sample(&mut rng), uniform_y.sample(&mut rng)))
+        .collect();
+    let bearing = Uniform::new(0.0, 360.0);
+    let near = |hub, rng: &mut ChaCha8Rng| {
+        let bearing = bearing.sample(rng);
+        let distance = params.jitter * rng.gen::<f64>();
+        let (lon, lat) = destination_point(hub, bearing, distance, EARTH_RADIUS);
+        bounds.clamp(lon, lat)
+    };
+    let pairs = (0..n)
+        .map(|_| {
+            let from = rng.gen_range(0..hubs.len());
+            // skips `from`, the other hubs stay equally likely
+            let to = match hubs.len() {
+                1 => 0,
+                len => (from + rng.gen_range(1..len)) % len,
+            };
+            HaversineDataPoint::from((near(hubs[from], &mut rng), near(hubs[to], &mut rng)))
+        })
+        .collect();
+    HaversineData { pairs }
+}
+
+/// Wraps a longitude into `X_LOW..X_HIGH`
+fn wrap_longitude(x: f64) -> f64 {
+    (x - X_LOW).rem_euclid(X_HIGH - X_LOW) + X_LOW
+}
+
+/// `n` numerically hard pairs, each picked at random among: identical
+/// points, antipodes and near antipodes, points a few ulps apart, pairs on
+/// either side of a pole, points on a pole at different longitudes, and
+/// pairs across the ±180° antimeridian, including its two equivalent
+/// longitudes
+#[must_use]
+pub fn edge_cases(n: usize, seed: u64) -> HaversineData {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let uniform_x = Uniform::new_inclusive(X_LOW, X_HIGH);
+    let uniform_y = Uniform::new_inclusive(Y_LOW, Y_HIGH);
+    let near_pole = Uniform::new_inclusive(80.0, Y_HIGH);
+    let near_antimeridian = Uniform::new_inclusive(0.0, 1.0);
+    let mut pairs: Vec<HaversineDataPoint> = Vec::with_capacity(n);
+    for _ in 0..n {
+        let (x, y) = (uniform_x.sample(&mut rng), uniform_y.sample(&mut rng));
+        let (x0, y0, x1, y1) = match rng.gen_range(0..7) {
+            0 => (x, y, x, y),
+            1 => (x, y, wrap_longitude(x + 180.0), -y),
+            2 => {
+                let offset = rng.gen_range(-1e-9..1e-9);
+                let y1 = (offset - y).clamp(Y_LOW, Y_HIGH);
+                (x, y, wrap_longitude(x + 180.0 + offset), y1)
+            }
+            3 => (
+                x,
+                y,
+                x.next_up().min(X_HIGH),
+                y.next_up().next_up().min(Y_HIGH),
+            ),
+            4 => {
+                let pole = if rng.gen() { 1.0 } else { -1.0 };
+                let (y0, y1) = (near_pole.sample(&mut rng), near_pole.sample(&mut rng));
+                (x, pole * y0, wrap_longitude(x + 180.0), pole * y1)
+            }
+            5 => {
+                let pole = if rng.gen() { Y_HIGH } else { Y_LOW };
+                (x, pole, uniform_x.sample(&mut rng), pole)
+            }
+            _ => match rng.gen_range(0..2) {
+                0 => (X_HIGH, y, X_LOW, y),
+                _ => (
+                    X_HIGH - near_antimeridian.sample(&mut rng),
+                    y,
+                    X_LOW + near_antimeridian.sample(&mut rng),
+                    uniform_y.sample(&mut rng),
+                ),
+            },
+        };
+        pairs.push(HaversineDataPoint {
+            p0: Coord { lon: x0, lat: y0 },
+            p1: Coord { lon: x1, lat: y1 },
+        });
+    }
+    HaversineData { pairs }
+}
@@ -0,0 +1,369 @@
+//! wgpu compute shader backend: uploads the coordinate arrays, computes one
+//! distance per invocation and reduces the sum on the device.
+//!
+//! WGSL has no f64 trigonometry, so the kernel runs in f32 and its distances
+//! only agree with the CPU reference to within [`TOLERANCE`].
+
+use std::{fmt, sync::mpsc};
+
+use wgpu::util::DeviceExt;
+
+use crate::soa::HaversineSoA;
+
+/// Max absolute difference from `reference_haversine` accepted on validation,
+/// in the radius' unit. About twice the worst seen on Earth sized uniform and
+/// cluster datasets (0.12 km).
+pub const TOLERANCE: f64 = 0.25;
+
+/// Invocations per workgroup, also the width of each reduction step
+const WORKGROUP_SIZE: u32 = 256;
+/// Workgroups per dispatch dimension guaranteed by the default limits
+const MAX_GROUPS_PER_DIMENSION: u32 = 65535;
+
+const SHADER: &str = r"
+struct Params {
+    count: u32,
+    radius: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> x0: array<f32>;
+@group(0) @binding(2) var<storage, read> y0: array<f32>;
+@group(0) @binding(3) var<storage, read> x1: array<f32>;
+@group(0) @binding(4) var<storage, read> y1: array<f32>;
+@group(0) @binding(5) var<storage, read_write> values: array<f32>;
+@group(0) @binding(6) var<storage, read_write> partials: array<f32>;
+
+var<workgroup> scratch: array<f32, 256>;
+
+fn group_index(id: vec3<u32>, groups: vec3<u32>) -> u32 {
+    return id.y * groups.x + id.x;
+}
+
+// Tree sum of the workgroup's values into partials[group]
+fn reduce(local: u32, group: u32, value: f32) {
+    scratch[local] = value;
+    workgroupBarrier();
+    for (var stride = 128u; stride > 0u; stride = stride / 2u) {
+        if local < stride {
+            scratch[local] += scratch[local + stride];
+        }
+        workgroupBarrier();
+    }
+    if local == 0u {
+        partials[group] = scratch[0];
+    }
+}
+
+@compute @workgroup_size(256)
+fn distance(
+    @builtin(workgroup_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let group = group_index(id, groups);
+    let i = group * 256u + local;
+    var dist = 0.0;
+    if i < params.count {
+        let d_lat = radians(y1[i] - y0[i]);
+        let d_lon = radians(x1[i] - x0[i]);
+        let sin_lat = sin(d_lat / 2.0);
+        let sin_lon = sin(d_lon / 2.0);
+        let a = sin_lat * sin_lat + cos(radians(y0[i])) * cos(radians(y1[i])) * sin_lon * sin_lon;
+        // atan2 rather than the reference's asin, which loses kilometres of
+        // f32 precision on near antipodal pairs
+        dist = params.radius * 2.0 * atan2(sqrt(a), sqrt(max(1.0 - a, 0.0)));
+        values[i] = dist;
+    }
+    reduce(local, group, dist);
+}
+
+@compute @workgroup_size(256)
+fn sum(
+    @builtin(workgroup_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    let group = group_index(id, groups);
+    let i = group * 256u + local;
+    var value = 0.0;
+    if i < params.count {
+        value = values[i];
+    }
+    reduce(local, group, value);
+}
+";
+
+#[derive(Debug)]
+pub enum GpuError {
+    /// No adapter or device could be acquired
+    Unavailable(String),
+    /// More pairs than a dispatch can address
+    TooManyPairs(usize),
+    /// Reading the results back failed
+    Readback(String),
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unavailable(reason) => write!(f, "no GPU available: {reason}"),
+            Self::TooManyPairs(count) => write!(f, "{count} pairs exceed the GPU dispatch limits"),
+            Self::Readback(reason) => write!(f, "failed to read GPU results: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+/// What the device computed
+pub struct GpuOutput {
+    pub sum: f64,
+    /// Per pair distances, empty unless requested
+    pub distances: Vec<f64>,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Params {
+    count: u32,
+    radius: f32,
+}
+
+/// Sums the distances of all pairs on the first available GPU, and reads back
+/// each pair's distance when `read_distances` is set.
+///
+/// # Errors
+///
+/// Returns an error if no GPU is available, the pairs do not fit the device's
+/// limits, or the results cannot be read back.
+pub fn compute(
+    soa: &HaversineSoA,
+    radius: f64,
+    read_distances: bool,
+) -> Result<GpuOutput, GpuError> {
+    let count = u32::try_from(soa.len()).map_err(|_| GpuError::TooManyPairs(soa.len()))?;
+    if count == 0 {
+        return Ok(GpuOutput {
+            sum: 0.0,
+            distances: Vec::new(),
+        });
+    }
+    let (device, queue) = pollster::block_on(request_device())?;
+    let limits = device.limits();
+    let array_size = u64::from(count) * 4;
+    if array_size > limits.max_storage_buffer_binding_size
+        || group_count(count).div_ceil(MAX_GROUPS_PER_DIMENSION) > MAX_GROUPS_PER_DIMENSION
+    {
+        return Err(GpuError::TooManyPairs(soa.len()));
+    }
+
+    let inputs = upload(&device, soa);
+    #[allow(clippy::cast_possible_truncation)]
+    let (sum, values) = dispatch(&device, &queue, &inputs, count, radius as f32);
+    let sum = read_back(&device, &queue, &sum, 1)?[0];
+    let distances = if read_distances {
+        read_back(&device, &queue, &values, count)?
+    } else {
+        Vec::new()
+    };
+    Ok(GpuOutput { sum, distances })
+}
+
+async fn request_device() -> Result<(wgpu::Device, wgpu::Queue), GpuError> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle());
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| GpuError::Unavailable(e.to_string()))?;
+    adapter
+        .request_device(&wgpu::DeviceDescriptor {
+            label: Some("haversine"),
+            // the largest buffers the adapter allows, for large datasets
+            required_limits: adapter.limits(),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| GpuError::Unavailable(e.to_string()))
+}
+
+fn group_count(count: u32) -> u32 {
+    count.div_ceil(WORKGROUP_SIZE)
+}
+
+/// Workgroups to dispatch for `count` invocations, split across two
+/// dimensions once they exceed one dimension's limit
+fn dispatch_size(count: u32) -> (u32, u32) {
+    let groups = group_count(count);
+    let rows = groups.div_ceil(MAX_GROUPS_PER_DIMENSION);
+    (groups.div_ceil(rows), rows)
+}
+
+#[perf::instrument]
+fn upload(device: &wgpu::Device, soa: &HaversineSoA) -> [wgpu::Buffer; 4] {
+    [&soa.x0, &soa.y0, &soa.x1, &soa.y1].map(|coordinates| {
+        #[allow(clippy::cast_possible_truncation)]
+        let coordinates: Vec<f32> = coordinates.iter().map(|&c| c as f32).collect();
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("coordinates"),
+            contents: bytemuck::cast_slice(&coordinates),
+            usage: wgpu::BufferUsages::STORAGE,
+        })
+    })
+}
+
+fn storage_buffer(device: &wgpu::Device, label: &str, count: u32) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: u64::from(count) * 4,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+fn params_buffer(device: &wgpu::Device, count: u32, radius: f32) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("params"),
+        contents: bytemuck::bytes_of(&Params { count, radius }),
+        usage: wgpu::BufferUsages::UNIFORM,
+    })
+}
+
+/// Runs the distance pass and then sum passes until one partial is left.
+/// Returns the buffer holding the sum and the per pair distances.
+#[perf::instrument]
+fn dispatch(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    inputs: &[wgpu::Buffer; 4],
+    count: u32,
+    radius: f32,
+) -> (wgpu::Buffer, wgpu::Buffer) {
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("haversine"),
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let pipeline = |entry_point| {
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: None,
+            module: &module,
+            entry_point: Some(entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        })
+    };
+    let (distance, sum) = (pipeline("distance"), pipeline("sum"));
+
+    let values = storage_buffer(device, "distances", count);
+    let mut partials = storage_buffer(device, "partials", group_count(count));
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+    let params = params_buffer(device, count, radius);
+    let mut entries = vec![wgpu::BindGroupEntry {
+        binding: 0,
+        resource: params.as_entire_binding(),
+    }];
+    for (binding, buffer) in (1..).zip(inputs) {
+        entries.push(wgpu::BindGroupEntry {
+            binding,
+            resource: buffer.as_entire_binding(),
+        });
+    }
+    entries.push(wgpu::BindGroupEntry {
+        binding: 5,
+        resource: values.as_entire_binding(),
+    });
+    entries.push(wgpu::BindGroupEntry {
+        binding: 6,
+        resource: partials.as_entire_binding(),
+    });
+    record_pass(device, &mut encoder, &distance, &entries, count);
+
+    let mut remaining = group_count(count);
+    while remaining > 1 {
+        let input = partials;
+        partials = storage_buffer(device, "partials", group_count(remaining));
+        let params = params_buffer(device, remaining, radius);
+        let entries = [
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: input.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: partials.as_entire_binding(),
+            },
+        ];
+        record_pass(device, &mut encoder, &sum, &entries, remaining);
+        remaining = group_count(remaining);
+    }
+    queue.submit([encoder.finish()]);
+    (partials, values)
+}
+
+fn record_pass(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    pipeline: &wgpu::ComputePipeline,
+    entries: &[wgpu::BindGroupEntry],
+    count: u32,
+) {
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries,
+    });
+    let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, &bind_group, &[]);
+    let (x, y) = dispatch_size(count);
+    pass.dispatch_workgroups(x, y, 1);
+}
+
+/// Copies the first `count` floats of `buffer` to the host
+#[perf::instrument]
+fn read_back(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    count: u32,
+) -> Result<Vec<f64>, GpuError> {
+    let size = u64::from(count) * 4;
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback"),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+    queue.submit([encoder.finish()]);
+
+    let (sender, receiver) = mpsc::channel();
+    staging.map_async(wgpu::MapMode::Read, .., move |result| {
+        // the receiver outlives the poll below
+        let _ = sender.send(result);
+    });
+    device
+        .poll(wgpu::PollType::wait_indefinitely())
+        .map_err(|e| GpuError::Readback(e.to_string()))?;
+    receiver
+        .recv()
+        .map_err(|e| GpuError::Readback(e.to_string()))?
+        .map_err(|e| GpuError::Readback(e.to_string()))?;
+    let view = staging
+        .get_mapped_range(..)
+        .map_err(|e| GpuError::Readback(e.to_string()))?;
+    Ok(bytemuck::cast_slice::<u8, f32>(&view)
+        .iter()
+        .map(|&value| f64::from(value))
+        .collect())
+}
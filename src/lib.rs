@@ -1,19 +1,234 @@
+#![cfg_attr(not(target_arch = "wasm32"), feature(stmt_expr_attributes))]
+#![cfg_attr(not(target_arch = "wasm32"), feature(proc_macro_hygiene))]
+#![cfg_attr(not(target_arch = "wasm32"), feature(portable_simd))]
+
+pub mod answers;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod checkpoint;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compute;
 mod deserializer;
+#[cfg(target_os = "linux")]
+pub mod direct;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+pub mod format;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod generator;
+#[cfg(all(feature = "gpu", not(target_arch = "wasm32")))]
+pub mod gpu;
+pub mod matrix;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod numa;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod pipeline;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod progress;
+mod scanner;
+mod serializer;
+pub mod shard;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod simd;
+#[cfg(all(feature = "simd-parse", not(target_arch = "wasm32")))]
+mod simd_scan;
+pub mod soa;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategy;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub mod uring;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod validation;
 
+pub use deserializer::{
+    DuplicateKeyPolicy, HaversineDataIter, PairBuffer, ParseError, ParseOptions, Token,
+    UnknownKeyPolicy,
+};
+pub use matrix::distance_matrix;
 use serde::{Deserialize, Serialize};
+pub use serializer::JsonWriter;
 
+/// Radius of [`EarthModel::SPHERE_DEFAULT`]
 pub const EARTH_RADIUS: f64 = 6372.8f64;
 pub const X_LOW: f64 = -180f64;
 pub const X_HIGH: f64 = 180f64;
 pub const Y_LOW: f64 = -90f64;
 pub const Y_HIGH: f64 = 90f64;
+/// WGS-84 semi-major axis in km, the unit of `EARTH_RADIUS`
+pub const WGS84_A: f64 = 6378.137f64;
+/// WGS-84 flattening
+pub const WGS84_F: f64 = 1f64 / 298.257_223_563;
+
+/// Shape of the earth distances are computed on, in the unit of its size
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum EarthModel {
+    /// great circle distances by `reference_haversine`
+    Sphere { radius: f64 },
+    /// geodesic distances by `vincenty_distance`, with semi-major axis `a`
+    /// and flattening `f`
+    Ellipsoid { a: f64, f: f64 },
+}
+
+impl EarthModel {
+    pub const SPHERE_DEFAULT: Self = Self::Sphere {
+        radius: EARTH_RADIUS,
+    };
+    pub const WGS84: Self = Self::Ellipsoid {
+        a: WGS84_A,
+        f: WGS84_F,
+    };
+
+    /// Distance between the two points of `point`. Vincenty's formula does
+    /// not converge for nearly antipodal points, which are measured on the
+    /// sphere of `mean_radius` instead.
+    #[must_use]
+    pub fn distance(&self, point: &HaversineDataPoint) -> f64 {
+        match *self {
+            Self::Sphere { radius } => reference_haversine(point, radius),
+            Self::Ellipsoid { a, f } => vincenty_distance(point, a, f)
+                .unwrap_or_else(|_| reference_haversine(point, self.mean_radius())),
+        }
+    }
+
+    /// Radius of the sphere, or the mean radius `(2a + b) / 3` of the ellipsoid
+    #[must_use]
+    pub fn mean_radius(&self) -> f64 {
+        match *self {
+            Self::Sphere { radius } => radius,
+            Self::Ellipsoid { a, f } => (3.0 - f) * a / 3.0,
+        }
+    }
+}
+
+impl Default for EarthModel {
+    fn default() -> Self {
+        Self::SPHERE_DEFAULT
+    }
+}
+
+impl std::fmt::Display for EarthModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Sphere { radius } => write!(f, "sphere of radius {radius}"),
+            // a sphere of radius `a`, which `1/inf` would obscure
+            Self::Ellipsoid { a, f: flattening } if *flattening == 0.0 => {
+                write!(f, "ellipsoid of semi-major axis {a} and flattening 0")
+            }
+            Self::Ellipsoid { a, f: flattening } => {
+                write!(
+                    f,
+                    "ellipsoid of semi-major axis {a} and flattening 1/{}",
+                    1.0 / flattening
+                )
+            }
+        }
+    }
+}
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+/// `sphere`, `sphere=<radius>`, `wgs84` or `ellipsoid=<a>,<f>`
+impl std::str::FromStr for EarthModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite() && *value >= 0.0)
+                .ok_or_else(|| format!("`{value}` is not a non-negative number"))
+        };
+        match s.split_once('=') {
+            None if s == "sphere" => Ok(Self::SPHERE_DEFAULT),
+            None if s == "wgs84" => Ok(Self::WGS84),
+            Some(("sphere", radius)) => Ok(Self::Sphere {
+                radius: number(radius)?,
+            }),
+            Some(("ellipsoid", axes)) => {
+                let (a, f) = axes
+                    .split_once(',')
+                    .ok_or_else(|| format!("expected ellipsoid=<a>,<f>, got `{s}`"))?;
+                // the semi-minor axis `a(1 - f)` has to be positive
+                let f = number(f)?;
+                if f >= 1.0 {
+                    return Err(format!("flattening `{f}` is not less than 1"));
+                }
+                Ok(Self::Ellipsoid { a: number(a)?, f })
+            }
+            _ => Err(format!(
+                "unknown model `{s}`, expected sphere, sphere=<radius>, wgs84 or ellipsoid=<a>,<f>"
+            )),
+        }
+    }
+}
+
+/// A point, in degrees
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[repr(C)]
+pub struct Coord {
+    pub lon: f64,
+    pub lat: f64,
+}
+
+/// Two points, serialized flat as `{"x0": .., "y0": .., "x1": .., "y1": ..}`
+/// with `p0` as `x0`, `y0` and `p1` as `x1`, `y1`, and laid out as those four
+/// `f64`s in that order
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(from = "FlatDataPoint", into = "FlatDataPoint")]
+#[repr(C)]
 pub struct HaversineDataPoint {
-    pub x0: f64,
-    pub y0: f64,
-    pub x1: f64,
-    pub y1: f64,
+    pub p0: Coord,
+    pub p1: Coord,
+}
+
+/// The JSON shape of `HaversineDataPoint`
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "HaversineDataPoint")]
+struct FlatDataPoint {
+    x0: f64,
+    y0: f64,
+    x1: f64,
+    y1: f64,
+}
+
+impl From<FlatDataPoint> for HaversineDataPoint {
+    fn from(FlatDataPoint { x0, y0, x1, y1 }: FlatDataPoint) -> Self {
+        ((x0, y0), (x1, y1)).into()
+    }
+}
+
+impl From<HaversineDataPoint> for FlatDataPoint {
+    fn from(HaversineDataPoint { p0, p1 }: HaversineDataPoint) -> Self {
+        Self {
+            x0: p0.lon,
+            y0: p0.lat,
+            x1: p1.lon,
+            y1: p1.lat,
+        }
+    }
+}
+
+/// From `(lon, lat)`
+impl From<(f64, f64)> for Coord {
+    fn from((lon, lat): (f64, f64)) -> Self {
+        Self { lon, lat }
+    }
+}
+
+impl From<Coord> for (f64, f64) {
+    fn from(Coord { lon, lat }: Coord) -> Self {
+        (lon, lat)
+    }
+}
+
+/// From `((x0, y0), (x1, y1))`
+impl From<((f64, f64), (f64, f64))> for HaversineDataPoint {
+    fn from((p0, p1): ((f64, f64), (f64, f64))) -> Self {
+        Self {
+            p0: p0.into(),
+            p1: p1.into(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
@@ -21,13 +236,84 @@ pub struct HaversineData {
     pub pairs: Vec<HaversineDataPoint>,
 }
 
+/// A coordinate outside its range, or NaN
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoordError {
+    /// `x0` or `x1` outside `X_LOW..=X_HIGH`
+    Longitude { name: &'static str, value: f64 },
+    /// `y0` or `y1` outside `Y_LOW..=Y_HIGH`
+    Latitude { name: &'static str, value: f64 },
+}
+
+impl std::fmt::Display for CoordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Longitude { name, value } => {
+                write!(
+                    f,
+                    "{name} = {value} is not a longitude in {X_LOW}..={X_HIGH}"
+                )
+            }
+            Self::Latitude { name, value } => {
+                write!(
+                    f,
+                    "{name} = {value} is not a latitude in {Y_LOW}..={Y_HIGH}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CoordError {}
+
+impl HaversineDataPoint {
+    /// # Errors
+    ///
+    /// Returns the first coordinate outside its range, see [`Self::check_range`].
+    pub fn new_checked(x0: f64, y0: f64, x1: f64, y1: f64) -> Result<Self, CoordError> {
+        let point = Self::from(((x0, y0), (x1, y1)));
+        point.check_range().map(|()| point)
+    }
+
+    /// # Errors
+    ///
+    /// Returns the first of `x0`, `y0`, `x1` and `y1` outside
+    /// `X_LOW..=X_HIGH` or `Y_LOW..=Y_HIGH`.
+    pub fn check_range(&self) -> Result<(), CoordError> {
+        for (name, value) in [("x0", self.p0.lon), ("x1", self.p1.lon)] {
+            if !(X_LOW..=X_HIGH).contains(&value) {
+                return Err(CoordError::Longitude { name, value });
+            }
+        }
+        for (name, value) in [("y0", self.p0.lat), ("y1", self.p1.lat)] {
+            if !(Y_LOW..=Y_HIGH).contains(&value) {
+                return Err(CoordError::Latitude { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HaversineData {
+    /// Indices of the pairs with a coordinate out of range
+    #[must_use]
+    pub fn validate(&self) -> Vec<usize> {
+        self.pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, point)| point.check_range().is_err())
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
 // Reference: https://github.com/cmuratori/computer_enhance/blob/a6e9cb2a7b57e450ba2e7b75d0fd3e36ffa72d7d/perfaware/part2/listing_0065_haversine_formula.cpp
 #[must_use]
 pub fn reference_haversine(point: &HaversineDataPoint, radius: f64) -> f64 {
-    let lat1 = point.y0;
-    let lat2 = point.y1;
-    let lon1 = point.x0;
-    let lon2 = point.x1;
+    let lat1 = point.p0.lat;
+    let lat2 = point.p1.lat;
+    let lon1 = point.p0.lon;
+    let lon2 = point.p1.lon;
 
     let d_lat = (lat2 - lat1).to_radians();
     let d_lon = (lon2 - lon1).to_radians();
@@ -37,7 +323,533 @@ pub fn reference_haversine(point: &HaversineDataPoint, radius: f64) -> f64 {
     let a = (d_lat / 2.0).sin().powf(2.0)
         + lat1_rad.cos() * lat2_rad.cos() * ((d_lon / 2.0).sin().powf(2.0));
 
-    let c = 2.0 * a.sqrt().asin();
+    // antipodal points can round `a` above 1, out of asin's domain
+    let c = 2.0 * a.min(1.0).sqrt().asin();
 
     radius * c
 }
+
+/// Direction at `(x0, y0)` of the great circle to `(x1, y1)`, in degrees
+/// clockwise from north in `0..360`
+// Reference: https://www.movable-type.co.uk/scripts/latlong.html
+#[must_use]
+pub fn initial_bearing(point: &HaversineDataPoint) -> f64 {
+    let lat1 = point.p0.lat.to_radians();
+    let lat2 = point.p1.lat.to_radians();
+    let d_lon = (point.p1.lon - point.p0.lon).to_radians();
+
+    let y = d_lon.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lon.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Direction at `(x1, y1)` of the great circle from `(x0, y0)`, in degrees
+/// clockwise from north in `0..360`
+#[must_use]
+pub fn final_bearing(point: &HaversineDataPoint) -> f64 {
+    let reverse = HaversineDataPoint {
+        p0: point.p1,
+        p1: point.p0,
+    };
+    (initial_bearing(&reverse) + 180.0).rem_euclid(360.0)
+}
+
+/// The point `distance` away from `origin` (longitude, latitude) along the
+/// great circle leaving it at `bearing` degrees, on a sphere of `radius`
+/// in the unit of `distance`, as (longitude, latitude) with the longitude in
+/// `X_LOW..X_HIGH`.
+#[must_use]
+pub fn destination_point(
+    origin: (f64, f64),
+    bearing: f64,
+    distance: f64,
+    radius: f64,
+) -> (f64, f64) {
+    let (x0, y0) = origin;
+    let lat1 = y0.to_radians();
+    let bearing = bearing.to_radians();
+    let angle = distance / radius;
+
+    let lat2 = (lat1.sin() * angle.cos() + lat1.cos() * angle.sin() * bearing.cos()).asin();
+    let d_lon =
+        (bearing.sin() * angle.sin() * lat1.cos()).atan2(angle.cos() - lat1.sin() * lat2.sin());
+    (
+        (x0 + d_lon.to_degrees() - X_LOW).rem_euclid(X_HIGH - X_LOW) + X_LOW,
+        lat2.to_degrees(),
+    )
+}
+
+/// The point `fraction` of the way along the great circle from `(x0, y0)` to
+/// `(x1, y1)`, as (longitude, latitude) with the longitude in `X_LOW..=X_HIGH`.
+/// `fraction` outside `0..=1` extrapolates along the same circle. The circle
+/// is not unique for antipodal points, which yield NaN, and so do points within
+/// a few millimeters on Earth of being antipodal.
+// Reference: https://www.movable-type.co.uk/scripts/latlong.html
+#[must_use]
+pub fn interpolate(point: &HaversineDataPoint, fraction: f64) -> (f64, f64) {
+    let angle = reference_haversine(point, 1.0);
+    if angle == 0.0 {
+        return (point.p0.lon, point.p0.lat);
+    }
+    // `sin(π)` isn't zero, it would scale rounding errors into an arbitrary point
+    if angle > std::f64::consts::FRAC_PI_2 && angle.sin() < 1e-9 {
+        return (f64::NAN, f64::NAN);
+    }
+    let (lon1, lat1) = (point.p0.lon.to_radians(), point.p0.lat.to_radians());
+    let (lon2, lat2) = (point.p1.lon.to_radians(), point.p1.lat.to_radians());
+    // slerp weights of the two endpoints as unit vectors
+    let start = ((1.0 - fraction) * angle).sin() / angle.sin();
+    let end = (fraction * angle).sin() / angle.sin();
+
+    let x = start * lat1.cos() * lon1.cos() + end * lat2.cos() * lon2.cos();
+    let y = start * lat1.cos() * lon1.sin() + end * lat2.cos() * lon2.sin();
+    let z = start * lat1.sin() + end * lat2.sin();
+    let lat = z.atan2(x.hypot(y));
+    let lon = y.atan2(x);
+    (lon.to_degrees(), lat.to_degrees())
+}
+
+/// Halfway point of the great circle from `(x0, y0)` to `(x1, y1)`, see `interpolate`
+#[must_use]
+pub fn midpoint(point: &HaversineDataPoint) -> (f64, f64) {
+    interpolate(point, 0.5)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VincentyError {
+    /// the iteration did not settle, which happens for nearly antipodal points
+    NoConvergence,
+}
+
+impl std::fmt::Display for VincentyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoConvergence => write!(f, "Vincenty's formula failed to converge"),
+        }
+    }
+}
+
+impl std::error::Error for VincentyError {}
+
+/// Distance on the ellipsoid with semi-major axis `a` and flattening `f`, in
+/// the unit of `a`, by Vincenty's inverse formula
+///
+/// # Errors
+///
+/// Returns [`VincentyError::NoConvergence`] for nearly antipodal points.
+// Reference: https://www.movable-type.co.uk/scripts/latlong-vincenty.html
+#[allow(clippy::many_single_char_names, clippy::similar_names)]
+pub fn vincenty_distance(point: &HaversineDataPoint, a: f64, f: f64) -> Result<f64, VincentyError> {
+    const MAX_ITERATIONS: usize = 200;
+
+    let b = (1.0 - f) * a;
+    // longitude difference in -180..180, the short way around
+    let l = ((point.p1.lon - point.p0.lon + 180.0).rem_euclid(360.0) - 180.0).to_radians();
+    let u1 = ((1.0 - f) * point.p0.lat.to_radians().tan()).atan();
+    let u2 = ((1.0 - f) * point.p1.lat.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..MAX_ITERATIONS {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0.0 {
+            // coincident points
+            return Ok(0.0);
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos_sq_alpha = 1.0 - sin_alpha * sin_alpha;
+        // zero on equatorial lines, where cos_sq_alpha is zero too
+        let cos_2sigma_m = if cos_sq_alpha == 0.0 {
+            0.0
+        } else {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha
+        };
+        let c = f / 16.0 * cos_sq_alpha * (4.0 + f * (4.0 - 3.0 * cos_sq_alpha));
+        let previous = lambda;
+        lambda = l
+            + (1.0 - c)
+                * f
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+        if lambda.abs() > std::f64::consts::PI {
+            return Err(VincentyError::NoConvergence);
+        }
+        if (lambda - previous).abs() < 1e-12 {
+            let u_sq = cos_sq_alpha * (a * a - b * b) / (b * b);
+            let big_a =
+                1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+            let big_b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+            let delta_sigma = big_b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + big_b / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                            - big_b / 6.0
+                                * cos_2sigma_m
+                                * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+            return Ok(b * big_a * (sigma - delta_sigma));
+        }
+    }
+    Err(VincentyError::NoConvergence)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    fn dms(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+        degrees.signum() * (degrees.abs() + minutes / 60.0 + seconds / 3600.0)
+    }
+
+    #[test]
+    fn data_point_keeps_its_json_shape() {
+        let point = HaversineDataPoint::from(((1.5, -2.0), (3.0, 4.25)));
+        assert_eq!(
+            point.p1,
+            Coord {
+                lon: 3.0,
+                lat: 4.25
+            }
+        );
+        assert_eq!(<(f64, f64)>::from(point.p0), (1.5, -2.0));
+        let json = r#"{"x0":1.5,"y0":-2.0,"x1":3.0,"y1":4.25}"#;
+        assert_eq!(serde_json::to_string(&point).unwrap(), json);
+        assert_eq!(
+            serde_json::from_str::<HaversineDataPoint>(json).unwrap(),
+            point
+        );
+        assert_eq!(size_of::<HaversineDataPoint>(), 4 * size_of::<f64>());
+    }
+
+    #[test]
+    fn vincenty_matches_published_geodesics() {
+        // Flinders Peak to Buninyong, from Vincenty's paper as reproduced by Geoscience Australia
+        let point = HaversineDataPoint {
+            p0: Coord {
+                lon: dms(144.0, 25.0, 29.524_40),
+                lat: dms(-37.0, 57.0, 3.720_30),
+            },
+            p1: Coord {
+                lon: dms(143.0, 55.0, 35.383_90),
+                lat: dms(-37.0, 39.0, 10.156_10),
+            },
+        };
+        let distance = vincenty_distance(&point, WGS84_A * 1000.0, WGS84_F).unwrap();
+        assert!((distance - 54_972.271).abs() < 1e-3, "{distance}");
+
+        // A quarter meridian is 10001.965729 km on WGS-84
+        let meridian = HaversineDataPoint {
+            p0: Coord { lon: 0.0, lat: 0.0 },
+            p1: Coord {
+                lon: 0.0,
+                lat: 90.0,
+            },
+        };
+        let distance = vincenty_distance(&meridian, WGS84_A, WGS84_F).unwrap();
+        assert!((distance - 10_001.965_729).abs() < 1e-6, "{distance}");
+
+        // Across the antimeridian, 1° of longitude on the equator
+        let across = HaversineDataPoint {
+            p0: Coord {
+                lon: 179.5,
+                lat: 0.0,
+            },
+            p1: Coord {
+                lon: -179.5,
+                lat: 0.0,
+            },
+        };
+        let distance = vincenty_distance(&across, WGS84_A, WGS84_F).unwrap();
+        assert!((distance - 111.319_491).abs() < 1e-6, "{distance}");
+
+        let same = HaversineDataPoint {
+            p0: Coord {
+                lon: 12.5,
+                lat: -33.25,
+            },
+            p1: Coord {
+                lon: 12.5,
+                lat: -33.25,
+            },
+        };
+        assert_eq!(vincenty_distance(&same, WGS84_A, WGS84_F), Ok(0.0));
+    }
+
+    #[test]
+    fn bearings_follow_the_great_circle() {
+        let point = |x0, y0, x1, y1| HaversineDataPoint {
+            p0: Coord { lon: x0, lat: y0 },
+            p1: Coord { lon: x1, lat: y1 },
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        assert!(close(initial_bearing(&point(0.0, 0.0, 0.0, 10.0)), 0.0));
+        assert!(close(initial_bearing(&point(0.0, 0.0, 10.0, 0.0)), 90.0));
+        assert!(close(initial_bearing(&point(0.0, 10.0, 0.0, 0.0)), 180.0));
+        // across the antimeridian, the short way round
+        assert!(close(
+            initial_bearing(&point(179.5, 0.0, -179.5, 0.0)),
+            90.0
+        ));
+        // a great circle from the equator at 45° reaches 45°N heading east
+        let to_vertex = point(0.0, 0.0, 90.0, 45.0);
+        assert!(close(initial_bearing(&to_vertex), 45.0));
+        assert!(close(final_bearing(&to_vertex), 90.0));
+
+        // and from a vertex it leaves heading east, crossing the equator at 135°
+        let mirrored = point(-90.0, 45.0, 0.0, 0.0);
+        assert!(close(initial_bearing(&mirrored), 90.0));
+        assert!(close(final_bearing(&mirrored), 135.0));
+        let back = point(0.0, 0.0, -90.0, 45.0);
+        assert!(close(final_bearing(&back), 270.0));
+    }
+
+    #[test]
+    fn destination_point_inverts_distance_and_bearing() {
+        let origin = (-0.127_8, 51.507_4);
+        for bearing in [0.0, 37.5, 90.0, 180.0, 271.25] {
+            for distance in [1.0, 500.0, 9000.0] {
+                let destination = destination_point(origin, bearing, distance, EARTH_RADIUS);
+                let pair = HaversineDataPoint::from((origin, destination));
+                let computed = reference_haversine(&pair, EARTH_RADIUS);
+                assert!((computed - distance).abs() < 1e-6, "{bearing} {distance}");
+                let turn = (initial_bearing(&pair) - bearing + 180.0).rem_euclid(360.0) - 180.0;
+                assert!(turn.abs() < 1e-6, "{bearing} {distance}");
+            }
+        }
+
+        // a quarter of the equator eastwards from 135°E wraps to 135°W
+        let (lon, lat) =
+            destination_point((135.0, 0.0), 90.0, EARTH_RADIUS * PI / 2.0, EARTH_RADIUS);
+        assert!(
+            (lon + 135.0).abs() < 1e-9 && lat.abs() < 1e-9,
+            "{lon} {lat}"
+        );
+    }
+
+    #[test]
+    fn interpolation_stays_on_the_great_circle() {
+        let close = |(x0, y0): (f64, f64), (x1, y1): (f64, f64)| {
+            (x0 - x1).abs() < 1e-9 && (y0 - y1).abs() < 1e-9
+        };
+        let equator = HaversineDataPoint {
+            p0: Coord { lon: 0.0, lat: 0.0 },
+            p1: Coord {
+                lon: 90.0,
+                lat: 0.0,
+            },
+        };
+        assert!(close(midpoint(&equator), (45.0, 0.0)));
+        let antimeridian = HaversineDataPoint {
+            p0: Coord {
+                lon: 170.0,
+                lat: 0.0,
+            },
+            p1: Coord {
+                lon: -170.0,
+                lat: 0.0,
+            },
+        };
+        assert!(close(midpoint(&antimeridian), (180.0, 0.0)));
+        let same = HaversineDataPoint {
+            p0: Coord {
+                lon: 12.5,
+                lat: -40.0,
+            },
+            p1: Coord {
+                lon: 12.5,
+                lat: -40.0,
+            },
+        };
+        assert_eq!(interpolate(&same, 0.3), (12.5, -40.0));
+
+        let pair = HaversineDataPoint {
+            p0: Coord {
+                lon: -0.127_8,
+                lat: 51.507_4,
+            },
+            p1: Coord {
+                lon: 139.691_7,
+                lat: 35.689_5,
+            },
+        };
+        assert!(close(interpolate(&pair, 0.0), (pair.p0.lon, pair.p0.lat)));
+        assert!(close(interpolate(&pair, 1.0), (pair.p1.lon, pair.p1.lat)));
+        let total = reference_haversine(&pair, EARTH_RADIUS);
+        let bearing = initial_bearing(&pair);
+        for fraction in [0.1, 0.25, 0.5, 0.9] {
+            let (x1, y1) = interpolate(&pair, fraction);
+            let partial = HaversineDataPoint {
+                p1: Coord { lon: x1, lat: y1 },
+                ..pair
+            };
+            let distance = reference_haversine(&partial, EARTH_RADIUS);
+            assert!((distance - fraction * total).abs() < 1e-6, "{fraction}");
+            assert!(
+                (initial_bearing(&partial) - bearing).abs() < 1e-6,
+                "{fraction}"
+            );
+        }
+        // extrapolated past either end, behind the start for negative fractions
+        for fraction in [-0.5, 1.5] {
+            let (x1, y1) = interpolate(&pair, fraction);
+            let partial = HaversineDataPoint {
+                p1: Coord { lon: x1, lat: y1 },
+                ..pair
+            };
+            let distance = reference_haversine(&partial, EARTH_RADIUS);
+            assert!(
+                (distance - fraction.abs() * total).abs() < 1e-6,
+                "{fraction}"
+            );
+            let reversed = if fraction < 0.0 { 180.0 } else { 0.0 };
+            let turn = (initial_bearing(&partial) - bearing - reversed + 180.0).rem_euclid(360.0);
+            assert!((turn - 180.0).abs() < 1e-6, "{fraction}");
+        }
+
+        let antipodes = HaversineDataPoint {
+            p0: Coord { lon: 0.0, lat: 0.0 },
+            p1: Coord {
+                lon: 180.0,
+                lat: 0.0,
+            },
+        };
+        let (x, y) = interpolate(&antipodes, 0.5);
+        assert!(x.is_nan() && y.is_nan());
+        let nearly = HaversineDataPoint {
+            p1: Coord {
+                lon: 179.0,
+                lat: 0.0,
+            },
+            ..antipodes
+        };
+        assert!(close(interpolate(&nearly, 0.5), (89.5, 0.0)));
+    }
+
+    #[test]
+    fn coordinates_are_range_checked() {
+        assert!(HaversineDataPoint::new_checked(X_LOW, Y_LOW, X_HIGH, Y_HIGH).is_ok());
+        assert_eq!(
+            HaversineDataPoint::new_checked(0.0, 0.0, 180.5, 0.0),
+            Err(CoordError::Longitude {
+                name: "x1",
+                value: 180.5
+            })
+        );
+        assert_eq!(
+            HaversineDataPoint::new_checked(0.0, -90.5, 0.0, 0.0),
+            Err(CoordError::Latitude {
+                name: "y0",
+                value: -90.5
+            })
+        );
+        assert!(HaversineDataPoint::new_checked(f64::NAN, 0.0, 0.0, 0.0).is_err());
+
+        let point = |x0| HaversineDataPoint {
+            p0: Coord { lon: x0, lat: 0.0 },
+            p1: Coord { lon: 0.0, lat: 0.0 },
+        };
+        let data = HaversineData {
+            pairs: vec![
+                point(0.0),
+                point(-181.0),
+                point(180.0),
+                point(f64::INFINITY),
+            ],
+        };
+        assert_eq!(data.validate(), [1, 3]);
+    }
+
+    #[test]
+    fn vincenty_fails_for_nearly_antipodal_points() {
+        let point = HaversineDataPoint {
+            p0: Coord { lon: 0.0, lat: 0.0 },
+            p1: Coord {
+                lon: 179.7,
+                lat: 0.5,
+            },
+        };
+        assert_eq!(
+            vincenty_distance(&point, WGS84_A, WGS84_F),
+            Err(VincentyError::NoConvergence)
+        );
+    }
+
+    #[test]
+    fn earth_models_parse_and_measure() {
+        assert_eq!("sphere".parse(), Ok(EarthModel::SPHERE_DEFAULT));
+        assert_eq!(
+            "sphere=6371".parse(),
+            Ok(EarthModel::Sphere { radius: 6371.0 })
+        );
+        assert_eq!("wgs84".parse(), Ok(EarthModel::WGS84));
+        assert_eq!(
+            "ellipsoid=6378.137,0.0033528106647474805".parse(),
+            Ok(EarthModel::WGS84)
+        );
+        for invalid in [
+            "sphere=",
+            "sphere=-1",
+            "ellipsoid=1",
+            "ellipsoid=6378,1",
+            "ellipsoid=6378,2.5",
+            "mars",
+        ] {
+            assert!(invalid.parse::<EarthModel>().is_err(), "{invalid}");
+        }
+        assert_eq!(
+            "ellipsoid=6378,0"
+                .parse::<EarthModel>()
+                .unwrap()
+                .to_string(),
+            "ellipsoid of semi-major axis 6378 and flattening 0"
+        );
+        assert_eq!(
+            EarthModel::Ellipsoid { a: 1.0, f: 0.25 }.to_string(),
+            "ellipsoid of semi-major axis 1 and flattening 1/4"
+        );
+
+        let meridian = HaversineDataPoint {
+            p0: Coord { lon: 0.0, lat: 0.0 },
+            p1: Coord {
+                lon: 0.0,
+                lat: 90.0,
+            },
+        };
+        assert_eq!(
+            EarthModel::SPHERE_DEFAULT.distance(&meridian).to_bits(),
+            reference_haversine(&meridian, EARTH_RADIUS).to_bits()
+        );
+        assert_eq!(
+            EarthModel::WGS84.distance(&meridian).to_bits(),
+            vincenty_distance(&meridian, WGS84_A, WGS84_F)
+                .unwrap()
+                .to_bits()
+        );
+        // where Vincenty gives up, the mean sphere stands in
+        let antipodal = HaversineDataPoint {
+            p0: Coord { lon: 0.0, lat: 0.0 },
+            p1: Coord {
+                lon: 179.7,
+                lat: 0.5,
+            },
+        };
+        let mean_radius = EarthModel::WGS84.mean_radius();
+        assert!((mean_radius - 6371.0088).abs() < 1e-4, "{mean_radius}");
+        assert_eq!(
+            EarthModel::WGS84.distance(&antipodal).to_bits(),
+            reference_haversine(&antipodal, mean_radius).to_bits()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! Distances between every two of a set of points, e.g. the endpoints of a
+//! generated dataset, without writing the O(n²) loop by hand.
+
+use crate::Coord;
+
+/// Points per side of the tiles the matrix is computed in. The columns of a
+/// tile (24 bytes each) stay in L1 while its rows go over them.
+const BLOCK: usize = 256;
+
+/// Entries of the packed matrix of `n` points, `n * (n - 1) / 2`
+///
+/// # Panics
+///
+/// Panics if the count overflows `usize`.
+#[must_use]
+pub fn packed_len(n: usize) -> usize {
+    n.checked_mul(n.saturating_sub(1))
+        .expect("distance matrix size overflows usize")
+        / 2
+}
+
+/// Position of the distance between points `i` and `j` (in either order,
+/// `i != j`) in the matrix of `n` points returned by `distance_matrix`
+#[must_use]
+pub fn packed_index(n: usize, i: usize, j: usize) -> usize {
+    debug_assert!(i != j && i < n && j < n, "no entry for ({i}, {j}) of {n}");
+    let (i, j) = (i.min(j), i.max(j));
+    // the rows before `i` hold n - 1, n - 2, ... n - i entries
+    i * (2 * n - i - 1) / 2 + (j - i - 1)
+}
+
+/// Great circle distance between every two `points` on a sphere of `radius`,
+/// as the upper triangle of the matrix without its zero diagonal packed row
+/// by row: (0, 1), (0, 2) .. (0, n - 1), (1, 2) .. (n - 2, n - 1). See
+/// `packed_index`. Each distance is `reference_haversine` of the pair from
+/// the point of the lower index, bit for bit.
+///
+/// The matrix is computed in tiles of `BLOCK` by `BLOCK` points after the
+/// cosines of the latitudes are taken once per point, rather than once per
+/// pair.
+///
+/// # Panics
+///
+/// Panics if the matrix would have more than `usize::MAX` entries.
+#[must_use]
+#[cfg_attr(not(target_arch = "wasm32"), perf::instrument)]
+pub fn distance_matrix(points: &[Coord], radius: f64) -> Vec<f64> {
+    let n = points.len();
+    let latitude_cosines: Vec<f64> = points
+        .iter()
+        .map(|point| point.lat.to_radians().cos())
+        .collect();
+    let mut matrix = vec![0.0; packed_len(n)];
+    for rows in (0..n).step_by(BLOCK) {
+        for columns in (rows..n).step_by(BLOCK) {
+            let column_end = (columns + BLOCK).min(n);
+            for i in rows..(rows + BLOCK).min(n) {
+                let first = columns.max(i + 1);
+                if first >= column_end {
+                    continue;
+                }
+                let row = packed_index(n, i, first);
+                let out = &mut matrix[row..row + column_end - first];
+                let (p0, cos_lat0) = (points[i], latitude_cosines[i]);
+                for ((dist, p1), &cos_lat1) in out
+                    .iter_mut()
+                    .zip(&points[first..column_end])
+                    .zip(&latitude_cosines[first..column_end])
+                {
+                    // `reference_haversine` with the cosines taken out
+                    let d_lat = (p1.lat - p0.lat).to_radians();
+                    let d_lon = (p1.lon - p0.lon).to_radians();
+                    let a = (d_lat / 2.0).sin().powf(2.0)
+                        + cos_lat0 * cos_lat1 * ((d_lon / 2.0).sin().powf(2.0));
+                    *dist = radius * (2.0 * a.min(1.0).sqrt().asin());
+                }
+            }
+        }
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{reference_haversine, HaversineDataPoint, EARTH_RADIUS};
+
+    #[test]
+    fn matches_reference_pair_by_pair() {
+        // across several tiles, with a partial one at the end
+        let n = 2 * BLOCK + 37;
+        #[allow(clippy::cast_precision_loss)]
+        let points: Vec<Coord> = (0..n)
+            .map(|i| {
+                let t = i as f64;
+                Coord {
+                    lon: (t * 37.77).rem_euclid(360.0) - 180.0,
+                    lat: (t * 13.31).rem_euclid(180.0) - 90.0,
+                }
+            })
+            .collect();
+        let matrix = distance_matrix(&points, EARTH_RADIUS);
+        assert_eq!(matrix.len(), n * (n - 1) / 2);
+
+        let mut expected = Vec::with_capacity(matrix.len());
+        for i in 0..n {
+            for j in i + 1..n {
+                assert_eq!(packed_index(n, i, j), expected.len());
+                assert_eq!(packed_index(n, j, i), expected.len());
+                let point = HaversineDataPoint {
+                    p0: points[i],
+                    p1: points[j],
+                };
+                expected.push(reference_haversine(&point, EARTH_RADIUS));
+            }
+        }
+        assert!(matrix
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| a.to_bits() == b.to_bits()));
+
+        assert!(distance_matrix(&[], EARTH_RADIUS).is_empty());
+        assert!(distance_matrix(&points[..1], EARTH_RADIUS).is_empty());
+    }
+}
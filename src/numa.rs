@@ -0,0 +1,134 @@
+//! Binds the process's threads and memory to one NUMA node and reads the
+//! node's allocation counters, so runs on multi-socket machines are comparable.
+
+use std::{fmt, io};
+
+/// Allocation counters of a node from its `numastat`, in pages. They are node
+/// wide, other processes allocating meanwhile show up in them too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeStat {
+    /// Allocated here by a thread running on this node
+    pub local: u64,
+    /// Allocated here by a thread running on another node
+    pub remote: u64,
+    /// Intended for another node but allocated here
+    pub miss: u64,
+}
+
+impl NodeStat {
+    /// Current counters of `node`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the node does not exist or NUMA is not supported.
+    pub fn read(node: u32) -> io::Result<Self> {
+        let stats =
+            std::fs::read_to_string(format!("/sys/devices/system/node/node{node}/numastat"))?;
+        let mut stat = Self::default();
+        for line in stats.lines() {
+            let Some((name, value)) = line.split_once(' ') else {
+                continue;
+            };
+            let value = value.trim().parse().map_err(io::Error::other)?;
+            match name {
+                "local_node" => stat.local = value,
+                "other_node" => stat.remote = value,
+                "numa_miss" => stat.miss = value,
+                _ => {}
+            }
+        }
+        Ok(stat)
+    }
+
+    /// Counters accumulated since `earlier`
+    #[must_use]
+    pub fn since(&self, earlier: &Self) -> Self {
+        Self {
+            local: self.local.saturating_sub(earlier.local),
+            remote: self.remote.saturating_sub(earlier.remote),
+            miss: self.miss.saturating_sub(earlier.miss),
+        }
+    }
+}
+
+impl fmt::Display for NodeStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} local, {} remote, {} missed pages",
+            self.local, self.remote, self.miss
+        )
+    }
+}
+
+/// Restricts the calling thread, and threads it spawns afterwards, to the CPUs
+/// of `node`, and their future allocations to its memory.
+///
+/// # Errors
+///
+/// Returns an error if the node does not exist, has no CPUs, or the kernel
+/// refuses the affinity or memory policy.
+#[cfg(target_os = "linux")]
+pub fn bind(node: u32) -> io::Result<()> {
+    let cpus = std::fs::read_to_string(format!("/sys/devices/system/node/node{node}/cpulist"))?;
+    let cpus = parse_cpu_list(cpus.trim())?;
+    if cpus.is_empty() {
+        return Err(io::Error::other(format!("NUMA node {node} has no CPUs")));
+    }
+
+    // Safety: cpu_set_t is plain data and CPU_SET bounds checks the index.
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for cpu in cpus {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    let size = std::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(0, size, &raw const set) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let node = node as usize;
+    let mut mask = vec![0 as libc::c_ulong; node / libc::c_ulong::BITS as usize + 1];
+    mask[node / libc::c_ulong::BITS as usize] |= 1 << (node % libc::c_ulong::BITS as usize);
+    // The kernel reads one bit less than maxnode
+    let max_node = mask.len() * libc::c_ulong::BITS as usize + 1;
+    // Safety: mask holds max_node - 1 bits.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            MPOL_BIND,
+            mask.as_ptr(),
+            max_node as libc::c_ulong,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Affinity and memory policies are Linux only here
+///
+/// # Errors
+///
+/// Always returns `Unsupported`.
+#[cfg(not(target_os = "linux"))]
+pub fn bind(_node: u32) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(target_os = "linux")]
+const MPOL_BIND: libc::c_int = 2;
+
+/// `0-3,8,10-11` style list from sysfs
+#[cfg(target_os = "linux")]
+fn parse_cpu_list(list: &str) -> io::Result<Vec<usize>> {
+    let parse = |cpu: &str| cpu.parse::<usize>().map_err(io::Error::other);
+    let mut cpus = Vec::new();
+    for range in list.split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => cpus.extend(parse(start)?..=parse(end)?),
+            None => cpus.push(parse(range)?),
+        }
+    }
+    Ok(cpus)
+}
@@ -0,0 +1,887 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    num::NonZeroUsize,
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use memmap2::MmapOptions;
+
+pub use crate::compute::{Mismatch, ValidationReport};
+use crate::{
+    answers::AnswersError,
+    checkpoint::Checkpoint,
+    compute::{self, DistanceSummary, Mismatches, ValidationError},
+    format::Format,
+    progress::{ProgressHook, Reporter, Stage},
+    scanner::PairStream,
+    validation::AnswerFile,
+    CoordError, EarthModel, HaversineData, HaversineDataIter, HaversineDataPoint, PairBuffer,
+    ParseError, ParseOptions,
+};
+
+/// How the input file is brought into memory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadStrategy {
+    #[default]
+    Mmap,
+    /// `read_to_end` into a heap buffer
+    Read,
+    /// reads with `PipelineOptions::queue_depth` requests in flight on an `io_uring`
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    IoUring,
+    /// `O_DIRECT` reads that bypass the page cache
+    #[cfg(target_os = "linux")]
+    Direct,
+}
+
+impl std::str::FromStr for ReadStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mmap" => Ok(Self::Mmap),
+            "read" => Ok(Self::Read),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            "io-uring" => Ok(Self::IoUring),
+            #[cfg(target_os = "linux")]
+            "direct" => Ok(Self::Direct),
+            _ => Err(format!("unknown read strategy `{s}`")),
+        }
+    }
+}
+
+/// Which JSON parser deserializes the input
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParserBackend {
+    /// custom nom based parser
+    #[default]
+    Nom,
+    Serde,
+    /// simd-json, on a copy of the input since it parses in place
+    #[cfg(feature = "simd-json")]
+    Simd,
+    /// two stages, a vectorized token scan then a walk over the tokens
+    #[cfg(feature = "simd-parse")]
+    SimdScan,
+}
+
+impl std::str::FromStr for ParserBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nom" => Ok(Self::Nom),
+            "serde" => Ok(Self::Serde),
+            #[cfg(feature = "simd-json")]
+            "simd" => Ok(Self::Simd),
+            #[cfg(feature = "simd-parse")]
+            "simd-scan" => Ok(Self::SimdScan),
+            _ => Err(format!("unknown parser `{s}`")),
+        }
+    }
+}
+
+/// Which implementation computes the distances
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ComputeBackend {
+    /// `EarthModel::distance` one pair at a time
+    #[default]
+    Scalar,
+    /// `simd::haversine_batch`, validated with at least `simd::TOLERANCE`
+    Simd,
+    /// wgpu compute shader in f32, validated with at least `gpu::TOLERANCE`
+    #[cfg(feature = "gpu")]
+    Gpu,
+}
+
+impl std::str::FromStr for ComputeBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scalar" => Ok(Self::Scalar),
+            "simd" => Ok(Self::Simd),
+            #[cfg(feature = "gpu")]
+            "gpu" => Ok(Self::Gpu),
+            _ => Err(format!("unknown compute backend `{s}`")),
+        }
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug)]
+pub struct PipelineOptions {
+    pub read: ReadStrategy,
+    /// reads kept in flight by `ReadStrategy::IoUring`
+    pub queue_depth: u32,
+    /// format of the input; `parser` only applies to JSON
+    pub format: Format,
+    pub parser: ParserBackend,
+    pub compute: ComputeBackend,
+    /// threads the scalar backend splits the pairs across; the partial sums
+    /// are added in a fixed order, so the average only depends on the count
+    pub threads: NonZeroUsize,
+    /// the simd and gpu backends only compute on a sphere
+    pub model: EarthModel,
+    /// answers file (`.f64`) to validate the distances and average against
+    pub answers: Option<PathBuf>,
+    /// `answers` is a legacy file, without the header and checksum
+    pub legacy_answers: bool,
+    /// max absolute difference between a computed distance and its answer
+    pub tolerance: f64,
+    /// record the distances off by more than `tolerance` in
+    /// `RunReport::mismatches` and carry on instead of failing on the first;
+    /// a resumed checkpoint only records those of the pairs left
+    pub collect_mismatches: bool,
+    /// persist progress to this file while computing; checkpointed runs stream
+    /// the pairs with the scanner parser on the scalar backend and sum with
+    /// compensation, so their average can differ from an unchecked run's in
+    /// the last bits
+    pub checkpoint: Option<PathBuf>,
+    /// time between checkpoint saves
+    pub checkpoint_interval: Duration,
+    /// continue from `checkpoint` if it exists
+    pub resume: bool,
+    /// sum the pairs with `EarthModel::distance` as `HaversineDataIter` parses
+    /// them instead of parsing the whole input first; `parser` and `compute`
+    /// are ignored, and other formats than JSON are parsed whole regardless
+    pub stream: bool,
+    /// reject inputs with a coordinate out of range (see
+    /// `HaversineDataPoint::check_range`) instead of computing with it
+    pub strict: bool,
+    /// called as the input is parsed and the distances are computed, see
+    /// `progress`. JSON input parsed by nom and the single-threaded scalar
+    /// backend then run on loops that report as they go, in the same order;
+    /// the other parsers and backends only report once they are done.
+    pub progress: Option<ProgressHook>,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            read: ReadStrategy::default(),
+            queue_depth: 8,
+            format: Format::Json,
+            parser: ParserBackend::default(),
+            compute: ComputeBackend::default(),
+            threads: NonZeroUsize::MIN,
+            model: EarthModel::SPHERE_DEFAULT,
+            answers: None,
+            legacy_answers: false,
+            // Note(sathwik): The error margin is configured after trail and error.
+            // Need to dig into serde's f64 serialize precision for a better understanding.
+            tolerance: 1e-10,
+            collect_mismatches: false,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_mins(1),
+            resume: false,
+            stream: false,
+            strict: false,
+            progress: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RunReport {
+    /// size of the input file in bytes
+    pub input_size: usize,
+    pub pair_count: usize,
+    pub average: f64,
+    pub validation: Option<ValidationReport>,
+    /// in pair order, only recorded with `PipelineOptions::collect_mismatches`
+    pub mismatches: Vec<Mismatch>,
+}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse(String),
+    /// a coordinate is out of range in a strict run
+    InvalidCoordinates {
+        index: usize,
+        error: CoordError,
+    },
+    /// the answers file is damaged, of another version or a legacy file
+    Answers {
+        path: PathBuf,
+        error: AnswersError,
+    },
+    /// the answers file has fewer entries than pairs + average
+    AnswersExhausted,
+    Validation {
+        index: usize,
+        point: HaversineDataPoint,
+        computed: f64,
+        expected: f64,
+    },
+    /// the compute backend cannot compute distances on the model
+    UnsupportedModel {
+        backend: ComputeBackend,
+        model: EarthModel,
+    },
+    /// the checkpoint cannot be resumed or the run cannot be checkpointed
+    Checkpoint {
+        path: PathBuf,
+        reason: &'static str,
+    },
+    #[cfg(feature = "gpu")]
+    Gpu(crate::gpu::GpuError),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "Unable to open `{}`: {source}", path.display()),
+            Self::Parse(reason) => write!(f, "Unable to deserialize input data: {reason}"),
+            Self::InvalidCoordinates { index, error } => {
+                write!(f, "Pair {index} is out of range: {error}")
+            }
+            Self::Answers { path, error } => {
+                write!(f, "Invalid answers file `{}`: {error}", path.display())
+            }
+            Self::AnswersExhausted => write!(f, "validation input exhausted"),
+            Self::Validation {
+                point,
+                computed,
+                expected,
+                ..
+            } => write!(
+                f,
+                "Failed validation for {point:?}. Got {computed} Expected {expected} Diff {}",
+                (computed - expected).abs()
+            ),
+            Self::UnsupportedModel { backend, model } => {
+                write!(
+                    f,
+                    "The {backend:?} backend only computes on a sphere, not on the {model}"
+                )
+            }
+            Self::Checkpoint { path, reason } => {
+                write!(f, "Unable to checkpoint to `{}`: {reason}", path.display())
+            }
+            #[cfg(feature = "gpu")]
+            Self::Gpu(e) => write!(f, "GPU backend failed: {e}"),
+        }
+    }
+}
+
+impl From<ValidationError> for PipelineError {
+    fn from(error: ValidationError) -> Self {
+        match error {
+            ValidationError::AnswersExhausted => Self::AnswersExhausted,
+            ValidationError::Mismatch {
+                index,
+                point,
+                computed,
+                expected,
+            } => Self::Validation {
+                index,
+                point,
+                computed,
+                expected,
+            },
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Answers { error, .. } => Some(error),
+            #[cfg(feature = "gpu")]
+            Self::Gpu(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Library version of the `haversine` processor: read, parse, compute and
+/// optionally validate a dataset.
+pub struct Pipeline {
+    options: PipelineOptions,
+}
+
+impl Pipeline {
+    #[must_use]
+    pub fn new(options: PipelineOptions) -> Self {
+        Self { options }
+    }
+
+    #[must_use]
+    pub fn options(&self) -> &PipelineOptions {
+        &self.options
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the input or answers file cannot be read or parsed,
+    /// or if a distance does not match its answer within the tolerance and
+    /// mismatches are not collected.
+    pub fn run(&self, path: impl AsRef<Path>) -> Result<RunReport, PipelineError> {
+        self.run_with(path, &mut PairBuffer::default())
+    }
+
+    /// `run` that reads the pairs into `buffer`, which the nom parser parses
+    /// over the pairs of the caller's previous run (see `PairBuffer`) so that
+    /// repeated runs don't allocate them again
+    ///
+    /// # Errors
+    ///
+    /// Same as `run`.
+    pub fn run_with(
+        &self,
+        path: impl AsRef<Path>,
+        buffer: &mut PairBuffer,
+    ) -> Result<RunReport, PipelineError> {
+        let path = path.as_ref();
+        let answers = self
+            .options
+            .answers
+            .as_deref()
+            .map(|answers| AnswerFile::read(answers, self.options.legacy_answers))
+            .transpose()?;
+        let answers = answers.as_ref().map(AnswerFile::values);
+        let mut mismatches = Mismatches::new(self.options.collect_mismatches);
+        let (input_size, pair_count, sum) = if let Some(checkpoint) = &self.options.checkpoint {
+            self.sum_checkpointed(path, checkpoint, answers, &mut mismatches)?
+        } else if self.options.stream && self.options.format == Format::Json {
+            sum_streaming(path, &self.options, answers, &mut mismatches)?
+        } else {
+            let input_size = read_input(path, &self.options, &mut buffer.pairs)?;
+            let pairs = buffer.pairs.as_slice();
+            if self.options.strict {
+                check_ranges(pairs)?;
+            }
+            let mut progress = self
+                .options
+                .progress
+                .map(|hook| Reporter::new(hook, Stage::Compute, pairs.len()));
+            let sum = match self.options.compute {
+                ComputeBackend::Scalar if self.options.threads.get() > 1 => sum_threaded(
+                    pairs,
+                    self.options.model,
+                    answers,
+                    self.options.tolerance,
+                    self.options.threads.get(),
+                    &mut mismatches,
+                )?,
+                ComputeBackend::Scalar => match (progress.as_mut(), answers) {
+                    (Some(progress), answers) => sum_reporting(
+                        pairs,
+                        self.options.model,
+                        answers,
+                        self.options.tolerance,
+                        &mut mismatches,
+                        progress,
+                    )?,
+                    (None, Some(answers)) => compute::sum_validated(
+                        pairs,
+                        self.options.model,
+                        answers,
+                        self.options.tolerance,
+                        &mut mismatches,
+                    )?,
+                    (None, None) => compute::sum_distances(pairs, self.options.model),
+                },
+                ComputeBackend::Simd => sum_simd(
+                    pairs,
+                    self.sphere_radius()?,
+                    answers,
+                    self.options.tolerance,
+                    &mut mismatches,
+                )?,
+                #[cfg(feature = "gpu")]
+                ComputeBackend::Gpu => sum_gpu(
+                    pairs,
+                    self.sphere_radius()?,
+                    answers,
+                    self.options.tolerance,
+                    &mut mismatches,
+                )?,
+            };
+            if let Some(progress) = progress {
+                progress.finish();
+            }
+            (input_size, pairs.len(), sum)
+        };
+        let summary = DistanceSummary::new(pair_count, sum);
+        let validation = answers
+            .map(|answers| ValidationReport::new(answers, &summary))
+            .transpose()?;
+
+        Ok(RunReport {
+            input_size,
+            pair_count,
+            average: summary.average,
+            validation,
+            mismatches: mismatches.found,
+        })
+    }
+}
+
+impl Pipeline {
+    /// Radius of the model, for the backends that only compute on a sphere
+    fn sphere_radius(&self) -> Result<f64, PipelineError> {
+        match self.options.model {
+            EarthModel::Sphere { radius } => Ok(radius),
+            model @ EarthModel::Ellipsoid { .. } => Err(PipelineError::UnsupportedModel {
+                backend: self.options.compute,
+                model,
+            }),
+        }
+    }
+}
+
+/// Checkpoint saves are timed, but the clock is only read every this many pairs
+const CHECKPOINT_CLOCK_EVERY: usize = 4096;
+
+impl Pipeline {
+    /// Streams the pairs and saves a `Checkpoint` every `checkpoint_interval`,
+    /// after restoring the saved one when resuming. The checkpoint is removed
+    /// once the run completes.
+    #[perf::instrument]
+    fn sum_checkpointed(
+        &self,
+        path: &Path,
+        checkpoint: &Path,
+        answers: Option<&[f64]>,
+        mismatches: &mut Mismatches,
+    ) -> Result<(usize, usize, f64), PipelineError> {
+        let options = &self.options;
+        let checkpoint_error = |reason| PipelineError::Checkpoint {
+            path: checkpoint.to_path_buf(),
+            reason,
+        };
+        if options.compute != ComputeBackend::Scalar {
+            return Err(checkpoint_error("only the scalar backend checkpoints"));
+        }
+        if options.format != Format::Json {
+            return Err(checkpoint_error("only JSON input checkpoints"));
+        }
+
+        let bytes = read_bytes(path, options)?;
+        let input_size = bytes.len() as u64;
+        let mut state = Checkpoint::new(input_size, options.model);
+        if options.resume {
+            if let Some(saved) = Checkpoint::load(checkpoint).map_err(io_error(checkpoint))? {
+                if saved.input_size != input_size || saved.model != options.model {
+                    return Err(checkpoint_error("it was taken on another input or model"));
+                }
+                state = saved;
+            }
+        }
+        let malformed = |e: ParseError| PipelineError::Parse(e.to_string());
+        let (mut pairs, mut index) = if state.pairs_processed == 0 {
+            (PairStream::new(&bytes).map_err(malformed)?, 0)
+        } else {
+            // Every pair takes more than a byte, so the count is checked too
+            let offset = usize::try_from(state.bytes_consumed)
+                .ok()
+                .filter(|&offset| offset <= bytes.len())
+                .ok_or(checkpoint_error("its offset is past the end of the input"))?;
+            let index = usize::try_from(state.pairs_processed)
+                .ok()
+                .filter(|&index| index <= offset)
+                .ok_or(checkpoint_error("it has more pairs than bytes"))?;
+            (PairStream::resume(&bytes, offset), index)
+        };
+
+        let mut progress = options
+            .progress
+            .map(|hook| Reporter::new(hook, Stage::Stream, bytes.len()));
+        let mut last_save = Instant::now();
+        while let Some(point) = pairs.next() {
+            let point = point.map_err(malformed)?;
+            if options.strict {
+                check_range(index, &point)?;
+            }
+            let dist = options.model.distance(&point);
+            if let Some(answers) = answers {
+                let expected = *answers.get(index).ok_or(PipelineError::AnswersExhausted)?;
+                if (dist - expected).abs() > options.tolerance {
+                    mismatches.report(index, &point, dist, expected)?;
+                }
+            }
+            index += 1;
+            state.add(dist);
+            state.pairs_processed = index as u64;
+            state.bytes_consumed = pairs.offset() as u64;
+            if let Some(progress) = &mut progress {
+                progress.update(pairs.offset());
+            }
+            if index % CHECKPOINT_CLOCK_EVERY == 0
+                && last_save.elapsed() >= options.checkpoint_interval
+            {
+                state.save(checkpoint).map_err(io_error(checkpoint))?;
+                last_save = Instant::now();
+            }
+        }
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+        match std::fs::remove_file(checkpoint) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(io_error(checkpoint)(e)),
+            _ => {}
+        }
+        Ok((bytes.len(), index, state.total()))
+    }
+}
+
+#[perf::instrument]
+fn sum_streaming(
+    path: &Path,
+    options: &PipelineOptions,
+    answers: Option<&[f64]>,
+    mismatches: &mut Mismatches,
+) -> Result<(usize, usize, f64), PipelineError> {
+    let bytes = read_bytes(path, options)?;
+    let malformed = |e: ParseError| PipelineError::Parse(e.to_string());
+    let mut sum = 0f64;
+    let mut count = 0;
+    let mut progress = options
+        .progress
+        .map(|hook| Reporter::new(hook, Stage::Stream, bytes.len()));
+    let mut points = HaversineDataIter::new(&bytes).map_err(malformed)?;
+    // parsing is timed with the distances, the iterator parses on demand
+    #[perf::instrument_loop("parse and calculate distance")]
+    while let Some(point) = points.next() {
+        let point = point.map_err(malformed)?;
+        if let Some(progress) = &mut progress {
+            progress.update(points.offset());
+        }
+        if options.strict {
+            check_range(count, &point)?;
+        }
+        let dist = options.model.distance(&point);
+        if let Some(answers) = answers {
+            let expected = *answers.get(count).ok_or(PipelineError::AnswersExhausted)?;
+            if (dist - expected).abs() > options.tolerance {
+                mismatches.report(count, &point, dist, expected)?;
+            }
+        }
+        sum += dist;
+        count += 1;
+    }
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    Ok((bytes.len(), count, sum))
+}
+
+#[perf::instrument]
+fn check_ranges(pairs: &[HaversineDataPoint]) -> Result<(), PipelineError> {
+    pairs
+        .iter()
+        .enumerate()
+        .try_for_each(|(index, point)| check_range(index, point))
+}
+
+fn check_range(index: usize, point: &HaversineDataPoint) -> Result<(), PipelineError> {
+    point
+        .check_range()
+        .map_err(|error| PipelineError::InvalidCoordinates { index, error })
+}
+
+pub(crate) fn io_error(path: &Path) -> impl Fn(io::Error) -> PipelineError + '_ {
+    move |source| PipelineError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// The input's bytes, however they were brought into memory
+type Input = Box<dyn Deref<Target = [u8]>>;
+
+fn read_bytes(path: &Path, options: &PipelineOptions) -> Result<Input, PipelineError> {
+    let file = File::open(path).map_err(io_error(path))?;
+    // A mapping is only read as it is touched, see the page faults of `parse`
+    let size = file.metadata().map_err(io_error(path))?.len();
+    let _trace = perf::trace_bandwidth!("read", size);
+    match options.read {
+        ReadStrategy::Mmap => {
+            let mmap = unsafe { MmapOptions::new().map(&file).map_err(io_error(path))? };
+            Ok(Box::new(mmap))
+        }
+        ReadStrategy::Read => {
+            let mut file = file;
+            let mut buffer = Vec::new();
+            file.read_to_end(&mut buffer).map_err(io_error(path))?;
+            Ok(Box::new(buffer))
+        }
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        ReadStrategy::IoUring => {
+            let buffer =
+                crate::uring::read_file(&file, options.queue_depth).map_err(io_error(path))?;
+            Ok(Box::new(buffer))
+        }
+        #[cfg(target_os = "linux")]
+        ReadStrategy::Direct => {
+            drop(file);
+            let buffer = crate::direct::read_file(path).map_err(io_error(path))?;
+            Ok(Box::new(buffer))
+        }
+    }
+}
+
+/// Reads and parses the input into `pairs`, returns its size
+#[perf::instrument(page_faults)]
+fn read_input(
+    path: &Path,
+    options: &PipelineOptions,
+    pairs: &mut Vec<HaversineDataPoint>,
+) -> Result<usize, PipelineError> {
+    let bytes = read_bytes(path, options)?;
+    let Some(hook) = options.progress else {
+        parse(&bytes, options.format, options.parser, pairs)?;
+        return Ok(bytes.len());
+    };
+    let mut progress = Reporter::new(hook, Stage::Parse, bytes.len());
+    if options.format == Format::Json && options.parser == ParserBackend::Nom {
+        parse_nom_reporting(&bytes, &mut progress, pairs)?;
+    } else {
+        parse(&bytes, options.format, options.parser, pairs)?;
+    }
+    progress.finish();
+    Ok(bytes.len())
+}
+
+/// Parses `bytes` into `pairs`, over the pairs already there with nom and
+/// replacing them otherwise
+#[perf::instrument(page_faults, bytes_from = bytes)]
+fn parse(
+    bytes: &[u8],
+    format: Format,
+    parser: ParserBackend,
+    pairs: &mut Vec<HaversineDataPoint>,
+) -> Result<(), PipelineError> {
+    if format != Format::Json {
+        *pairs = HaversineData::parse_from_slice(bytes, format)
+            .map_err(|e| PipelineError::Parse(format!("malformed {format}: {e}")))?
+            .pairs;
+        return Ok(());
+    }
+    *pairs = match parser {
+        ParserBackend::Nom => return parse_nom(bytes, pairs),
+        ParserBackend::Serde => parse_serde(bytes)?.pairs,
+        #[cfg(feature = "simd-json")]
+        ParserBackend::Simd => parse_simd(bytes)?.pairs,
+        #[cfg(feature = "simd-parse")]
+        ParserBackend::SimdScan => parse_simd_scan(bytes)?.pairs,
+    };
+    Ok(())
+}
+
+// One anchor per parser, so that profiles of runs with different
+// `--parser`s can be compared line by line.
+
+#[perf::instrument(bytes_from = bytes)]
+fn parse_nom(bytes: &[u8], pairs: &mut Vec<HaversineDataPoint>) -> Result<(), PipelineError> {
+    HaversineData::parse_into(bytes, ParseOptions::default(), pairs)
+        .map_err(|e| PipelineError::Parse(e.to_string()))
+}
+
+/// `parse_nom` one pair at a time, reporting the bytes parsed
+#[perf::instrument(bytes_from = bytes)]
+fn parse_nom_reporting(
+    bytes: &[u8],
+    progress: &mut Reporter,
+    pairs: &mut Vec<HaversineDataPoint>,
+) -> Result<(), PipelineError> {
+    let malformed = |e: ParseError| PipelineError::Parse(e.to_string());
+    let mut points = HaversineDataIter::new(bytes).map_err(malformed)?;
+    pairs.clear();
+    while let Some(point) = points.next() {
+        pairs.push(point.map_err(malformed)?);
+        progress.update(points.offset());
+    }
+    Ok(())
+}
+
+#[perf::instrument(bytes_from = bytes)]
+fn parse_serde(bytes: &[u8]) -> Result<HaversineData, PipelineError> {
+    serde_json::from_slice(bytes).map_err(|e| PipelineError::Parse(e.to_string()))
+}
+
+#[cfg(feature = "simd-json")]
+#[perf::instrument(bytes_from = bytes)]
+fn parse_simd(bytes: &[u8]) -> Result<HaversineData, PipelineError> {
+    simd_json::serde::from_slice(&mut bytes.to_vec())
+        .map_err(|e| PipelineError::Parse(e.to_string()))
+}
+
+#[cfg(feature = "simd-parse")]
+#[perf::instrument(bytes_from = bytes)]
+fn parse_simd_scan(bytes: &[u8]) -> Result<HaversineData, PipelineError> {
+    HaversineData::parse_from_json_slice_simd_scan(bytes)
+        .map_err(|()| PipelineError::Parse(String::from("malformed JSON")))
+}
+
+/// Sums contiguous chunks of the pairs on `threads` threads and adds their
+/// sums and mismatches in chunk order. A mismatch is reported for the first
+/// failing chunk.
+#[perf::instrument]
+fn sum_threaded(
+    pairs: &[HaversineDataPoint],
+    model: EarthModel,
+    answers: Option<&[f64]>,
+    tolerance: f64,
+    threads: usize,
+    mismatches: &mut Mismatches,
+) -> Result<f64, PipelineError> {
+    if answers.is_some_and(|answers| answers.len() < pairs.len()) {
+        return Err(PipelineError::AnswersExhausted);
+    }
+    let bound = |chunk| chunk * pairs.len() / threads;
+    let collect = mismatches.collect;
+    let partial_sums: Vec<Result<(f64, Mismatches), PipelineError>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|chunk| {
+                let range = bound(chunk)..bound(chunk + 1);
+                scope.spawn(move || {
+                    let mut found = Mismatches::new(collect);
+                    let sum = sum_range(pairs, range, model, answers, tolerance, &mut found)?;
+                    Ok((sum, found))
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("summation thread panicked"))
+            .collect()
+    });
+    let mut sum = 0f64;
+    for partial in partial_sums {
+        let (partial, found) = partial?;
+        sum += partial;
+        mismatches.found.extend(found.found);
+    }
+    Ok(sum)
+}
+
+/// Sum of the pairs in `range`, validated against the answers at the same
+/// indices. Runs on worker threads, so it is only instrumented by the
+/// thread-safe profiler.
+#[perf::instrument(cfg = feature = "enable-perf-mt")]
+fn sum_range(
+    pairs: &[HaversineDataPoint],
+    range: std::ops::Range<usize>,
+    model: EarthModel,
+    answers: Option<&[f64]>,
+    tolerance: f64,
+    mismatches: &mut Mismatches,
+) -> Result<f64, PipelineError> {
+    let mut sum = 0f64;
+    for index in range {
+        let point = &pairs[index];
+        let dist = model.distance(point);
+        sum += dist;
+        if let Some(answers) = answers {
+            if (dist - answers[index]).abs() > tolerance {
+                mismatches.report(index, point, dist, answers[index])?;
+            }
+        }
+    }
+    Ok(sum)
+}
+
+/// `compute::sum_validated`, or `compute::sum_distances` without answers,
+/// reporting the pairs computed
+#[perf::instrument]
+fn sum_reporting(
+    pairs: &[HaversineDataPoint],
+    model: EarthModel,
+    answers: Option<&[f64]>,
+    tolerance: f64,
+    mismatches: &mut Mismatches,
+    progress: &mut Reporter,
+) -> Result<f64, PipelineError> {
+    if answers.is_some_and(|answers| answers.len() < pairs.len()) {
+        return Err(PipelineError::AnswersExhausted);
+    }
+    let mut sum = 0f64;
+    #[perf::instrument_loop("calculate distance")]
+    for (index, point) in pairs.iter().enumerate() {
+        let dist = model.distance(point);
+        sum += dist;
+        if let Some(answers) = answers {
+            if (dist - answers[index]).abs() > tolerance {
+                mismatches.report(index, point, dist, answers[index])?;
+            }
+        }
+        progress.update(index + 1);
+    }
+    Ok(sum)
+}
+
+/// The simd backend computes distances this many at a time into a buffer
+/// that stays in L1
+const SIMD_BLOCK: usize = 1024;
+
+#[perf::instrument]
+fn sum_simd(
+    pairs: &[HaversineDataPoint],
+    radius: f64,
+    answers: Option<&[f64]>,
+    tolerance: f64,
+    mismatches: &mut Mismatches,
+) -> Result<f64, PipelineError> {
+    if answers.is_some_and(|answers| answers.len() < pairs.len()) {
+        return Err(PipelineError::AnswersExhausted);
+    }
+    let tolerance = tolerance.max(crate::simd::TOLERANCE);
+    let mut distances = [0f64; SIMD_BLOCK];
+    let mut sum = 0f64;
+    #[perf::instrument_loop("calculate distances")]
+    for (block, chunk) in pairs.chunks(SIMD_BLOCK).enumerate() {
+        let distances = &mut distances[..chunk.len()];
+        crate::simd::haversine_batch(chunk, radius, distances);
+        sum += distances.iter().sum::<f64>();
+        if let Some(answers) = answers {
+            let start = block * SIMD_BLOCK;
+            let expected = &answers[start..start + chunk.len()];
+            for (offset, (&dist, &expected)) in distances.iter().zip(expected).enumerate() {
+                if (dist - expected).abs() > tolerance {
+                    let index = start + offset;
+                    mismatches.report(index, &pairs[index], dist, expected)?;
+                }
+            }
+        }
+    }
+    Ok(sum)
+}
+
+#[cfg(feature = "gpu")]
+#[perf::instrument]
+fn sum_gpu(
+    pairs: &[HaversineDataPoint],
+    radius: f64,
+    answers: Option<&[f64]>,
+    tolerance: f64,
+    mismatches: &mut Mismatches,
+) -> Result<f64, PipelineError> {
+    let soa = crate::soa::HaversineSoA::from(pairs);
+    let output =
+        crate::gpu::compute(&soa, radius, answers.is_some()).map_err(PipelineError::Gpu)?;
+    if let Some(answers) = answers {
+        if answers.len() < pairs.len() {
+            return Err(PipelineError::AnswersExhausted);
+        }
+        // the kernel runs in f32, compare at its precision
+        let tolerance = tolerance.max(crate::gpu::TOLERANCE);
+        for (index, (&dist, &expected)) in output.distances.iter().zip(answers).enumerate() {
+            if (dist - expected).abs() > tolerance {
+                mismatches.report(index, &pairs[index], dist, expected)?;
+            }
+        }
+    }
+    Ok(output.sum)
+}
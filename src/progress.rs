@@ -0,0 +1,120 @@
+//! Progress of a pipeline run over a huge input, reported to the
+//! `PipelineOptions::progress` hook as the input is parsed and the distances
+//! are computed.
+
+use std::time::{Duration, Instant};
+
+/// What a `Progress` counts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// bytes of the input parsed
+    Parse,
+    /// pairs whose distance was computed
+    Compute,
+    /// bytes of the input parsed, each pair computed as it is parsed by
+    /// streaming and checkpointed runs
+    Stream,
+}
+
+impl Stage {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Parse => "parse",
+            Self::Compute => "compute",
+            Self::Stream => "parse and compute",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub stage: Stage,
+    /// bytes or pairs done, `total` once the stage completes
+    pub done: u64,
+    pub total: u64,
+    /// since the stage started
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Share of the stage done, in `0.0..=1.0`
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.done as f64 / self.total as f64
+        }
+    }
+
+    /// Time left at the rate so far, none before anything was done
+    #[must_use]
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        (fraction > 0.0).then(|| self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.done >= self.total
+    }
+}
+
+/// Called with the progress of every stage about a hundred times, the last
+/// with `Progress::is_done`. A plain fn so that options without one stay
+/// cheap to clone and compare.
+pub type ProgressHook = fn(&Progress);
+
+/// Calls the hook of one stage each time another hundredth of it is done
+pub(crate) struct Reporter {
+    hook: ProgressHook,
+    stage: Stage,
+    total: u64,
+    step: u64,
+    next: u64,
+    start: Instant,
+}
+
+impl Reporter {
+    const UPDATES: u64 = 100;
+
+    pub(crate) fn new(hook: ProgressHook, stage: Stage, total: usize) -> Self {
+        let total = total as u64;
+        let step = (total / Self::UPDATES).max(1);
+        Self {
+            hook,
+            stage,
+            total,
+            step,
+            next: step,
+            start: Instant::now(),
+        }
+    }
+
+    /// `done` bytes or pairs of the stage are done; cheap unless it crosses
+    /// the next hundredth
+    #[inline]
+    pub(crate) fn update(&mut self, done: usize) {
+        let done = done as u64;
+        if done >= self.next && done < self.total {
+            self.next = done - done % self.step + self.step;
+            self.report(done);
+        }
+    }
+
+    /// The stage completed
+    pub(crate) fn finish(self) {
+        self.report(self.total);
+    }
+
+    fn report(&self, done: u64) {
+        (self.hook)(&Progress {
+            stage: self.stage,
+            done,
+            total: self.total,
+            elapsed: self.start.elapsed(),
+        });
+    }
+}
@@ -0,0 +1,268 @@
+//! Hand-rolled single pass parser for the generator's JSON, as a baseline for
+//! the nom parser. Accepts the same documents: `{"pairs": [...]}` with the four
+//! coordinate keys of every pair in any order.
+
+use crate::{Coord, HaversineData, HaversineDataPoint, ParseError, Token};
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    /// `expected` wasn't at `position`
+    fn error(&self, position: usize, expected: Token) -> ParseError {
+        match self.bytes.get(position) {
+            Some(&found) => ParseError::Unexpected {
+                position,
+                expected,
+                found,
+            },
+            None => ParseError::UnexpectedEnd { position, expected },
+        }
+    }
+
+    /// Next byte after whitespace, consumed
+    fn next_token(&mut self, expected: Token) -> Result<u8, ParseError> {
+        self.skip_whitespace();
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or(self.error(self.pos, expected))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ParseError> {
+        let expected = Token::Char(char::from(byte));
+        if self.next_token(expected)? == byte {
+            Ok(())
+        } else {
+            Err(self.error(self.pos - 1, expected))
+        }
+    }
+
+    /// `"key":`, and the offset of its opening quote
+    fn key(&mut self) -> Result<(usize, &'a [u8]), ParseError> {
+        if self.next_token(Token::Key)? != b'"' {
+            return Err(self.error(self.pos - 1, Token::Key));
+        }
+        let start = self.pos;
+        let len = self.bytes[start..]
+            .iter()
+            .position(|&b| b == b'"')
+            .ok_or(self.error(self.bytes.len(), Token::Char('"')))?;
+        self.pos = start + len + 1;
+        self.expect(b':')?;
+        Ok((start - 1, &self.bytes[start..start + len]))
+    }
+
+    fn number(&mut self) -> Result<f64, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while let Some(b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+        // Safety: the bytes matched above are ASCII
+        unsafe { std::str::from_utf8_unchecked(&self.bytes[start..self.pos]) }
+            .parse()
+            .map_err(|_| self.error(start, Token::Number))
+    }
+
+    fn point(&mut self) -> Result<HaversineDataPoint, ParseError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        self.expect(b'{')?;
+        let mut coordinates = [None; 4];
+        for i in 0..4 {
+            let (position, key) = self.key()?;
+            let slot = match key {
+                b"x0" => 0,
+                b"y0" => 1,
+                b"x1" => 2,
+                b"y1" => 3,
+                _ => return Err(ParseError::UnknownKey { position }),
+            };
+            if coordinates[slot].is_some() {
+                return Err(ParseError::DuplicateKey { position });
+            }
+            coordinates[slot] = Some(self.number()?);
+            let separator = if i == 3 { b'}' } else { b',' };
+            match self.next_token(Token::Char(char::from(separator)))? {
+                byte if byte == separator => {}
+                b'}' => return Err(ParseError::InvalidDatapoint { position: start }),
+                _ => return Err(self.error(self.pos - 1, Token::Char(char::from(separator)))),
+            }
+        }
+        match coordinates {
+            [Some(x0), Some(y0), Some(x1), Some(y1)] => Ok(HaversineDataPoint {
+                p0: Coord { lon: x0, lat: y0 },
+                p1: Coord { lon: x1, lat: y1 },
+            }),
+            _ => Err(ParseError::InvalidDatapoint { position: start }),
+        }
+    }
+
+    /// `{"pairs": [`
+    fn prefix(&mut self) -> Result<(), ParseError> {
+        self.expect(b'{')?;
+        match self.key()? {
+            (_, b"pairs") => self.expect(b'['),
+            (position, _) => Err(self.error(position, Token::Key)),
+        }
+    }
+
+    fn data(&mut self) -> Result<HaversineData, ParseError> {
+        self.prefix()?;
+        let mut pairs = Vec::new();
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+        } else {
+            loop {
+                pairs.push(self.point()?);
+                match self.next_token(Token::Char(']'))? {
+                    b',' => {}
+                    b']' => break,
+                    _ => return Err(self.error(self.pos - 1, Token::Char(']'))),
+                }
+            }
+        }
+        self.expect(b'}')?;
+        Ok(HaversineData { pairs })
+    }
+}
+
+/// The pairs of a document one at a time, tracking the offset after the
+/// last one so that a run can stop and pick up again at any pair boundary
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct PairStream<'a> {
+    scanner: Scanner<'a>,
+    /// no pair has been read yet, so `]` may close the array right away
+    first: bool,
+    done: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> PairStream<'a> {
+    /// Stream over the pairs of `bytes`, after checking the `{"pairs": [` prefix
+    pub(crate) fn new(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        let mut scanner = Scanner { bytes, pos: 0 };
+        scanner.prefix()?;
+        Ok(Self {
+            scanner,
+            first: true,
+            done: false,
+        })
+    }
+
+    /// Stream continuing after the pair that ends at `offset`
+    pub(crate) fn resume(bytes: &'a [u8], offset: usize) -> Self {
+        Self {
+            scanner: Scanner { bytes, pos: offset },
+            first: false,
+            done: false,
+        }
+    }
+
+    /// Offset just past the last pair read
+    pub(crate) fn offset(&self) -> usize {
+        self.scanner.pos
+    }
+
+    /// `Some(point)`, or `None` once `]}` closes the document
+    fn advance(&mut self) -> Result<Option<HaversineDataPoint>, ParseError> {
+        let scanner = &mut self.scanner;
+        let close = if self.first {
+            scanner.skip_whitespace();
+            let close = scanner.bytes.get(scanner.pos) == Some(&b']');
+            scanner.pos += usize::from(close);
+            close
+        } else {
+            match scanner.next_token(Token::Char(']'))? {
+                b',' => false,
+                b']' => true,
+                _ => return Err(scanner.error(scanner.pos - 1, Token::Char(']'))),
+            }
+        };
+        if close {
+            scanner.expect(b'}')?;
+            return Ok(None);
+        }
+        self.first = false;
+        scanner.point().map(Some)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Iterator for PairStream<'_> {
+    type Item = Result<HaversineDataPoint, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.advance().transpose();
+        self.done = !matches!(next, Some(Ok(_)));
+        next
+    }
+}
+
+impl HaversineData {
+    /// Same input as `parse_from_json_slice`, parsed without nom.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` on malformed JSON or pairs with missing keys.
+    #[allow(clippy::result_unit_err)]
+    pub fn parse_from_json_slice_scanner(bytes: &[u8]) -> Result<HaversineData, ()> {
+        Scanner { bytes, pos: 0 }.data().map_err(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::PairStream;
+    use crate::{fixtures::MALFORMED, HaversineData, HaversineDataIter};
+
+    #[test]
+    fn stream_locates_malformed_input_like_the_nom_parser() {
+        for (name, bytes) in MALFORMED {
+            let error = |stream: Result<Vec<_>, _>| stream.expect_err(name);
+            let nom = error(HaversineDataIter::new(bytes).and_then(Iterator::collect));
+            let scanner = error(PairStream::new(bytes).and_then(Iterator::collect));
+            assert_eq!(scanner, nom, "{name}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn parses_serialized_data(data in any::<HaversineData>()) {
+            let json = serde_json::to_vec_pretty(&data).unwrap();
+            prop_assert_eq!(HaversineData::parse_from_json_slice_scanner(&json), Ok(data));
+        }
+
+        #[test]
+        fn resumes_at_any_pair(data in any::<HaversineData>(), split in any::<prop::sample::Index>()) {
+            let json = serde_json::to_vec_pretty(&data).unwrap();
+            let split = split.index(data.pairs.len() + 1);
+            let mut stream = PairStream::new(&json).unwrap();
+            let mut pairs: Vec<_> = stream.by_ref().take(split).collect::<Result<_, _>>().unwrap();
+            let rest = if split == 0 {
+                PairStream::new(&json).unwrap()
+            } else {
+                PairStream::resume(&json, stream.offset())
+            };
+            pairs.extend(rest.map(Result::unwrap));
+            prop_assert_eq!(pairs, data.pairs);
+        }
+    }
+}
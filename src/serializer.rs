@@ -0,0 +1,166 @@
+//! `HaversineData` as JSON without serde, in the generator's layout.
+
+use std::io::{self, Write};
+
+use crate::{HaversineData, HaversineDataPoint};
+
+impl HaversineData {
+    /// Writes the same bytes as `serde_json`'s pretty printer with a two space
+    /// indent. Coordinates get `precision` decimals, or the shortest
+    /// representation that round-trips (as with `serde_json`) if `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error of the underlying writer.
+    #[cfg_attr(not(target_arch = "wasm32"), perf::instrument)]
+    pub fn write_json<W: Write>(&self, writer: W, precision: Option<usize>) -> io::Result<()> {
+        let mut json = JsonWriter::new(writer, precision)?;
+        for point in &self.pairs {
+            json.write_pair(point)?;
+        }
+        json.finish().map(drop)
+    }
+}
+
+/// Writes pairs one at a time in the layout of `HaversineData::write_json`:
+/// the opening through `new`, the pairs through `write_pair` and the closing
+/// through `finish`
+pub struct JsonWriter<W: Write> {
+    writer: W,
+    precision: Option<usize>,
+    buffer: ryu::Buffer,
+    pairs_written: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    /// # Errors
+    ///
+    /// Returns the error of writing the opening.
+    pub fn new(mut writer: W, precision: Option<usize>) -> io::Result<Self> {
+        writer.write_all(b"{\n  \"pairs\": [")?;
+        Ok(Self {
+            writer,
+            precision,
+            buffer: ryu::Buffer::new(),
+            pairs_written: 0,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Returns the error of the underlying writer.
+    pub fn write_pair(&mut self, point: &HaversineDataPoint) -> io::Result<()> {
+        let opening: &[u8] = if self.pairs_written == 0 {
+            b"\n    {"
+        } else {
+            b",\n    {"
+        };
+        self.writer.write_all(opening)?;
+        let coordinates = [
+            ("x0", point.p0.lon),
+            ("y0", point.p0.lat),
+            ("x1", point.p1.lon),
+            ("y1", point.p1.lat),
+        ];
+        for (index, (key, value)) in coordinates.into_iter().enumerate() {
+            let separator = if index == 0 { "" } else { "," };
+            write!(self.writer, "{separator}\n      \"{key}\": ")?;
+            match self.precision {
+                // serde_json writes NaN and infinities as null
+                _ if !value.is_finite() => self.writer.write_all(b"null")?,
+                Some(precision) => write!(self.writer, "{value:.precision$}")?,
+                None => self
+                    .writer
+                    .write_all(self.buffer.format_finite(value).as_bytes())?,
+            }
+        }
+        self.pairs_written += 1;
+        self.writer.write_all(b"\n    }")
+    }
+
+    /// Writes the closing and returns the writer, unflushed
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let closing: &[u8] = if self.pairs_written == 0 {
+            b"]\n}"
+        } else {
+            b"\n  ]\n}"
+        };
+        self.writer.write_all(closing)?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde::Serialize;
+
+    use crate::{fixtures::EDGE_CASES, Coord, HaversineData, HaversineDataPoint};
+
+    fn serde_pretty(data: &HaversineData) -> Vec<u8> {
+        let mut json = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"  ");
+        let mut serializer = serde_json::Serializer::with_formatter(&mut json, formatter);
+        data.serialize(&mut serializer).unwrap();
+        json
+    }
+
+    fn write_json(data: &HaversineData, precision: Option<usize>) -> Vec<u8> {
+        let mut json = Vec::new();
+        data.write_json(&mut json, precision).unwrap();
+        json
+    }
+
+    #[test]
+    fn matches_serde_layout() {
+        for data in [EDGE_CASES.data(), HaversineData { pairs: Vec::new() }] {
+            assert_eq!(
+                String::from_utf8(write_json(&data, None)).unwrap(),
+                String::from_utf8(serde_pretty(&data)).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn rounds_to_precision() {
+        let point = HaversineDataPoint {
+            p0: Coord {
+                lon: 1.0 / 3.0,
+                lat: -2.0 / 3.0,
+            },
+            p1: Coord {
+                lon: 100.0,
+                lat: -0.000_4,
+            },
+        };
+        let json = write_json(&HaversineData { pairs: vec![point] }, Some(3));
+        let data = HaversineData::parse_from_json_slice(&json).unwrap();
+        assert_eq!(
+            data.pairs,
+            [HaversineDataPoint {
+                p0: Coord {
+                    lon: 0.333,
+                    lat: -0.667
+                },
+                p1: Coord {
+                    lon: 100.0,
+                    lat: -0.0
+                }
+            }]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(pairs in prop::collection::vec(any::<HaversineDataPoint>(), 0..8)) {
+            let data = HaversineData { pairs };
+            let json = write_json(&data, None);
+            prop_assert_eq!(&json, &serde_pretty(&data));
+            prop_assert_eq!(HaversineData::parse_from_json_slice(&json), Ok(data));
+        }
+    }
+}
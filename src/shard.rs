@@ -0,0 +1,121 @@
+//! A dataset split in order across several files, as `generate --shards`
+//! writes it: each shard holds a contiguous run of the pairs and has its own
+//! answers file, and together they hold the dataset and its answers.
+
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use crate::{format::Format, HaversineData};
+
+/// `count` contiguous runs of the pairs of `data`, in order, whose lengths
+/// differ by at most one (some are empty if there are fewer pairs)
+pub fn split(
+    data: &HaversineData,
+    count: NonZeroUsize,
+) -> impl Iterator<Item = HaversineData> + '_ {
+    let pair_count = data.pairs.len();
+    let bound = move |index| index * pair_count / count;
+    (0..count.get()).map(move |index| HaversineData {
+        pairs: data.pairs[bound(index)..bound(index + 1)].to_vec(),
+    })
+}
+
+/// `data_1000_flex.json` -> `data_1000_part0_flex.json`, so each shard's
+/// answers file follows the generator's naming
+#[must_use]
+pub fn path(input: &Path, index: usize, to: Format) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    let name = match stem.strip_suffix("_flex") {
+        Some(stem) => format!("{stem}_part{index}_flex"),
+        None => format!("{stem}_part{index}"),
+    };
+    input.with_file_name(name).with_extension(to.extension())
+}
+
+/// Average over all the pairs of shards given as their pair count and the
+/// average of their answers file
+#[allow(clippy::cast_precision_loss)]
+pub fn average(shards: impl IntoIterator<Item = (usize, f64)>) -> f64 {
+    let (mut pair_count, mut sum) = (0, 0f64);
+    for (count, average) in shards {
+        pair_count += count;
+        sum += average * count as f64;
+    }
+    sum / pair_count as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        answers::{self, AnswersHeader, AnswersWriter},
+        fixtures::VALID,
+        EarthModel,
+    };
+
+    /// Answers file of `data`, as `generate` writes it
+    #[allow(clippy::cast_precision_loss)]
+    fn answers_file(data: &HaversineData) -> Vec<u8> {
+        let model = EarthModel::SPHERE_DEFAULT;
+        let header = AnswersHeader {
+            count: data.pairs.len() as u64,
+            model,
+            distribution: None,
+            seed: None,
+        };
+        let mut writer = AnswersWriter::new(Vec::new(), &header).unwrap();
+        let mut sum = 0f64;
+        for point in &data.pairs {
+            let distance = model.distance(point);
+            sum += distance;
+            writer.write_distance(distance).unwrap();
+        }
+        writer.finish(sum / data.pairs.len() as f64).unwrap()
+    }
+
+    #[test]
+    fn shards_concatenate_to_the_dataset_and_its_answers() {
+        let data = VALID.data();
+        let (_, values) = answers::decode(&answers_file(&data)).unwrap();
+        let (average, distances) = values.split_last().unwrap();
+        for count in 1..=data.pairs.len() {
+            let shards: Vec<_> = split(&data, NonZeroUsize::new(count).unwrap()).collect();
+            assert_eq!(shards.len(), count);
+            let lengths: Vec<_> = shards.iter().map(|shard| shard.pairs.len()).collect();
+            assert!(lengths.iter().max().unwrap() - lengths.iter().min().unwrap() <= 1);
+            let pairs: Vec<_> = shards
+                .iter()
+                .flat_map(|shard| shard.pairs.clone())
+                .collect();
+            assert_eq!(pairs, data.pairs);
+
+            let mut sharded_distances = Vec::new();
+            let mut averages = Vec::new();
+            for shard in &shards {
+                let (_, values) = answers::decode(&answers_file(shard)).unwrap();
+                let (shard_average, shard_distances) = values.split_last().unwrap();
+                sharded_distances.extend_from_slice(shard_distances);
+                averages.push((shard.pairs.len(), *shard_average));
+            }
+            assert_eq!(sharded_distances, distances);
+            assert!((super::average(averages) - average).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn shard_paths_follow_the_generator_naming() {
+        assert_eq!(
+            path(Path::new("out/data_1000_flex.json"), 2, Format::Binary),
+            Path::new("out/data_1000_part2_flex.bin")
+        );
+        assert_eq!(
+            path(Path::new("pairs.json"), 0, Format::Json),
+            Path::new("pairs_part0.json")
+        );
+    }
+}
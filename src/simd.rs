@@ -0,0 +1,214 @@
+//! `reference_haversine` over `LANES` pairs at a time with `std::simd`.
+//! `std::simd` has no vector `asin` and its `sin`/`cos` call libm lane by lane,
+//! so those are Cephes' polynomial approximations evaluated on whole vectors.
+// The coefficients are quoted as Cephes publishes them
+#![allow(clippy::excessive_precision)]
+
+use std::{
+    f64::consts::FRAC_2_PI,
+    simd::{cmp::SimdPartialEq, cmp::SimdPartialOrd, num::SimdFloat, Select, Simd, StdFloat},
+};
+
+use crate::HaversineDataPoint;
+
+pub const LANES: usize = 4;
+/// Max absolute difference from `reference_haversine` in km on the earth.
+/// The relative difference stays around 1e-15, but near antipodes one ulp of
+/// `a` moves `asin(sqrt(a))`, and so the distance, by up to 0.2 m.
+pub const TOLERANCE: f64 = 1e-3;
+
+type F64s = Simd<f64, LANES>;
+
+/// pi/4 split into three parts (Cody-Waite), the first two exactly
+/// representable when multiplied by small integers
+const DP1: f64 = 7.853_981_256_484_985_351_56E-1;
+const DP2: f64 = 3.774_894_707_930_798_176_68E-8;
+const DP3: f64 = 2.695_151_429_079_059_526_45E-15;
+
+const SIN: [f64; 6] = [
+    1.589_623_015_765_465_680_60E-10,
+    -2.505_074_776_285_780_728_66E-8,
+    2.755_731_362_138_572_452_13E-6,
+    -1.984_126_982_958_953_859_96E-4,
+    8.333_333_333_322_118_588_78E-3,
+    -1.666_666_666_666_663_072_95E-1,
+];
+const COS: [f64; 6] = [
+    -1.135_853_652_138_768_173_00E-11,
+    2.087_570_084_197_473_167_78E-9,
+    -2.755_731_417_929_673_881_12E-7,
+    2.480_158_728_885_170_453_48E-5,
+    -1.388_888_888_887_305_641_16E-3,
+    4.166_666_666_666_659_292_18E-2,
+];
+
+/// asin on `0..=0.625`: x + x^3 P(x^2) / Q(x^2)
+const ASIN_P: [f64; 6] = [
+    4.253_011_369_004_428_248_960E-3,
+    -6.019_598_008_014_123_785_661E-1,
+    5.444_622_390_564_711_410_273E0,
+    -1.626_247_967_210_700_244_449E1,
+    1.956_261_983_317_594_739_197E1,
+    -8.198_089_802_484_824_371_615E0,
+];
+const ASIN_Q: [f64; 5] = [
+    -1.474_091_372_988_853_791_896E1,
+    7.049_610_280_856_842_141_659E1,
+    -1.471_791_292_232_726_029_859E2,
+    1.395_105_614_657_485_689_735E2,
+    -4.918_853_881_490_881_290_097E1,
+];
+/// asin(1 - x) = pi/2 - sqrt(2x) (1 + x R(x) / S(x)) above 0.625
+const ASIN_R: [f64; 5] = [
+    2.967_721_961_301_243_206_100E-3,
+    -5.634_242_780_008_963_776_856E-1,
+    6.968_710_824_104_713_396_794E0,
+    -2.556_901_049_652_824_852_289E1,
+    2.853_665_548_261_061_424_989E1,
+];
+const ASIN_S: [f64; 4] = [
+    -2.194_779_531_642_920_639_778E1,
+    1.470_656_354_026_814_941_758E2,
+    -3.838_770_957_603_691_357_202E2,
+    3.424_398_657_913_078_477_438E2,
+];
+/// low bits of pi/4 beyond `FRAC_PI_4`
+const PIO4_LOW: f64 = 6.123_233_995_736_765_886_130E-17;
+
+/// Polynomial with the coefficients of the highest power first
+fn polynomial(x: F64s, coefficients: &[f64]) -> F64s {
+    coefficients
+        .iter()
+        .fold(F64s::splat(0.0), |acc, &c| acc.mul_add(x, F64s::splat(c)))
+}
+
+/// Same as `polynomial` with an implied leading coefficient of 1
+fn monic_polynomial(x: F64s, coefficients: &[f64]) -> F64s {
+    coefficients
+        .iter()
+        .fold(F64s::splat(1.0), |acc, &c| acc.mul_add(x, F64s::splat(c)))
+}
+
+/// `(sin(x), cos(x))` for `|x| <= pi`
+#[allow(clippy::many_single_char_names)]
+fn sin_cos(x: F64s) -> (F64s, F64s) {
+    // x = q pi/2 + r with |r| <= pi/4
+    let q = (x * F64s::splat(FRAC_2_PI)).round();
+    let y = q * F64s::splat(2.0);
+    let r = ((x - y * F64s::splat(DP1)) - y * F64s::splat(DP2)) - y * F64s::splat(DP3);
+    let z = r * r;
+    let sin = r + r * z * polynomial(z, &SIN);
+    let cos = F64s::splat(1.0) - F64s::splat(0.5) * z + z * z * polynomial(z, &COS);
+
+    #[allow(clippy::cast_possible_truncation)]
+    let quadrant: Simd<i64, LANES> = q.cast::<i64>() & Simd::splat(3);
+    let odd = (quadrant & Simd::splat(1)).simd_eq(Simd::splat(1));
+    let (sin, cos) = (odd.select(cos, sin), odd.select(sin, cos));
+    let negate_sin = (quadrant & Simd::splat(2)).simd_eq(Simd::splat(2));
+    let negate_cos = ((quadrant + Simd::splat(1)) & Simd::splat(2)).simd_eq(Simd::splat(2));
+    (negate_sin.select(-sin, sin), negate_cos.select(-cos, cos))
+}
+
+/// `asin(x)` for `0 <= x <= 1`
+fn asin(x: F64s) -> F64s {
+    let near_one = x.simd_gt(F64s::splat(0.625));
+
+    let zz = x * x;
+    let small = x + x * zz * polynomial(zz, &ASIN_P) / monic_polynomial(zz, &ASIN_Q);
+
+    let w = F64s::splat(1.0) - x;
+    let p = w * polynomial(w, &ASIN_R) / monic_polynomial(w, &ASIN_S);
+    let root = (w + w).sqrt();
+    let pio4 = F64s::splat(std::f64::consts::FRAC_PI_4);
+    let large = (pio4 - root) - (root * p - F64s::splat(PIO4_LOW)) + pio4;
+
+    near_one.select(large, small)
+}
+
+/// `reference_haversine` of `LANES` pairs given as coordinate vectors
+fn haversine(x0: F64s, y0: F64s, x1: F64s, y1: F64s, radius: f64) -> F64s {
+    let to_radians = F64s::splat(std::f64::consts::PI / 180.0);
+    let half = F64s::splat(0.5);
+    let (sin_d_lat, _) = sin_cos((y1 - y0) * to_radians * half);
+    let (sin_d_lon, _) = sin_cos((x1 - x0) * to_radians * half);
+    let (_, cos_lat1) = sin_cos(y0 * to_radians);
+    let (_, cos_lat2) = sin_cos(y1 * to_radians);
+
+    let a = sin_d_lat * sin_d_lat + cos_lat1 * cos_lat2 * (sin_d_lon * sin_d_lon);
+    // rounding can push `a` just past 1 for antipodal points
+    let c = F64s::splat(2.0) * asin(a.simd_min(F64s::splat(1.0)).sqrt());
+    F64s::splat(radius) * c
+}
+
+/// Distances of `points` into `out`, `LANES` pairs at a time. They agree with
+/// `reference_haversine` within `TOLERANCE`.
+///
+/// # Panics
+///
+/// Panics if `out` is shorter than `points`.
+pub fn haversine_batch(points: &[HaversineDataPoint], radius: f64, out: &mut [f64]) {
+    assert!(out.len() >= points.len(), "`out` is shorter than `points`");
+    let lanes = |chunk: &[HaversineDataPoint], field: fn(&HaversineDataPoint) -> f64| {
+        let mut lanes = [0.0; LANES];
+        for (lane, point) in lanes.iter_mut().zip(chunk) {
+            *lane = field(point);
+        }
+        F64s::from_array(lanes)
+    };
+    // The remainder is padded with zeros, so it is computed the same way
+    for (chunk, out) in points.chunks(LANES).zip(out.chunks_mut(LANES)) {
+        let distances = haversine(
+            lanes(chunk, |p| p.p0.lon),
+            lanes(chunk, |p| p.p0.lat),
+            lanes(chunk, |p| p.p1.lon),
+            lanes(chunk, |p| p.p1.lat),
+            radius,
+        );
+        let len = chunk.len();
+        out[..len].copy_from_slice(&distances.as_array()[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{generator, reference_haversine, EARTH_RADIUS};
+
+    /// Max absolute difference, and max relative difference short of antipodes
+    fn max_difference(points: &[HaversineDataPoint]) -> (f64, f64) {
+        let mut out = vec![0.0; points.len()];
+        haversine_batch(points, EARTH_RADIUS, &mut out);
+        let (mut absolute, mut relative) = (0f64, 0f64);
+        for (point, &distance) in points.iter().zip(&out) {
+            let reference = reference_haversine(point, EARTH_RADIUS);
+            absolute = absolute.max((reference - distance).abs());
+            if reference < 19_000.0 {
+                relative = relative.max(((reference - distance) / reference).abs());
+            }
+        }
+        (absolute, relative)
+    }
+
+    #[test]
+    fn matches_reference_on_generated_data() {
+        for data in [
+            generator::uniform(10_001, 1),
+            generator::cluster(10_001, 2),
+            generator::edge_cases(10_001, 3),
+        ] {
+            let (absolute, relative) = max_difference(&data.pairs);
+            assert!(absolute <= TOLERANCE, "{absolute}");
+            assert!(relative <= 1e-14, "{relative}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn matches_reference(points in prop::collection::vec(any::<HaversineDataPoint>(), 0..9)) {
+            let (absolute, _) = max_difference(&points);
+            prop_assert!(absolute <= TOLERANCE, "{}", absolute);
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Two-stage parser for the generator's JSON in the spirit of simdjson. Stage
+//! one finds the tokens 64 bytes at a time with `std::simd` (SSE2 or AVX2
+//! compares and movemasks on `x86_64`): the structural characters and opening
+//! quotes outside strings, and the first byte of every number. Stage two
+//! walks those offsets instead of the bytes and reads the numbers with
+//! Clinger's fast path. Accepts the same documents as
+//! `parse_from_json_slice_scanner`, except keys with escapes.
+
+use std::simd::{cmp::SimdPartialEq, Simd};
+
+use crate::{Coord, HaversineData, HaversineDataPoint};
+
+const BLOCK: usize = 64;
+/// Blocks indexed per refill of the token buffer, so that it stays in L1
+/// however large the input
+const BLOCKS_PER_WINDOW: usize = 64;
+
+type Bytes = Simd<u8, BLOCK>;
+
+/// Bits of `block` equal to `byte`
+fn eq(block: Bytes, byte: u8) -> u64 {
+    block.simd_eq(Bytes::splat(byte)).to_bitmask()
+}
+
+/// Bit `i` is the xor of bits `0..=i`, which turns quote bits into the bits
+/// from each opening quote up to its closing one
+fn prefix_xor(mut bits: u64) -> u64 {
+    for shift in [1, 2, 4, 8, 16, 32] {
+        bits ^= bits << shift;
+    }
+    bits
+}
+
+/// Stage one: offsets of the tokens of a document, found a window at a time
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    /// start of the next block to index
+    block_start: usize,
+    /// the last block ended inside a string, all ones, or outside, zero
+    in_string: u64,
+    /// the last byte of the last block was part of a number
+    in_scalar: u64,
+    offsets: Vec<usize>,
+    next: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            block_start: 0,
+            in_string: 0,
+            in_scalar: 0,
+            offsets: Vec::with_capacity(BLOCK * BLOCKS_PER_WINDOW / 4),
+            next: 0,
+        }
+    }
+
+    /// Indexes the next window. Documents with a backslash are rejected here,
+    /// since none of their keys can have one.
+    fn refill(&mut self) -> Result<(), ()> {
+        self.offsets.clear();
+        self.next = 0;
+        for _ in 0..BLOCKS_PER_WINDOW {
+            let Some(rest) = self.bytes.get(self.block_start..).filter(|r| !r.is_empty()) else {
+                break;
+            };
+            let block = if let Some(block) = rest.first_chunk::<BLOCK>() {
+                Bytes::from_array(*block)
+            } else {
+                // Spaces are neither tokens nor part of them
+                let mut padded = [b' '; BLOCK];
+                padded[..rest.len()].copy_from_slice(rest);
+                Bytes::from_array(padded)
+            };
+            if eq(block, b'\\') != 0 {
+                return Err(());
+            }
+            let quotes = eq(block, b'"');
+            let in_string = prefix_xor(quotes) ^ self.in_string;
+            let structural = (eq(block, b'{')
+                | eq(block, b'}')
+                | eq(block, b'[')
+                | eq(block, b']')
+                | eq(block, b':')
+                | eq(block, b','))
+                & !in_string;
+            let whitespace =
+                eq(block, b' ') | eq(block, b'\n') | eq(block, b'\r') | eq(block, b'\t');
+            let scalar = !(structural | quotes | whitespace | in_string);
+            let scalar_starts = scalar & !(scalar << 1 | self.in_scalar);
+
+            let mut tokens = structural | (quotes & in_string) | scalar_starts;
+            while tokens != 0 {
+                self.offsets
+                    .push(self.block_start + tokens.trailing_zeros() as usize);
+                tokens &= tokens - 1;
+            }
+            self.in_string = 0u64.wrapping_sub(in_string >> 63);
+            self.in_scalar = scalar >> 63;
+            self.block_start += BLOCK;
+        }
+        Ok(())
+    }
+
+    /// Offset and byte of the next token
+    fn next(&mut self) -> Result<(usize, u8), ()> {
+        // a window of whitespace has no tokens
+        while self.next == self.offsets.len() {
+            if self.block_start >= self.bytes.len() {
+                return Err(());
+            }
+            self.refill()?;
+        }
+        let offset = self.offsets[self.next];
+        self.next += 1;
+        Ok((offset, self.bytes[offset]))
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ()> {
+        match self.next()? {
+            (_, token) if token == byte => Ok(()),
+            _ => Err(()),
+        }
+    }
+
+    /// `"key":` for a key of `len` bytes, returned
+    fn key(&mut self, len: usize) -> Result<&'a [u8], ()> {
+        let (offset, byte) = self.next()?;
+        let key = self.bytes.get(offset + 1..offset + 2 + len).ok_or(())?;
+        if byte != b'"' || key[len] != b'"' {
+            return Err(());
+        }
+        self.expect(b':')?;
+        Ok(&key[..len])
+    }
+
+    fn number(&mut self) -> Result<f64, ()> {
+        let (start, _) = self.next()?;
+        let len = self.bytes[start..]
+            .iter()
+            .position(|b| {
+                matches!(
+                    b,
+                    b' ' | b'\n' | b'\r' | b'\t' | b'"' | b'{' | b'}' | b'[' | b']' | b':' | b','
+                )
+            })
+            .unwrap_or(self.bytes.len() - start);
+        parse_number(&self.bytes[start..start + len]).ok_or(())
+    }
+
+    fn point(&mut self) -> Result<HaversineDataPoint, ()> {
+        let mut coordinates = [None; 4];
+        for i in 0..4 {
+            let slot = match self.key(2)? {
+                b"x0" => 0,
+                b"y0" => 1,
+                b"x1" => 2,
+                b"y1" => 3,
+                _ => return Err(()),
+            };
+            coordinates[slot] = Some(self.number()?);
+            self.expect(if i == 3 { b'}' } else { b',' })?;
+        }
+        match coordinates {
+            [Some(x0), Some(y0), Some(x1), Some(y1)] => Ok(HaversineDataPoint {
+                p0: Coord { lon: x0, lat: y0 },
+                p1: Coord { lon: x1, lat: y1 },
+            }),
+            _ => Err(()),
+        }
+    }
+
+    /// Stage two, over the tokens
+    fn data(&mut self) -> Result<HaversineData, ()> {
+        self.expect(b'{')?;
+        if self.key(5)? != b"pairs" {
+            return Err(());
+        }
+        self.expect(b'[')?;
+        let mut pairs = Vec::new();
+        let mut token = self.next()?.1;
+        if token != b']' {
+            loop {
+                if token != b'{' {
+                    return Err(());
+                }
+                pairs.push(self.point()?);
+                match self.next()?.1 {
+                    b',' => token = self.next()?.1,
+                    b']' => break,
+                    _ => return Err(()),
+                }
+            }
+        }
+        self.expect(b'}')?;
+        Ok(HaversineData { pairs })
+    }
+}
+
+/// Powers of ten that are exact in an `f64`
+const POWERS_OF_TEN: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+    1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+/// `text` as `f64::from_str` reads it, for the characters the scanner takes
+/// as a number: `[+-]digits[.digits][(e|E)[+-]digits]` with a digit on at
+/// least one side of the point. When the digits and the power of ten are both
+/// exact in an `f64`, one multiplication or division rounds correctly
+/// (Clinger's fast path); other numbers go through `from_str`.
+fn parse_number(text: &[u8]) -> Option<f64> {
+    let (negative, rest) = match text.split_first() {
+        Some((b'-', rest)) => (true, rest),
+        Some((b'+', rest)) => (false, rest),
+        _ => (false, text),
+    };
+    let mut digits = rest.iter().peekable();
+    let (mut mantissa, mut significant, mut seen, mut exponent) = (0u64, 0, 0, 0i64);
+    let mut exact = true;
+    let mut point = false;
+    while let Some(&&byte) = digits.peek() {
+        match byte {
+            b'0'..=b'9' => {
+                seen += 1;
+                if mantissa != 0 || byte != b'0' {
+                    significant += 1;
+                }
+                if significant > 19 {
+                    exact = false;
+                } else {
+                    mantissa = mantissa * 10 + u64::from(byte - b'0');
+                    exponent -= i64::from(point);
+                }
+            }
+            b'.' if !point => point = true,
+            _ => break,
+        }
+        digits.next();
+    }
+    if seen == 0 {
+        return None;
+    }
+    if let Some(b'e' | b'E') = digits.peek() {
+        digits.next();
+        let sign = match digits.peek() {
+            Some(b'-') => -1,
+            Some(b'+') => 1,
+            _ => 0,
+        };
+        if sign != 0 {
+            digits.next();
+        }
+        let mut value = 0i64;
+        let mut any = false;
+        for &byte in digits.by_ref() {
+            if !byte.is_ascii_digit() {
+                return None;
+            }
+            any = true;
+            value = (value * 10 + i64::from(byte - b'0')).min(1 << 20);
+        }
+        if !any {
+            return None;
+        }
+        exponent += if sign < 0 { -value } else { value };
+    }
+    if digits.next().is_some() {
+        return None;
+    }
+    let power = usize::try_from(exponent.unsigned_abs())
+        .ok()
+        .and_then(|power| POWERS_OF_TEN.get(power));
+    if let (true, Some(&power)) = (exact && mantissa <= 1 << 53, power) {
+        #[allow(clippy::cast_precision_loss)]
+        let mantissa = mantissa as f64;
+        let value = if exponent < 0 {
+            mantissa / power
+        } else {
+            mantissa * power
+        };
+        return Some(if negative { -value } else { value });
+    }
+    // Safety: the bytes matched above are ASCII
+    unsafe { std::str::from_utf8_unchecked(text) }.parse().ok()
+}
+
+impl HaversineData {
+    /// Same input as `parse_from_json_slice_scanner`, parsed in two stages
+    /// with vectorized token finding (see `simd_scan`).
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(())` on malformed JSON, pairs with missing keys or keys
+    /// with escapes.
+    #[allow(clippy::result_unit_err)]
+    pub fn parse_from_json_slice_simd_scan(bytes: &[u8]) -> Result<HaversineData, ()> {
+        Tokens::new(bytes).data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use serde::Serialize;
+
+    use super::parse_number;
+    use crate::HaversineData;
+
+    #[test]
+    fn parses_numbers_like_from_str() {
+        for text in [
+            "0",
+            "-0",
+            "-0.0",
+            "+1",
+            ".5",
+            "5.",
+            "1e5",
+            "1E-5",
+            "2.5e+3",
+            "-87.41560068587911",
+            "20015.086796020572",
+            "9007199254740993",
+            "0.000000000000000000000001",
+            "1e400",
+            "123456789012345678901234567890",
+            "4.9e-324",
+        ] {
+            let expected: f64 = text.parse().unwrap();
+            assert_eq!(
+                parse_number(text.as_bytes()).map(f64::to_bits),
+                Some(expected.to_bits()),
+                "{text}"
+            );
+        }
+        for text in ["", "-", ".", "1e", "1e+", "1.2.3", "--1", "1-", "e5", "1x"] {
+            assert_eq!(parse_number(text.as_bytes()), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn rejects_keys_with_escapes() {
+        let json = br#"{"pairs": [{"x\u0030": 1, "y0": 2, "x1": 3, "y1": 4}]}"#;
+        assert_eq!(
+            HaversineData::parse_from_json_slice_simd_scan(json),
+            Err(())
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn parses_serialized_data(data in any::<HaversineData>(), indent in 0..80usize) {
+            // the indent moves every token across the block boundaries
+            let indent = vec![b' '; indent];
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(&indent);
+            let mut indented = Vec::new();
+            data.serialize(&mut serde_json::Serializer::with_formatter(&mut indented, formatter))
+                .unwrap();
+            for json in [serde_json::to_vec(&data).unwrap(), indented] {
+                let parsed = HaversineData::parse_from_json_slice_simd_scan(&json)
+                    .map(|parsed| parsed.pairs);
+                prop_assert_eq!(parsed.as_ref(), Ok(&data.pairs));
+            }
+        }
+
+        #[test]
+        fn parses_formatted_numbers(value in any::<f64>().prop_filter("finite", |v| v.is_finite())) {
+            for text in [format!("{value}"), format!("{value:e}"), format!("{value:.3}")] {
+                let expected: f64 = text.parse().unwrap();
+                prop_assert_eq!(
+                    parse_number(text.as_bytes()).map(f64::to_bits),
+                    Some(expected.to_bits()),
+                    "{}", text
+                );
+            }
+        }
+
+        #[test]
+        fn parses_long_mantissas(mantissa in any::<u64>(), point in 0..20usize, exponent in -25..25i32) {
+            let digits = mantissa.to_string();
+            let point = point.min(digits.len());
+            let text = format!("{}.{}e{exponent}", &digits[..point], &digits[point..]);
+            let expected: f64 = text.parse().unwrap();
+            prop_assert_eq!(
+                parse_number(text.as_bytes()).map(f64::to_bits),
+                Some(expected.to_bits()),
+                "{}", text
+            );
+        }
+    }
+}
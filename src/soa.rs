@@ -0,0 +1,69 @@
+//! Structure of arrays layout of the pairs, one contiguous array per coordinate.
+
+use crate::{reference_haversine, Coord, HaversineData, HaversineDataPoint};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HaversineSoA {
+    pub x0: Vec<f64>,
+    pub y0: Vec<f64>,
+    pub x1: Vec<f64>,
+    pub y1: Vec<f64>,
+}
+
+impl HaversineSoA {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.x0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.x0.is_empty()
+    }
+
+    #[must_use]
+    pub fn point(&self, index: usize) -> HaversineDataPoint {
+        HaversineDataPoint {
+            p0: Coord {
+                lon: self.x0[index],
+                lat: self.y0[index],
+            },
+            p1: Coord {
+                lon: self.x1[index],
+                lat: self.y1[index],
+            },
+        }
+    }
+
+    /// Sum of `reference_haversine` over all pairs
+    #[must_use]
+    pub fn sum_distances(&self, radius: f64) -> f64 {
+        (0..self.len())
+            .map(|i| reference_haversine(&self.point(i), radius))
+            .sum()
+    }
+}
+
+impl From<&HaversineData> for HaversineSoA {
+    fn from(data: &HaversineData) -> Self {
+        Self::from(data.pairs.as_slice())
+    }
+}
+
+impl From<&[HaversineDataPoint]> for HaversineSoA {
+    fn from(pairs: &[HaversineDataPoint]) -> Self {
+        let mut soa = Self {
+            x0: Vec::with_capacity(pairs.len()),
+            y0: Vec::with_capacity(pairs.len()),
+            x1: Vec::with_capacity(pairs.len()),
+            y1: Vec::with_capacity(pairs.len()),
+        };
+        for point in pairs {
+            soa.x0.push(point.p0.lon);
+            soa.y0.push(point.p0.lat);
+            soa.x1.push(point.p1.lon);
+            soa.y1.push(point.p1.lat);
+        }
+        soa
+    }
+}
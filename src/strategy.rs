@@ -0,0 +1,93 @@
+//! proptest strategies and `Arbitrary` impls for the data types.
+
+use proptest::{arbitrary::Arbitrary, collection::SizeRange, prelude::*, strategy::BoxedStrategy};
+
+use crate::{Coord, HaversineData, HaversineDataPoint, X_HIGH, X_LOW, Y_HIGH, Y_LOW};
+
+/// Longitude in `X_LOW..=X_HIGH`, biased towards the bounds and zero
+pub fn longitude() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        4 => X_LOW..=X_HIGH,
+        1 => prop::sample::select(vec![X_LOW, X_HIGH, 0.0, -0.0]),
+    ]
+}
+
+/// Latitude in `Y_LOW..=Y_HIGH`, biased towards the poles and the equator
+pub fn latitude() -> impl Strategy<Value = f64> {
+    prop_oneof![
+        4 => Y_LOW..=Y_HIGH,
+        1 => prop::sample::select(vec![Y_LOW, Y_HIGH, 0.0, -0.0]),
+    ]
+}
+
+/// Pairs drawn uniformly from the valid coordinate ranges
+pub fn uniform_data_point() -> impl Strategy<Value = HaversineDataPoint> {
+    let point = (X_LOW..=X_HIGH, Y_LOW..=Y_HIGH);
+    (point.clone(), point).prop_map(HaversineDataPoint::from)
+}
+
+/// Pairs in the valid coordinate ranges, biased towards bounds, identical
+/// points and antipodal points
+pub fn data_point() -> impl Strategy<Value = HaversineDataPoint> {
+    prop_oneof![
+        8 => ((longitude(), latitude()), (longitude(), latitude()))
+            .prop_map(HaversineDataPoint::from),
+        1 => (longitude(), latitude()).prop_map(|p| HaversineDataPoint::from((p, p))),
+        1 => (longitude(), latitude()).prop_map(|(x, y)| HaversineDataPoint {
+            p0: Coord { lon: x, lat: y },
+            p1: Coord {
+                lon: if x > 0.0 { x - 180.0 } else { x + 180.0 },
+                lat: -y,
+            },
+        }),
+    ]
+}
+
+pub fn haversine_data(pair_count: impl Into<SizeRange>) -> impl Strategy<Value = HaversineData> {
+    prop::collection::vec(data_point(), pair_count).prop_map(|pairs| HaversineData { pairs })
+}
+
+impl Arbitrary for HaversineDataPoint {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((): Self::Parameters) -> Self::Strategy {
+        data_point().boxed()
+    }
+}
+
+impl Arbitrary for HaversineData {
+    /// number of pairs
+    type Parameters = SizeRange;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(pair_count: Self::Parameters) -> Self::Strategy {
+        haversine_data(pair_count).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{reference_haversine, EARTH_RADIUS};
+
+    proptest! {
+        #[test]
+        fn distance_is_symmetric_and_bounded(point in data_point()) {
+            let swapped = HaversineDataPoint { p0: point.p1, p1: point.p0 };
+            let dist = reference_haversine(&point, EARTH_RADIUS);
+            prop_assert!((dist - reference_haversine(&swapped, EARTH_RADIUS)).abs() < 1e-9);
+            prop_assert!((0.0..=std::f64::consts::PI * EARTH_RADIUS + 1e-9).contains(&dist));
+        }
+
+        #[test]
+        fn generated_data_is_in_range(data in any::<HaversineData>()) {
+            for point in &data.pairs {
+                prop_assert!((X_LOW..=X_HIGH).contains(&point.p0.lon));
+                prop_assert!((X_LOW..=X_HIGH).contains(&point.p1.lon));
+                prop_assert!((Y_LOW..=Y_HIGH).contains(&point.p0.lat));
+                prop_assert!((Y_LOW..=Y_HIGH).contains(&point.p1.lat));
+            }
+        }
+    }
+}
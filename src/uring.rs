@@ -0,0 +1,107 @@
+//! `io_uring` file reader: keeps `queue_depth` fixed-buffer reads in flight and
+//! copies completions into the output in file order.
+
+use std::{fs::File, io, os::fd::AsRawFd};
+
+use io_uring::{opcode, types, IoUring};
+
+/// Bytes requested by each read
+const CHUNK_SIZE: usize = 1 << 20;
+
+struct Slot {
+    buffer: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+/// Reads all of `file` with up to `queue_depth` reads in flight into buffers
+/// registered with the ring.
+///
+/// # Errors
+///
+/// Returns an error if the ring cannot be set up (e.g. `io_uring` is disabled or
+/// `RLIMIT_MEMLOCK` is too low for the registered buffers) or a read fails.
+// Chunks are at most `CHUNK_SIZE` and io_uring caps the ring at 32768 entries,
+// so lengths and slot indices fit the narrower integer types.
+#[allow(clippy::cast_possible_truncation)]
+pub fn read_file(file: &File, queue_depth: u32) -> io::Result<Vec<u8>> {
+    let size = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+    let mut output = vec![0u8; size];
+    let queue_depth = queue_depth.max(1);
+    let mut ring = IoUring::new(queue_depth)?;
+
+    let mut slots: Vec<Slot> = (0..queue_depth)
+        .map(|_| Slot {
+            buffer: vec![0u8; CHUNK_SIZE],
+            offset: 0,
+            len: 0,
+        })
+        .collect();
+    let iovecs: Vec<libc::iovec> = slots
+        .iter_mut()
+        .map(|slot| libc::iovec {
+            iov_base: slot.buffer.as_mut_ptr().cast(),
+            iov_len: slot.buffer.len(),
+        })
+        .collect();
+    // Safety: the buffers outlive the ring, which is dropped first.
+    unsafe { ring.submitter().register_buffers(&iovecs)? };
+
+    let fd = types::Fd(file.as_raw_fd());
+    let mut next_offset = 0;
+    let mut in_flight = 0;
+    let submit = |ring: &mut IoUring, index: usize, slot: &mut Slot| -> io::Result<()> {
+        let entry =
+            opcode::ReadFixed::new(fd, slot.buffer.as_mut_ptr(), slot.len as u32, index as u16)
+                .offset(slot.offset as u64)
+                .build()
+                .user_data(index as u64);
+        // Safety: the slot's buffer stays alive and untouched until its completion.
+        unsafe { ring.submission().push(&entry) }.map_err(io::Error::other)
+    };
+
+    for (index, slot) in slots.iter_mut().enumerate() {
+        if next_offset >= size {
+            break;
+        }
+        slot.offset = next_offset;
+        slot.len = CHUNK_SIZE.min(size - next_offset);
+        next_offset += slot.len;
+        submit(&mut ring, index, slot)?;
+        in_flight += 1;
+    }
+
+    while in_flight > 0 {
+        ring.submit_and_wait(1)?;
+        let completions: Vec<(usize, i32)> = ring
+            .completion()
+            .map(|cqe| (cqe.user_data() as usize, cqe.result()))
+            .collect();
+        for (index, result) in completions {
+            in_flight -= 1;
+            let slot = &mut slots[index];
+            let read =
+                usize::try_from(result).map_err(|_| io::Error::from_raw_os_error(-result))?;
+            if read == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            output[slot.offset..slot.offset + read].copy_from_slice(&slot.buffer[..read]);
+
+            if read < slot.len {
+                // short read, request the rest of the chunk
+                slot.offset += read;
+                slot.len -= read;
+            } else if next_offset < size {
+                slot.offset = next_offset;
+                slot.len = CHUNK_SIZE.min(size - next_offset);
+                next_offset += slot.len;
+            } else {
+                continue;
+            }
+            submit(&mut ring, index, slot)?;
+            in_flight += 1;
+        }
+    }
+    drop(ring);
+    Ok(output)
+}
@@ -0,0 +1,211 @@
+//! Answers files read back and checked against the distances of a dataset,
+//! the validation of the processor with everything it finds instead of only
+//! the first mismatch.
+
+use std::path::Path;
+
+use crate::{
+    answers::{self, AnswersError, AnswersHeader},
+    compute::{DistanceSummary, Mismatch, ValidationError, ValidationReport},
+    pipeline::{io_error, PipelineError},
+    EarthModel, HaversineDataPoint,
+};
+
+/// The distances of an answers file (see [`crate::answers`]) and the average
+/// that follows them
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnswerFile {
+    /// `None` for a legacy file
+    pub header: Option<AnswersHeader>,
+    values: Vec<f64>,
+}
+
+impl AnswerFile {
+    /// Reads an answers file, or a `legacy` one without the header: one
+    /// distance per pair followed by the average
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PipelineError::Io`] if the file cannot be read and
+    /// [`PipelineError::Answers`] if it is not a valid answers file.
+    pub fn read(path: &Path, legacy: bool) -> Result<Self, PipelineError> {
+        let bytes = std::fs::read(path).map_err(io_error(path))?;
+        Self::decode(&bytes, legacy).map_err(|error| PipelineError::Answers {
+            path: path.to_owned(),
+            error,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if `bytes` are not a valid answers file, never for
+    /// `legacy` ones.
+    pub fn decode(bytes: &[u8], legacy: bool) -> Result<Self, AnswersError> {
+        if legacy {
+            return Ok(Self {
+                header: None,
+                values: answers::decode_legacy(bytes),
+            });
+        }
+        let (header, values) = answers::decode(bytes)?;
+        Ok(Self {
+            header: Some(header),
+            values,
+        })
+    }
+
+    /// The distances followed by the average, as `compute::validate_against`
+    /// takes them
+    #[must_use]
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// One per pair
+    #[must_use]
+    pub fn distances(&self) -> &[f64] {
+        self.values
+            .split_last()
+            .map_or(&[], |(_, distances)| distances)
+    }
+
+    /// `None` for an empty legacy file
+    #[must_use]
+    pub fn average(&self) -> Option<f64> {
+        self.values.last().copied()
+    }
+
+    /// Computes the distances of `pairs` on `model` in order, like the scalar
+    /// backend, and compares each to its answer and their average to the
+    /// reference one, recording every pair off by more than `tolerance`
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ValidationError::AnswersExhausted`] if there are fewer answers
+    /// than pairs + average.
+    #[perf::instrument]
+    pub fn validate(
+        &self,
+        pairs: &[HaversineDataPoint],
+        model: EarthModel,
+        tolerance: f64,
+    ) -> Result<ValidationOutcome, ValidationError> {
+        if self.values.len() <= pairs.len() {
+            return Err(ValidationError::AnswersExhausted);
+        }
+        let mut sum = 0f64;
+        let (mut max_deviation, mut max_deviation_index) = (0f64, None);
+        let mut mismatches = Vec::new();
+        for (index, (point, &expected)) in pairs.iter().zip(&self.values).enumerate() {
+            let computed = model.distance(point);
+            sum += computed;
+            let deviation = (computed - expected).abs();
+            if deviation > max_deviation || max_deviation_index.is_none() {
+                (max_deviation, max_deviation_index) = (deviation, Some(index));
+            }
+            if deviation > tolerance {
+                mismatches.push(Mismatch {
+                    index,
+                    point: *point,
+                    computed,
+                    expected,
+                });
+            }
+        }
+        let summary = DistanceSummary::new(pairs.len(), sum);
+        let ValidationReport {
+            reference_average,
+            difference,
+        } = ValidationReport::new(&self.values, &summary)?;
+        Ok(ValidationOutcome {
+            summary,
+            reference_average,
+            difference,
+            max_deviation,
+            max_deviation_index,
+            mismatches,
+        })
+    }
+}
+
+/// Everything `AnswerFile::validate` found
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationOutcome {
+    pub summary: DistanceSummary,
+    /// the average of the answers file
+    pub reference_average: f64,
+    /// `reference_average - summary.average`
+    pub difference: f64,
+    /// largest `|computed - expected|` of a pair, zero without pairs
+    pub max_deviation: f64,
+    /// the pair of `max_deviation`, its first one on ties
+    pub max_deviation_index: Option<usize>,
+    /// pairs off by more than the tolerance, in order
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl ValidationOutcome {
+    /// No pair was off by more than the tolerance
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// The averages alone, as `RunReport::validation` holds them
+    #[must_use]
+    pub fn report(&self) -> ValidationReport {
+        ValidationReport {
+            reference_average: self.reference_average,
+            difference: self.difference,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{answers::AnswersWriter, fixtures::VALID};
+
+    #[test]
+    fn validation_records_every_mismatch() {
+        let pairs = VALID.data().pairs;
+        let file = AnswerFile::decode(VALID.answers, false).unwrap();
+        let header = file.header.unwrap();
+        assert_eq!(file.distances().len(), pairs.len());
+        let outcome = file.validate(&pairs, header.model, 1e-10).unwrap();
+        assert!(outcome.passed());
+        assert_eq!(
+            outcome.reference_average.to_bits(),
+            file.average().unwrap().to_bits()
+        );
+        assert!(outcome.max_deviation <= 1e-10);
+
+        let mut distances = file.distances().to_vec();
+        distances[1] += 0.5;
+        distances[2] -= 2.0;
+        let mut writer = AnswersWriter::new(Vec::new(), &header).unwrap();
+        for &distance in &distances {
+            writer.write_distance(distance).unwrap();
+        }
+        let bytes = writer.finish(file.average().unwrap()).unwrap();
+        let file = AnswerFile::decode(&bytes, false).unwrap();
+        let outcome = file.validate(&pairs, header.model, 1.0).unwrap();
+        assert_eq!(outcome.max_deviation_index, Some(2));
+        assert!((outcome.max_deviation - 2.0).abs() < 1e-6);
+        assert!(!outcome.passed());
+        let indices: Vec<usize> = outcome.mismatches.iter().map(|m| m.index).collect();
+        assert_eq!(indices, [2]);
+
+        let legacy = AnswerFile::decode(&distances_bytes(&distances[..3]), true).unwrap();
+        assert_eq!(legacy.header, None);
+        assert_eq!(legacy.average(), Some(distances[2]));
+        assert_eq!(
+            legacy.validate(&pairs, header.model, 1.0),
+            Err(ValidationError::AnswersExhausted)
+        );
+    }
+
+    fn distances_bytes(distances: &[f64]) -> Vec<u8> {
+        distances.iter().flat_map(|d| d.to_le_bytes()).collect()
+    }
+}
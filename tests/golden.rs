@@ -0,0 +1,263 @@
+//! Checks fixed seeded datasets against checked-in distances and averages for
+//! every pipeline backend combination, streamed or not. After an intended
+//! numerical change, regenerate the golden files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden`.
+
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use haversine::{
+    answers::{AnswersHeader, AnswersWriter, Distribution},
+    format::Format,
+    generator::{self, Bounds, ExponentialParams, GaussianParams, GeneratorConfig, HubParams},
+    pipeline::{
+        ComputeBackend, ParserBackend, Pipeline, PipelineError, PipelineOptions, ReadStrategy,
+    },
+    reference_haversine, simd, EarthModel, HaversineData, PairBuffer, EARTH_RADIUS,
+};
+
+type Generator = fn(usize, u64) -> HaversineData;
+
+const DATASETS: [(&str, Generator, u64); 6] = [
+    ("uniform_1", generator::uniform, 1),
+    ("cluster_2", generator::cluster, 2),
+    (
+        "gaussian_3",
+        |n, seed| generator::gaussian(n, seed, GaussianParams::default()),
+        3,
+    ),
+    (
+        "exponential_4",
+        |n, seed| generator::exponential(n, seed, ExponentialParams::default()),
+        4,
+    ),
+    ("edge_cases_5", generator::edge_cases, 5),
+    (
+        "hub_6",
+        |n, seed| generator::hub(n, seed, HubParams::default()),
+        6,
+    ),
+];
+const READ_STRATEGIES: &[ReadStrategy] = &[
+    ReadStrategy::Mmap,
+    ReadStrategy::Read,
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    ReadStrategy::IoUring,
+];
+const PARSERS: &[ParserBackend] = &[
+    ParserBackend::Nom,
+    ParserBackend::Serde,
+    #[cfg(feature = "simd-json")]
+    ParserBackend::Simd,
+    #[cfg(feature = "simd-parse")]
+    ParserBackend::SimdScan,
+];
+const COMPUTE_BACKENDS: [ComputeBackend; 2] = [ComputeBackend::Scalar, ComputeBackend::Simd];
+const PAIR_COUNT: usize = 32;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.txt"))
+}
+
+/// One distance per line followed by the average, in round-trippable `{:?}` form
+fn expected_answers(name: &str, data: &HaversineData) -> Vec<f64> {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        let distances: Vec<f64> = data
+            .pairs
+            .iter()
+            .map(|point| reference_haversine(point, EARTH_RADIUS))
+            .collect();
+        #[allow(clippy::cast_precision_loss)]
+        let average = distances.iter().sum::<f64>() / distances.len() as f64;
+        let mut golden = String::new();
+        for value in distances.iter().chain([&average]) {
+            writeln!(golden, "{value:?}").unwrap();
+        }
+        std::fs::write(&path, golden).unwrap();
+    }
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+        .lines()
+        .map(|line| line.parse().unwrap())
+        .collect()
+}
+
+/// `answers` (distances and the average) as an answers file
+fn answers_file(answers: &[f64]) -> Vec<u8> {
+    let (average, distances) = answers.split_last().unwrap();
+    let header = AnswersHeader {
+        count: distances.len() as u64,
+        model: EarthModel::SPHERE_DEFAULT,
+        distribution: None,
+        seed: None,
+    };
+    let mut writer = AnswersWriter::new(Vec::new(), &header).unwrap();
+    for &distance in distances {
+        writer.write_distance(distance).unwrap();
+    }
+    writer.finish(*average).unwrap()
+}
+
+#[test]
+fn golden_values() {
+    let dir = std::env::temp_dir().join(format!("haversine-golden-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    for (name, generate, seed) in DATASETS {
+        let data = generate(PAIR_COUNT, seed);
+        let answers = expected_answers(name, &data);
+        assert_eq!(answers.len(), PAIR_COUNT + 1, "{name}");
+
+        let input = dir.join(format!("{name}.json"));
+        std::fs::write(&input, serde_json::to_vec(&data).unwrap()).unwrap();
+        let answers_path = dir.join(format!("{name}.f64"));
+        std::fs::write(&answers_path, answers_file(&answers)).unwrap();
+
+        for &read in READ_STRATEGIES {
+            for &parser in PARSERS {
+                for (compute, stream) in COMPUTE_BACKENDS
+                    .into_iter()
+                    .flat_map(|c| [(c, false), (c, true)])
+                {
+                    let pipeline = Pipeline::new(PipelineOptions {
+                        read,
+                        parser,
+                        compute,
+                        stream,
+                        answers: Some(answers_path.clone()),
+                        tolerance: 0.0,
+                        ..PipelineOptions::default()
+                    });
+                    let report = pipeline.run(&input).unwrap_or_else(|e| {
+                        panic!("{name} {read:?} {parser:?} {compute:?} stream={stream}: {e}")
+                    });
+                    let case = format!("{name} {read:?} {parser:?} {compute:?} stream={stream}");
+                    if compute == ComputeBackend::Simd {
+                        // the vectorized math is only within `simd::TOLERANCE` of the reference
+                        let difference = (report.average - answers[PAIR_COUNT]).abs();
+                        assert!(difference <= simd::TOLERANCE, "{case}: {difference}");
+                    } else {
+                        assert_eq!(
+                            report.average.to_bits(),
+                            answers[PAIR_COUNT].to_bits(),
+                            "{case}"
+                        );
+                    }
+                }
+            }
+        }
+
+        let binary = dir.join(format!("{name}.bin"));
+        let mut bytes = Vec::new();
+        data.write_binary(&mut bytes).unwrap();
+        std::fs::write(&binary, bytes).unwrap();
+        let report = Pipeline::new(PipelineOptions {
+            format: Format::Binary,
+            answers: Some(answers_path.clone()),
+            tolerance: 0.0,
+            ..PipelineOptions::default()
+        })
+        .run(&binary)
+        .unwrap_or_else(|e| panic!("{name} binary: {e}"));
+        assert_eq!(
+            report.average.to_bits(),
+            answers[PAIR_COUNT].to_bits(),
+            "{name} binary"
+        );
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn threaded_sum_is_deterministic() {
+    let dir = std::env::temp_dir().join(format!("haversine-threads-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (name, generate, seed) = DATASETS[0];
+    let data = generate(PAIR_COUNT, seed);
+    let mut answers = expected_answers(name, &data);
+    let input = dir.join(format!("{name}.json"));
+    std::fs::write(&input, serde_json::to_vec(&data).unwrap()).unwrap();
+    let answers_path = dir.join(format!("{name}.f64"));
+    let write_answers = |answers: &[f64]| {
+        std::fs::write(&answers_path, answers_file(answers)).unwrap();
+    };
+    write_answers(&answers);
+
+    let pipeline = Pipeline::new(PipelineOptions {
+        threads: 3.try_into().unwrap(),
+        answers: Some(answers_path.clone()),
+        tolerance: 0.0,
+        ..PipelineOptions::default()
+    });
+    let first = pipeline.run(&input).unwrap();
+    let mut buffer = PairBuffer::default();
+    assert_eq!(pipeline.run_with(&input, &mut buffer).unwrap(), first);
+    let capacity = buffer.capacity();
+    assert!(capacity >= PAIR_COUNT);
+    assert_eq!(pipeline.run_with(&input, &mut buffer).unwrap(), first);
+    assert_eq!(buffer.capacity(), capacity);
+    assert!((first.average - answers[PAIR_COUNT]).abs() < 1e-9);
+
+    // The mismatch in the first chunk is reported, not the one in the last
+    answers[PAIR_COUNT - 1] += 1.0;
+    answers[1] += 1.0;
+    write_answers(&answers);
+    assert!(matches!(
+        pipeline.run(&input),
+        Err(PipelineError::Validation { index: 1, .. })
+    ));
+    // Collected, both are reported in pair order and the run completes
+    let report = Pipeline::new(PipelineOptions {
+        collect_mismatches: true,
+        ..pipeline.options().clone()
+    })
+    .run(&input)
+    .unwrap();
+    let indices: Vec<_> = report.mismatches.iter().map(|m| m.index).collect();
+    assert_eq!(indices, [1, PAIR_COUNT - 1]);
+    assert!((report.mismatches[0].difference() - 1.0).abs() < 1e-6);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn generator_config_matches_golden_datasets() {
+    let distributions = [
+        Distribution::Uniform,
+        Distribution::Cluster,
+        Distribution::Gaussian,
+        Distribution::Exponential,
+        Distribution::EdgeCases,
+        Distribution::Hub,
+    ];
+    for ((name, generate, seed), distribution) in DATASETS.into_iter().zip(distributions) {
+        let config = GeneratorConfig::new(distribution, PAIR_COUNT).seed(seed);
+        assert_eq!(
+            config.generate().pairs,
+            generate(PAIR_COUNT, seed).pairs,
+            "{name}"
+        );
+    }
+
+    let bounds = Bounds {
+        x: (-10.0, 10.0),
+        y: (40.0, 50.0),
+    };
+    for distribution in [&distributions[..4], &distributions[5..]].concat() {
+        let data = GeneratorConfig::new(distribution, 1000)
+            .bounds(bounds)
+            .generate();
+        assert!(data.pairs.iter().all(|p| {
+            [p.p0.lon, p.p1.lon]
+                .iter()
+                .all(|x| (-10.0..=10.0).contains(x))
+                && [p.p0.lat, p.p1.lat]
+                    .iter()
+                    .all(|y| (40.0..=50.0).contains(y))
+        }));
+    }
+}